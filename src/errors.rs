@@ -11,6 +11,50 @@ pub enum PebbleQueryError {
     InvalidField(String),
     #[error("Missing value: {0}")]
     MissingValue(String),
+    #[error("Too many conditions: {0}")]
+    TooManyConditions(String),
+    #[error("IN list too large: {0}")]
+    InListTooLarge(String),
+    #[error("Page length too large: {0}")]
+    PageLengthTooLarge(String),
+    #[error("Offset too large: {0}")]
+    OffsetTooLarge(String),
+    #[error("Leading wildcard LIKE: {0}")]
+    LeadingWildcardLike(String),
+    #[error("Scope error: {0}")]
+    ScopeError(String),
+    #[error("Deleted rows not allowed: {0}")]
+    DeletedRowsNotAllowed(String),
+    #[error("Empty filter: {0}")]
+    EmptyFilter(String),
+    #[error("Export error: {0}")]
+    ExportError(String),
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Invalid grouping: {0}")]
+    InvalidGrouping(String),
+    #[error("Invalid include: {0}")]
+    InvalidInclude(String),
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    #[error("Cache error: {0}")]
+    CacheError(String),
+    #[error("Unknown entity: {0}")]
+    UnknownEntity(String),
     #[error("SeaOrmDbError: {0}")]
     SeaOrmDbError(#[from] DbErr),
 }
+
+/// Convert a PebbleQueryError back into a SeaOrm `DbErr` for the functions that return `DbErr`, e.g. `use_pebble_query`.
+///
+/// Database errors are returned as they are, the other errors as `DbErr::Custom` with their message.
+impl From<PebbleQueryError> for DbErr {
+    fn from(err: PebbleQueryError) -> Self {
+        match err {
+            PebbleQueryError::SeaOrmDbError(db_err) => db_err,
+            err => DbErr::Custom(err.to_string()),
+        }
+    }
+}
@@ -25,23 +25,27 @@
 //! You can do:
 //!
 //!
-//! ### 1. Without Pebble Query (pseudo-code)
+//! ### 1. Without Pebble Query
 //!
 //! A typical SeaOrm DSL query looks like this which is not easily reusable:
 //!
 //! ```rust
-//! let db = conn();
-//! let book_ids: Vec<i32> = vec![1, 2, 3];
+//! # use pebble_query::sample_entities::{author, book};
+//! # use sea_orm::prelude::Expr;
+//! # use sea_orm::sea_query::IntoCondition;
+//! # use sea_orm::{DatabaseConnection, DbErr, EntityTrait, JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Select};
+//! # async fn example(db: &DatabaseConnection) -> Result<(), DbErr> {
+//! let author_ids: Vec<i32> = vec![1, 2, 3];
 //!
 //! // construct query
-//! let select: Select<Book> = book::Entity::find()
-//!    .inner_join(author::Entity)
-//!    .filter(
-//!     Expr::col((document::Entity, document::Column::Id)).is_in(book_ids).into_condition() // filter by book ids
-//!    )
-//!    .filter(
-//!     Expr::col((book::Entity, book::Column::Title)) // contains "sea"
-//!         .contains("sea")
+//! let select: Select<book::Entity> = book::Entity::find()
+//!     .join(JoinType::InnerJoin, book::Relation::Author.def())
+//!     .filter(
+//!         Expr::col((author::Entity, author::Column::Id)).is_in(author_ids).into_condition() // filter by author ids
+//!     )
+//!     .filter(
+//!         Expr::col((book::Entity, book::Column::Title)) // contains "sea"
+//!             .like("%sea%")
 //!             .and(Expr::col((book::Entity, book::Column::PublicationYear)) // published after 1976
 //!                 .gt(1976))
 //!     )
@@ -53,82 +57,106 @@
 //!
 //! // another trip to the database to get the pagination information such as total number of items and pages.
 //! let pagination_info = select
-//!    .paginate(db, 10)
-//!    .num_items_and_pages().await?;
-//!
+//!     .paginate(db, 10)
+//!     .num_items_and_pages().await?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! ### 2. With Pebble Query
 //!
 //! For each entity, in order to use Pebble Query, all that you need to write anew is a mapper between the queryable field names and SeaOrm Columns. For example:
 //! ```rust
-//!let book_column_map: HashMap<String, (book::Entity, book::Entity::Column)> = std::collections::HashMap::from([
-//!                        ("id".to_string(), (book::Entity, book::Column::Id)),
-//!                        ("title".to_string(), (book::Entity, book::Column::Title)),
-//!                        ("publication_year".to_string(), (book::Entity, book::Column::PublicationYear)),
-//!                        // and so on to map all the fields you want to be able to query
-//!                    ].into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+//! # use std::collections::HashMap;
+//! # use pebble_query::sample_entities::book;
+//! let book_column_map: HashMap<String, (book::Entity, book::Column)> = [
+//!     ("id", (book::Entity, book::Column::Id)),
+//!     ("title", (book::Entity, book::Column::Title)),
+//!     ("publication_year", (book::Entity, book::Column::PublicationYear)),
+//!     // and so on to map all the fields you want to be able to query
+//! ].into_iter().map(|(k, v)| (k.to_string(), v)).collect();
 //! ```
 //!
 //! ```rust
+//! # use pebble_query::sample_dto_structs::{SearchCondition, SearchFilter, SearchOperator, SearchQuery, SearchSortOption, SortDirection};
 //! // then you can populate the standard SearchQuery DTO struct with the same query as above. Our example below is handwritten, but usually is generated and fed to SeaOrm backend. In fact, you can simply provide None for the `SearchQuery` parameter to Pebble Query and it will return all the results filtered by your initial `Select<T>`.
 //! let query = SearchQuery {
-//!             sort: Some(SearchSortOption {
+//!     sort: Some(SearchSortOption {
+//!         field: "title".to_string(),
+//!         order: SortDirection::Asc as i32,
+//!         ..Default::default()
+//!     }),
+//!     offset: 10,
+//!     length: 10,
+//!     page: 0,
+//!     filter: Some(SearchFilter {
+//!         must: vec![
+//!             SearchCondition {
 //!                 field: "title".to_string(),
-//!                 order: SortDirection::Asc as i32,
-//!             }),
-//!             offset: 10,
-//!             length: 10,
-//!             page: 0,
-//!             filter: Some(SearchFilter {
-//!                 must: vec![
-//!                     SearchCondition {
-//!                         field: "title".to_string(),
-//!                             operator: SearchOperator::Contains as i32,
-//!                         value: Some("sea".to_string()),
-//!                         ..Default::default()
-//!                     },
-//!                     SearchCondition {
-//!                             field: "publication_year".to_string(),
-//!                             operator: SearchOperator::GreaterThan as i32,
-//!                             value: Some("1976".to_string()),
-//!                             ..Default::default()
-//!                     },
-//!                 ],
+//!                 operator: SearchOperator::Contains as i32,
+//!                 value: Some("sea".to_string()),
 //!                 ..Default::default()
-//!             }),
-//!             ..Default::default()
-//!         };
+//!             },
+//!             SearchCondition {
+//!                 field: "publication_year".to_string(),
+//!                 operator: SearchOperator::GreaterThan as i32,
+//!                 value: Some("1976".to_string()),
+//!                 ..Default::default()
+//!             },
+//!         ],
+//!         ..Default::default()
+//!     }),
+//!     ..Default::default()
+//! };
 //! ```
 //!
 //! Then you can simply do:
 //!
 //! ```rust
-//! let db = conn();
-//! let book_ids: Vec<i32> = vec![1, 2, 3];
-//! let select: Select<Book> = book::Entity::find()
-//!    .inner_join(author::Entity)
-//!    .filter(
-//!     Expr::col((document::Entity, document::Column::Id)).is_in(book_ids).into_condition() // filter by book ids
-//!    );
+//! # use pebble_query::pebble_query::use_pebble_query;
+//! # use pebble_query::pebble_query_result::PebbleQueryResult;
+//! # use pebble_query::sample_dto_structs::SearchQuery;
+//! # use pebble_query::sample_entities::{author, book, BOOK_COLUMN_MAP};
+//! # use sea_orm::prelude::Expr;
+//! # use sea_orm::sea_query::IntoCondition;
+//! # use sea_orm::{DatabaseConnection, DbErr, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait, Select};
+//! # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>) -> Result<(), DbErr> {
+//! let author_ids: Vec<i32> = vec![1, 2, 3];
+//! let select: Select<book::Entity> = book::Entity::find()
+//!     .join(JoinType::InnerJoin, book::Relation::Author.def())
+//!     .filter(
+//!         Expr::col((author::Entity, author::Column::Id)).is_in(author_ids).into_condition() // filter by author ids
+//!     );
 //! let results: PebbleQueryResult<book::Entity> = use_pebble_query(select, query, &BOOK_COLUMN_MAP, db).await?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! Or use fluent syntax:
 //!
 //! ```rust
-//!let db = conn();
-//!let result = book::Entity::find()
-//!    .inner_join(author::Entity)
-//!    .filter(
-//!     Expr::col((document::Entity, document::Column::Id)).is_in(doc_ids).into_condition()
-//!    )
-//!    .pebble_query(query, &BOOK_COLUMN_MAP, db).await?; // add this to your existing `Select`.
+//! # use pebble_query::pebble_query::RunQueryExt;
+//! # use pebble_query::sample_dto_structs::SearchQuery;
+//! # use pebble_query::sample_entities::{author, book, BOOK_COLUMN_MAP};
+//! # use sea_orm::prelude::Expr;
+//! # use sea_orm::sea_query::IntoCondition;
+//! # use sea_orm::{DatabaseConnection, DbErr, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait};
+//! # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>, author_ids: Vec<i32>) -> Result<(), DbErr> {
+//! let result = book::Entity::find()
+//!     .join(JoinType::InnerJoin, book::Relation::Author.def())
+//!     .filter(
+//!         Expr::col((author::Entity, author::Column::Id)).is_in(author_ids).into_condition()
+//!     )
+//!     .pebble_query(query, &BOOK_COLUMN_MAP, db).await?; // add this to your existing `Select`.
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! The query result, with pagination information will be returned in these structs, ready to be returned to the frontend.
 //!
 //! ```rust
+//! # use pebble_query::sample_dto_structs::SearchQuery;
+//! # use sea_orm::EntityTrait;
 //! // The result will contain the following information:
 //! pub struct PebbleQueryResult<T: EntityTrait> {
 //!     pub metadata: SearchResultMetadata,
@@ -145,6 +173,8 @@
 //!     pub query: Option<SearchQuery>,
 //!     pub filter_count: Option<i32>,
 //!     pub filter_reason: Option<String>,
+//!     pub cache_hit: Option<bool>,
+//!     pub total_items_accuracy: i32,
 //! }
 //! ```
 //!
@@ -152,8 +182,56 @@
 //!
 //! For new entities, you only need to write a new mapper.
 //!
+//! `use_pebble_query` and `pebble_query` return SeaOrm's `DbErr`, with invalid fields and conditions as `DbErr::Custom`. `try_use_pebble_query` and `try_pebble_query` return a `PebbleQueryError` instead, which tells the client's mistakes apart from the database errors.
+//!
+//! By default the data and the pagination information are fetched with two queries, one after the other. On remote databases, use `use_pebble_query_with_options` with `PebbleExecutionMode::Concurrent` to run them at the same time, or `PebbleExecutionMode::WindowCount` to fetch both in a single query with `COUNT(*) OVER ()`. On very large tables, set `PebbleQueryOptions::count_strategy` to `PebbleCountStrategy::Estimated`, the row estimate of `EXPLAIN` on Postgres, or `PebbleCountStrategy::Capped(n)` to avoid a full `COUNT(*)`; `total_items_accuracy` in the metadata tells whether the total is exact, estimated or a lower bound.
+//!
+//! For multi-tenant tables, put the field map into a `PebbleQueryConfig` and register the mandatory conditions with `with_scope`, e.g. `tenant_id = current_tenant` computed from the request context. `use_pebble_query_with_config` always ANDs them into the select, and the client's `any` conditions cannot bypass them. Declare a soft-delete column with `with_soft_delete` to filter out deleted rows unless the query sets `include_deleted` or `only_deleted` and `PebbleFieldPolicy::allow_deleted` permits it.
+//!
+//! For nullable sort fields, set `nulls` on the `SearchSortOption` to `SortNulls::First` or `SortNulls::Last` to place the rows without a value consistently on every database. It is written as `NULLS FIRST`/`NULLS LAST` on Postgres and SQLite, and emulated with an `IS NULL` sort key on MySQL; cursors from `pebble_connection` page through the NULLs where they were placed.
+//!
+//! To sort user-facing text naturally, e.g. "apple" before "Zebra", set `case_insensitive` on the `SearchSortOption`, or give the field a `PebbleSortCollation` in `PebbleFieldPolicy::sort_collations`: `CaseInsensitive` sorts by `LOWER(field)` on every database, and `Collate("NOCASE")` on SQLite or `Collate("\"und-x-icu\"")` on Postgres sorts by `field COLLATE ...`. `case_insensitive` only lowercases string columns and the computed fields registered with `with_text_computed_field`, so that numbers and dates keep their order. The collations are looked up by the snake case field name like the field map, and are written into the SQL as is, so they are only taken from the configuration, never from the client.
+//!
+//! To sort or filter by something other than a column, e.g. `LENGTH(title)`, a `CASE` priority mapping or a full-text rank, register the SeaQuery expression under a field name with `PebbleQueryConfig::with_computed_field`, or `with_text_computed_field` if its values are strings. Clients use it like any other field with every `_with_config` function, including the bulk, grouped, stream, GraphQL and tonic ones; values that parse as numbers or booleans are compared as such. Queries sorted by a computed field are paged with `offset`, as cursors cannot be built from it.
+//!
+//! For domain operators that are not in `SearchOperator`, e.g. "within N days of" or "has tag", register a closure building the SeaQuery condition from the field's expression and the `SearchCondition` with `PebbleQueryConfig::with_custom_operator(code, name, ...)`, which panics if the code is one of `SearchOperator`, or `try_with_custom_operator`, which returns `InvalidOperator`. Clients send the code in `operator`, or the name in `operator_name`, e.g. from GraphQL or TypeScript; it is looked up in the `PebbleOperatorRegistry` of the field policy before the condition is rejected with `InvalidOperator`. `Unspecified` and the `SearchOperator` variants without an implementation are rejected as well. The custom operators work with every `_with_config` function and are listed by `query_json_schema_with_config`.
+//!
+//! For rolling windows, e.g. "the last 7 days" or "this month", send relative values in `value` and `value_to` of date and time fields: an anchor (`now`, `today`, `start_of_week`, `end_of_month`, ...) followed by offsets such as `-7d` or `+1M`. They are resolved by `resolve_relative_times` when the query runs, against `PebbleQueryOptions::clock`, which can be set to `PebbleClock::fixed` in tests; the `_with_config` functions, including the bulk and grouped ones, use the clock of the configuration's options. Days, weeks and months start in the time zone of the clock; date-time columns without a time zone are taken to hold UTC times, and the resolved times are converted to UTC for them. `metadata.query` echoes the values unresolved, so saved filters stay relative. Offsets above `MAX_OFFSET_AMOUNT` or beyond the supported range of times are rejected with `InvalidValue`.
+//!
+//! For metadata kept in JSON columns, map a `meta.*` field to the column: clients then filter by `meta.color` or `meta.dims.width`, and numeric keys such as `meta.tags.0` index arrays. Values that parse as numbers or booleans are only compared with the JSON numbers or booleans at the path, and quoted values such as `"02134"` are always compared as strings. `Contains` tests whether the array at the path holds the value. The paths are read with the JSON functions of the database the query runs on, `json_extract` on SQLite and MySQL and `#>>` on Postgres, where the column must be `jsonb`; `PebbleQueryConfig::with_json_dialect` overrides it. The schemas describe these fields with a `^meta\..+` pattern, and the TypeScript definitions with `` `meta.${string}` ``.
+//!
+//! `map_into_generic_with`, `try_map_into_generic`, `map_filter_result_with`, `try_filter_map`, `try_map`, `map_async` and `filter_map_async` map the results with closures that can capture context, fail, or be async, e.g. to enrich the rows with another query. Their filtered out rows are added to `filter_count` and the reasons are joined into `filter_reason`, so chained filters are all accounted for, while `map_into_generic` and `map_filter_result` replace them.
+//!
+//! To avoid a follow-up query per row for related entities, register the relations clients may ask for in `PebbleIncludes` with `with_include` or `with_include_via`, and run `use_pebble_query_with_includes`. The names in `SearchQuery.include` are validated against the registered relations, and each relation is batch-loaded for the whole page with SeaOrm's `LoaderTrait`. The related models are returned as JSON next to each row, and the counts are not affected. With `use_pebble_query_with_includes_with_config`, the relations registered with `with_include_with_config` or `with_include_via_with_config` only load the related rows within the scopes and soft delete of their own `PebbleQueryConfig`, computed from the same request context.
+//!
+//! For "distinct authors of the books matching this filter" or per-group counts, set `distinct_on` or `group_by` in the SearchQuery and run it with `use_pebble_query_grouped`, or `use_pebble_query_grouped_with_config` to apply the scopes, soft-delete column, field policy and limits of a `PebbleQueryConfig`. The rows are returned as JSON objects keyed by the field names, with the size of each group as `_count` (`GROUP_COUNT_FIELD`), and the pagination counts are computed over the distinct or grouped rows. The other query methods reject these queries with `InvalidGrouping`, since they return models, and `query_to_select` panics with it; build their select with `try_query_to_grouped_select` instead.
+//!
+//! When rows are filtered out in Rust, e.g. by permission checks, `use_pebble_query_backfilled` keeps reading batches of rows until the page is full again, up to `max_iterations` batches. The metadata reports the rows read in `scanned_items`, the rejected ones in `filter_count`, and the offset of the next page in `next_offset`. `use_pebble_query_backfilled_with_config` applies the scopes, soft delete and field policy of a `PebbleQueryConfig` before the predicate.
+//!
+//! For Relay clients, convert a result with `pebble_connection` into a `PebbleConnection` with `edges { cursor node }` and `pageInfo { hasNextPage endCursor }`. The cursors are built from the sort key and the primary key of each row; send the `endCursor` back as `after` in the next SearchQuery to get the rows after it. Run the query with `PebbleQueryOptions::connection_paging`, so that the rows with the same sort value are ordered by the primary key on the first page as they are on the pages after a cursor.
+//!
+//! To serve every entity from one generic endpoint, e.g. `/api/{entity}/search`, register each entity's `PebbleQueryConfig` and default select in a `PebbleRegistry` and query it by name; the rows are returned as `serde_json::Value`.
+//!
+//! To protect the database from crafted requests, set `PebbleQueryOptions::limits` to a `PebbleQueryLimits` with the maximum number of conditions, `IN` list size, page length and offset, and whether leading wildcard patterns are allowed. Queries exceeding the limits are rejected with a `PebbleQueryError` before any SQL is built.
+//!
+//! For large exports, `use_pebble_query_stream` applies the same query but streams the rows with SeaOrm's `stream()` instead of collecting them, and `use_pebble_query_metadata` returns the pagination information separately. Their `_with_config` variants apply the scopes, soft delete, field policy and options of a `PebbleQueryConfig`. To download the rows, `write_csv` and `write_ndjson` (or `write_result_csv` and `write_result_ndjson` for a `PebbleQueryResultGeneric`) export them with the metadata as a header or trailer record.
+//!
+//! For dashboards that repeat the same queries, `use_pebble_query_cached` puts a cache (in-memory LRU or your own `PebbleCacheStore`) in front of `use_pebble_query`, keyed by the initial select, the field map and the normalized query, with relative date and time values resolved against the clock of the cache. `PebbleQueryCache::invalidate` removes the results that read an entity's table, or that declared it as a dependency with `PebbleQueryCache::with_dependency`, e.g. because the initial select joins it.
+//!
+//! To tell API clients which fields, operators and value types an entity accepts, `query_json_schema(&BOOK_COLUMN_MAP)` generates the JSON Schema of its `SearchQuery`, and `query_openapi_components` wraps it for an OpenAPI 3 document. Their `_with_config` variants also list the computed fields of the configuration.
+//!
+//! For web clients, `dto_typescript_definitions()` emits the `.d.ts` definitions of the DTOs, read from their serialized fields so that they follow the DTOs, and `entity_fields_typescript("Book", &BOOK_COLUMN_MAP)` the unions of the filterable and sortable fields of an entity, or `entity_fields_typescript_with_config` with its computed fields too. A `SearchCondition` has either an `operator` or the `operator_name` of a custom operator.
+//!
+//! The same filters can change data in bulk: `pebble_update_many` and `pebble_delete_many` apply a `SearchFilter` to `Entity::update_many()` and `Entity::delete_many()`, with a dry run that only counts the matching rows. Filters without any condition are refused. Their `_with_config` variants are restricted to the scopes of a `PebbleQueryConfig` and leave its soft-deleted rows alone.
+//!
 //! Some other middle util methods, e.g. convert SearchCondition to SeaOrm Condition, are also exposed which you can use to reduce boilerplate code.
 //!
+//! ## Optional features
+//!
+//! - `async-graphql`: `GraphqlSearchQuery` and the other `InputObject`/`Enum` equivalents of the DTOs take the same query in a GraphQL schema, and `pebble_graphql_query` runs it and returns a `PebbleGraphqlConnection` with edges, page info, the total count and the metadata, or `pebble_graphql_query_with_config` with a `PebbleQueryConfig`. The cursors of the edges are the ones of `pebble_connection`, to send back as `after`. `PebbleQueryError` is converted to a GraphQL error with a `code` extension.
+//! - `tonic`: `pebble_tonic_query` runs a `tonic::Request<SearchQuery>` and returns a `tonic::Response` with the metadata and rows converted to your prost messages, or `pebble_tonic_query_with_config` with a `PebbleQueryConfig`. `PebbleQueryError` is mapped to `tonic::Status`.
+//! - `utoipa`: `query_utoipa_schema` converts the entity query schema from `query_json_schema` into a `utoipa` schema for your OpenAPI document.
+//!
 //! ## Note:
 //!
 //! 1. This is not the most polished library. __Please do not use in production without reviewing the code and make necessary changes__.
@@ -162,9 +240,31 @@ extern crate core;
 
 pub mod errors;
 pub mod filter_conditions;
+pub mod pebble_backfill;
+pub mod pebble_bulk;
+pub mod pebble_cache;
+pub mod pebble_connection;
 pub mod pebble_converter;
+pub mod pebble_export;
+#[cfg(feature = "async-graphql")]
+pub mod pebble_graphql;
+pub mod pebble_grouping;
+pub mod pebble_include;
+pub mod pebble_json;
+pub mod pebble_operators;
 pub mod pebble_query;
+pub mod pebble_query_config;
+pub mod pebble_query_limits;
+pub mod pebble_query_options;
 pub mod pebble_query_result;
+pub mod pebble_registry;
+pub mod pebble_relative_time;
+pub mod pebble_schema;
+pub mod pebble_stream;
+#[cfg(feature = "tonic")]
+pub mod pebble_tonic;
+pub mod pebble_typescript;
 pub mod pebble_utils;
 
-pub mod sample_dto_structs;
\ No newline at end of file
+pub mod sample_dto_structs;
+pub mod sample_entities;
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use sea_orm::{ConnectionTrait, EntityTrait, FromQueryResult, QuerySelect, Select};
+
+use crate::errors::PebbleQueryError;
+use crate::pebble_connection::{
+    add_tiebreak_order_to_select, try_add_cursor_to_select_with_policy,
+};
+use crate::pebble_query::try_query_to_select_with_policy;
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::pebble_query_options::PebbleQueryOptions;
+use crate::pebble_query_result::PebbleQueryResult;
+use crate::pebble_relative_time::resolve_relative_times;
+use crate::pebble_utils::get_query_pagination_numbers_with_strategy;
+use crate::sample_dto_structs::SearchQuery;
+
+/// # Apply and run SearchQuery, keeping only the rows accepted by a Rust-side predicate and backfilling the page
+///
+/// Filtering the rows after the query, e.g. with `map_into_generic`, leaves the page short. This instead reads the rows in batches of `length` after the requested offset, keeps the ones `predicate` accepts, and reads the next batch until the page holds `length` rows, the rows run out, or `max_iterations` batches were read.
+///
+/// The metadata tells how the page was filled: `scanned_items` is the number of rows read, `filter_count` the number of them the predicate rejected, and `next_offset` the offset to request the next page from, since the rows read to fill this page go beyond `offset + length`. With an `after` cursor, the cursor of the last row of the page can be used instead.
+///
+/// `result_total_items` is still the number of rows matching the query before the predicate, counted with the count strategy of `options`. The execution mode of `options` is not used, as the batches depend on each other.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query.
+/// * `query`: Option<SearchQuery> object - the query to be applied to the select. If none, all entity entries accepted by the predicate will be returned.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection.
+/// * `options`: The options of the count query and the limits of the query.
+/// * `max_iterations`: The maximum number of batches to read, so that a predicate that rejects almost everything cannot scan the whole table.
+/// * `predicate`: Whether a row is kept, e.g. whether the user is allowed to see it.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_backfill::use_pebble_query_backfilled;
+/// # use pebble_query::pebble_query_options::PebbleQueryOptions;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// # async fn example(db: &DatabaseConnection, query: SearchQuery, readable_tenants: Vec<i32>) -> Result<(), PebbleQueryError> {
+/// let result = use_pebble_query_backfilled(
+///     book::Entity::find(),
+///     Some(query),
+///     &BOOK_COLUMN_MAP,
+///     db,
+///     &PebbleQueryOptions::default(),
+///     5,
+///     |book| readable_tenants.contains(&book.tenant_id),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn use_pebble_query_backfilled<C, E, M, F>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+    options: &PebbleQueryOptions,
+    max_iterations: usize,
+    predicate: F,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    F: FnMut(&M) -> bool,
+{
+    run_pebble_query_backfilled(
+        initial_select,
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default().for_backend(db.get_database_backend()),
+        db,
+        options,
+        max_iterations,
+        predicate,
+    )
+    .await
+}
+
+/// `use_pebble_query_backfilled` with the query configuration of the entity: the scopes computed from `context`, the soft-delete column, the field policy and the options, like `use_pebble_query_with_config`.
+pub async fn use_pebble_query_backfilled_with_config<C, E, M, Ctx, F>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+    max_iterations: usize,
+    predicate: F,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    F: FnMut(&M) -> bool,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    let scoped_select = config.apply_scopes(initial_select, context)?;
+    let scoped_select = config.apply_soft_delete(scoped_select, &query)?;
+    run_pebble_query_backfilled(
+        scoped_select,
+        Some(query),
+        &config.field_to_column_map,
+        &config.field_policy.for_backend(db.get_database_backend()),
+        db,
+        &config.options,
+        max_iterations,
+        predicate,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_pebble_query_backfilled<C, E, M, F>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    db: &C,
+    options: &PebbleQueryOptions,
+    max_iterations: usize,
+    mut predicate: F,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    F: FnMut(&M) -> bool,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    options.limits.validate(&query)?;
+    // relative dates are resolved for the SQL only; the metadata echoes the query as the client sent it.
+    let unresolved_query = query;
+    let query = resolve_relative_times(&unresolved_query, field_to_column_map, &options.clock)?;
+    let mut select_with_conditions =
+        try_query_to_select_with_policy(&query, field_to_column_map, field_policy, initial_select)?;
+    // the batches are read with offsets, so the rows with the same sort value must keep their order between them.
+    if query.after.is_none() {
+        select_with_conditions =
+            add_tiebreak_order_to_select(select_with_conditions, &query, field_to_column_map)?;
+    }
+
+    // with a cursor, the batches are read from the first row after it.
+    let (batch_select, start_offset) = match &query.after {
+        Some(after) => (
+            try_add_cursor_to_select_with_policy(
+                select_with_conditions.clone(),
+                after,
+                &query,
+                field_to_column_map,
+                field_policy,
+            )?,
+            0,
+        ),
+        None => (select_with_conditions.clone(), query.offset.max(0) as u64),
+    };
+    let page_length = if query.find_one {
+        Some(1)
+    } else if query.length > 0 {
+        Some(query.length as u64)
+    } else {
+        None
+    };
+
+    let mut results: Vec<M> = Vec::new();
+    let mut scanned_items: u64 = 0;
+    let mut exhausted = false;
+    for _ in 0..max_iterations.max(1) {
+        let mut batch = batch_select.clone().offset(start_offset + scanned_items);
+        if let Some(page_length) = page_length {
+            batch = batch.limit(page_length);
+        }
+        let rows = batch.all(db).await?;
+        let rows_count = rows.len() as u64;
+
+        for row in rows {
+            if page_length.is_some_and(|page_length| results.len() as u64 >= page_length) {
+                break;
+            }
+            scanned_items += 1;
+            if predicate(&row) {
+                results.push(row);
+            }
+        }
+
+        match page_length {
+            Some(page_length) if results.len() as u64 >= page_length => break,
+            Some(page_length) if rows_count < page_length => {
+                exhausted = true;
+                break;
+            }
+            Some(_) => {}
+            // without a page length, the first batch already read all the rows.
+            None => {
+                exhausted = true;
+                break;
+            }
+        }
+    }
+
+    let (total_items_and_pages_number, total_items_accuracy) =
+        get_query_pagination_numbers_with_strategy(
+            db,
+            &query,
+            select_with_conditions,
+            options.count_strategy,
+        )
+        .await?;
+    let next_offset = start_offset + scanned_items;
+    let has_next_page = !exhausted
+        && (query.after.is_some() || next_offset < total_items_and_pages_number.number_of_items);
+    let filter_count = scanned_items as i32 - results.len() as i32;
+
+    let mut pebble_query_result =
+        PebbleQueryResult::from(unresolved_query, results, total_items_and_pages_number);
+    pebble_query_result.metadata.total_items_accuracy = total_items_accuracy as i32;
+    pebble_query_result.metadata.filter_count = Some(filter_count);
+    pebble_query_result.metadata.scanned_items = Some(scanned_items as i32);
+    pebble_query_result.metadata.next_offset = Some(next_offset as i32);
+    pebble_query_result.metadata.has_next_page = Some(has_next_page);
+
+    Ok(pebble_query_result)
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use sea_orm::{
+    ActiveModelTrait, Condition, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+};
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::EmptyFilter;
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::pebble_relative_time::{resolve_relative_times, PebbleClock};
+use crate::pebble_utils::try_query_to_seaorm_conditions_with_policy;
+use crate::sample_dto_structs::{SearchFilter, SearchQuery};
+
+/// The result of a bulk update or delete.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PebbleBulkResult {
+    /// The rows updated or deleted, or in a dry run, the rows that would be.
+    pub rows_affected: u64,
+    /// Whether this was a dry run that did not change anything.
+    pub dry_run: bool,
+}
+
+/// Convert the filter into a single condition, refusing filters without any condition so that a bulk operation never hits the whole table by accident.
+fn filter_to_bulk_condition<E: EntityTrait>(
+    filter: &SearchFilter,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    clock: &PebbleClock,
+) -> Result<Condition, PebbleQueryError> {
+    let query = SearchQuery {
+        filter: Some(filter.clone()),
+        ..Default::default()
+    };
+    let query = resolve_relative_times(&query, field_to_column_map, clock)?;
+    let (must_conditions, any_conditions) =
+        try_query_to_seaorm_conditions_with_policy(&query, field_to_column_map, field_policy)?;
+
+    if must_conditions.is_empty() && any_conditions.is_empty() {
+        return Err(EmptyFilter(format!(
+            "a bulk operation on {} needs at least one condition",
+            E::default().table_name()
+        )));
+    }
+
+    let mut condition = Condition::all();
+    if !must_conditions.is_empty() {
+        condition = condition.add(must_conditions);
+    }
+    if !any_conditions.is_empty() {
+        condition = condition.add(any_conditions);
+    }
+    Ok(condition)
+}
+
+/// # Update all the rows matching a SearchFilter
+///
+/// The filter is converted with the same field map as `use_pebble_query`, and the values set in `active_model` are written to every matching row with `Entity::update_many()`.
+///
+/// # Arguments
+/// * `filter`: The SearchFilter selecting the rows to update. A filter without any condition is refused with `PebbleQueryError::EmptyFilter`.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `active_model`: The values to set, e.g. `book::ActiveModel { price: Set(Some(5.0)), ..Default::default() }`.
+/// * `db`: The database connection.
+/// * `dry_run`: If true, only count the matching rows without updating them.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_bulk::pebble_update_many;
+/// # use pebble_query::sample_dto_structs::SearchFilter;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, Set};
+/// # async fn example(db: &DatabaseConnection, filter: SearchFilter) -> Result<(), PebbleQueryError> {
+/// // reprice all the books matching the filter
+/// let repriced = book::ActiveModel { price: Set(Some(5.0)), ..Default::default() };
+/// let preview = pebble_update_many(&filter, &BOOK_COLUMN_MAP, repriced.clone(), db, true).await?;
+/// // ... confirm `preview.rows_affected` with the user, then
+/// let result = pebble_update_many(&filter, &BOOK_COLUMN_MAP, repriced, db, false).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn pebble_update_many<C, E, A>(
+    filter: &SearchFilter,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    active_model: A,
+    db: &C,
+    dry_run: bool,
+) -> Result<PebbleBulkResult, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Send + Sync,
+    A: ActiveModelTrait<Entity = E>,
+{
+    let condition = filter_to_bulk_condition(
+        filter,
+        field_to_column_map,
+        &PebbleFieldPolicy::default().for_backend(db.get_database_backend()),
+        &PebbleClock::default(),
+    )?;
+    update_many_with_condition(condition, active_model, db, dry_run).await
+}
+
+/// # Delete all the rows matching a SearchFilter
+///
+/// The filter is converted with the same field map as `use_pebble_query`, and every matching row is deleted with `Entity::delete_many()`.
+///
+/// # Arguments
+/// * `filter`: The SearchFilter selecting the rows to delete. A filter without any condition is refused with `PebbleQueryError::EmptyFilter`.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection.
+/// * `dry_run`: If true, only count the matching rows without deleting them.
+pub async fn pebble_delete_many<C, E>(
+    filter: &SearchFilter,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+    dry_run: bool,
+) -> Result<PebbleBulkResult, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Send + Sync,
+{
+    let condition = filter_to_bulk_condition(
+        filter,
+        field_to_column_map,
+        &PebbleFieldPolicy::default().for_backend(db.get_database_backend()),
+        &PebbleClock::default(),
+    )?;
+    delete_many_with_condition::<C, E>(condition, db, dry_run).await
+}
+
+/// `pebble_update_many` with the field map, field policy and clock of a `PebbleQueryConfig`, restricted to its scopes computed from `context`.
+///
+/// Soft-deleted rows are never updated when the configuration has a soft-delete column.
+pub async fn pebble_update_many_with_config<C, E, A, Ctx>(
+    filter: &SearchFilter,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    active_model: A,
+    db: &C,
+    dry_run: bool,
+) -> Result<PebbleBulkResult, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Send + Sync,
+    A: ActiveModelTrait<Entity = E>,
+{
+    let condition = filter_to_bulk_condition(
+        filter,
+        &config.field_to_column_map,
+        &config.field_policy.for_backend(db.get_database_backend()),
+        &config.options.clock,
+    )?
+    .add(config.scope_condition(context)?)
+    .add(config.soft_delete_condition(&SearchQuery::default())?);
+    update_many_with_condition(condition, active_model, db, dry_run).await
+}
+
+/// `pebble_delete_many` with the field map, field policy and clock of a `PebbleQueryConfig`, restricted to its scopes computed from `context`.
+///
+/// Soft-deleted rows are never deleted when the configuration has a soft-delete column, use `pebble_delete_many` to purge them.
+pub async fn pebble_delete_many_with_config<C, E, Ctx>(
+    filter: &SearchFilter,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+    dry_run: bool,
+) -> Result<PebbleBulkResult, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Send + Sync,
+{
+    let condition = filter_to_bulk_condition(
+        filter,
+        &config.field_to_column_map,
+        &config.field_policy.for_backend(db.get_database_backend()),
+        &config.options.clock,
+    )?
+    .add(config.scope_condition(context)?)
+    .add(config.soft_delete_condition(&SearchQuery::default())?);
+    delete_many_with_condition::<C, E>(condition, db, dry_run).await
+}
+
+async fn update_many_with_condition<C, E, A>(
+    condition: Condition,
+    active_model: A,
+    db: &C,
+    dry_run: bool,
+) -> Result<PebbleBulkResult, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Send + Sync,
+    A: ActiveModelTrait<Entity = E>,
+{
+    let rows_affected = match dry_run {
+        true => E::find().filter(condition).count(db).await?,
+        false => {
+            E::update_many()
+                .set(active_model)
+                .filter(condition)
+                .exec(db)
+                .await?
+                .rows_affected
+        }
+    };
+    Ok(PebbleBulkResult {
+        rows_affected,
+        dry_run,
+    })
+}
+
+async fn delete_many_with_condition<C, E>(
+    condition: Condition,
+    db: &C,
+    dry_run: bool,
+) -> Result<PebbleBulkResult, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Send + Sync,
+{
+    let rows_affected = match dry_run {
+        true => E::find().filter(condition).count(db).await?,
+        false => {
+            E::delete_many()
+                .filter(condition)
+                .exec(db)
+                .await?
+                .rows_affected
+        }
+    };
+    Ok(PebbleBulkResult {
+        rows_affected,
+        dry_run,
+    })
+}
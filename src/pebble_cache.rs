@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::sea_query::SqliteQueryBuilder;
+use sea_orm::{ConnectionTrait, EntityTrait, FromQueryResult, IdenStatic, QueryTrait, Select};
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::CacheError;
+use crate::pebble_query::use_pebble_query_with_options;
+use crate::pebble_query_options::PebbleQueryOptions;
+use crate::pebble_query_result::PebbleQueryResult;
+use crate::pebble_relative_time::{resolve_relative_times, PebbleClock};
+use crate::pebble_utils::normalize_query_with_map;
+use crate::sample_dto_structs::SearchQuery;
+
+/// The key of a cached query result.
+///
+/// `entity` is the table name of the queried entity, and `tables` the names of the tables the result depends on: the entity's own and the declared dependencies, e.g. the tables joined by the initial select, see `PebbleQueryCache::with_dependency`. They are used to invalidate the cached results that depend on an entity. `query` identifies the query itself, see `PebbleCacheKey::new`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PebbleCacheKey {
+    pub entity: String,
+    pub tables: Vec<String>,
+    pub query: String,
+}
+
+impl PebbleCacheKey {
+    /// Build the cache key from the initial select, the field map and the normalized SearchQuery, so that the same query with differently cased field names hits the same entry, while the same query against another select or field map does not.
+    ///
+    /// The relative date and time values of the query, e.g. `now-7d`, must be resolved first, see `resolve_relative_times`, or the same key would stand for results of another time.
+    pub fn new<E: EntityTrait>(
+        initial_select: &Select<E>,
+        field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+        query: &SearchQuery,
+    ) -> Self {
+        let initial_select_sql = initial_select.as_query().to_string(SqliteQueryBuilder);
+        let mut fields: Vec<String> = field_to_column_map
+            .iter()
+            .map(|(field, (entity, column))| {
+                format!("{}={}.{}", field, entity.table_name(), column.as_str())
+            })
+            .collect();
+        fields.sort();
+        let normalized_query = normalize_query_with_map(query, field_to_column_map);
+        PebbleCacheKey {
+            entity: E::default().table_name().to_string(),
+            tables: vec![E::default().table_name().to_string()],
+            query: format!(
+                "{}\n{}\n{}",
+                initial_select_sql,
+                fields.join(","),
+                serde_json::to_string(&normalized_query).unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Add the tables of the entities the result also depends on, e.g. through a join.
+    pub fn with_dependencies(mut self, dependencies: &[String]) -> Self {
+        for table in dependencies {
+            if !self.tables.contains(table) {
+                self.tables.push(table.clone());
+            }
+        }
+        self
+    }
+
+    /// Whether the cached result read the table of `entity`.
+    pub fn reads(&self, entity: &str) -> bool {
+        self.entity == entity || self.tables.iter().any(|table| table == entity)
+    }
+}
+
+/// Storage for cached query results.
+///
+/// An in-memory LRU store is provided in `PebbleLruCacheStore`. Implement this trait to keep the results elsewhere, e.g. in Redis. Errors of the store, e.g. a lost connection, are returned as `PebbleQueryError::CacheError`.
+#[async_trait]
+pub trait PebbleCacheStore<V>: Send + Sync {
+    /// Get the cached value, or `None` if it is missing or expired.
+    async fn get(&self, key: &PebbleCacheKey) -> Result<Option<V>, PebbleQueryError>;
+    /// Cache the value for `ttl`.
+    async fn insert(
+        &self,
+        key: PebbleCacheKey,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), PebbleQueryError>;
+    /// Remove all the cached values that read the table of an entity, see `PebbleCacheKey::reads`, e.g. after it is inserted, updated or deleted.
+    async fn invalidate_entity(&self, entity: &str) -> Result<(), PebbleQueryError>;
+}
+
+/// The cached values of `PebbleLruCacheStore` with the time they expire at.
+type LruCacheEntries<V> = LruCache<PebbleCacheKey, (Instant, V)>;
+
+/// In-memory store that evicts the least recently used entries once `capacity` is reached.
+pub struct PebbleLruCacheStore<V> {
+    entries: Mutex<LruCacheEntries<V>>,
+}
+
+impl<V> PebbleLruCacheStore<V> {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        PebbleLruCacheStore {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// The entries, or an error if a thread panicked while holding them.
+    fn lock_entries(&self) -> Result<MutexGuard<'_, LruCacheEntries<V>>, PebbleQueryError> {
+        self.entries
+            .lock()
+            .map_err(|err| CacheError(format!("the cache entries are poisoned: {}", err)))
+    }
+}
+
+#[async_trait]
+impl<V: Clone + Send + Sync> PebbleCacheStore<V> for PebbleLruCacheStore<V> {
+    async fn get(&self, key: &PebbleCacheKey) -> Result<Option<V>, PebbleQueryError> {
+        let mut entries = self.lock_entries()?;
+        Ok(match entries.get(key) {
+            Some((expires_at, value)) if *expires_at > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        })
+    }
+
+    async fn insert(
+        &self,
+        key: PebbleCacheKey,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), PebbleQueryError> {
+        let mut entries = self.lock_entries()?;
+        entries.put(key, (Instant::now() + ttl, value));
+        Ok(())
+    }
+
+    async fn invalidate_entity(&self, entity: &str) -> Result<(), PebbleQueryError> {
+        let mut entries = self.lock_entries()?;
+        let keys: Vec<PebbleCacheKey> = entries
+            .iter()
+            .filter(|(key, _)| key.reads(entity))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            entries.pop(&key);
+        }
+        Ok(())
+    }
+}
+
+/// A query cache: a store, the time-to-live of the results put into it, the tables the results also depend on besides the queried entity's, and the clock relative date and time values are resolved against.
+pub struct PebbleQueryCache<S> {
+    pub store: S,
+    pub ttl: Duration,
+    pub dependencies: Vec<String>,
+    pub clock: PebbleClock,
+}
+
+impl<S> PebbleQueryCache<S> {
+    pub fn new(store: S, ttl: Duration) -> Self {
+        PebbleQueryCache {
+            store,
+            ttl,
+            dependencies: vec![],
+            clock: PebbleClock::default(),
+        }
+    }
+
+    /// Declare that the cached results also read the table of the entity `E`, e.g. through a join of the initial select, so that `invalidate::<E, _>()` removes them too.
+    pub fn with_dependency<E: EntityTrait>(mut self) -> Self {
+        self.dependencies
+            .push(E::default().table_name().to_string());
+        self
+    }
+
+    /// Resolve relative date and time values against `clock` rather than the system clock in UTC.
+    pub fn with_clock(mut self, clock: PebbleClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Remove all the cached results of type `V` that read the table of the entity `E`, e.g. `invalidate::<author::Entity, PebbleQueryResult<book::Entity>>()` after an author is renamed, for the books queried with a join on their authors, see `with_dependency`.
+    pub async fn invalidate<E, V>(&self) -> Result<(), PebbleQueryError>
+    where
+        E: EntityTrait,
+        S: PebbleCacheStore<V>,
+    {
+        self.store
+            .invalidate_entity(E::default().table_name())
+            .await
+    }
+}
+
+/// # Apply and run SearchQuery to a SeaOrm Select<Entity>, caching the result
+///
+/// This is `use_pebble_query` with a cache in front of it. The cache key is built from the SQL of the initial select, the field map and the normalized query, see `PebbleCacheKey::new`. Relative date and time values, e.g. `now-7d`, are resolved against the clock of the cache before the key is built, so that their results are not served once the time they stand for has moved on.
+///
+/// On a cache hit the database is not queried at all. The `cache_hit` field of the returned metadata tells whether the result came from the cache.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query.
+/// * `query`: Option<SearchQuery> object - the query to be applied to the select. If none, all entity entries will be returned.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection.
+/// * `cache`: The cache to read from and write to.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::num::NonZeroUsize;
+/// # use std::sync::LazyLock;
+/// # use std::time::Duration;
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_cache::{use_pebble_query_cached, PebbleLruCacheStore, PebbleQueryCache};
+/// # use pebble_query::pebble_query_result::PebbleQueryResult;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// static BOOK_QUERY_CACHE: LazyLock<PebbleQueryCache<PebbleLruCacheStore<PebbleQueryResult<book::Entity>>>> =
+///     LazyLock::new(|| PebbleQueryCache::new(PebbleLruCacheStore::new(NonZeroUsize::new(1000).unwrap()), Duration::from_secs(10)));
+///
+/// # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>) -> Result<(), PebbleQueryError> {
+/// let result = use_pebble_query_cached(book::Entity::find(), query, &BOOK_COLUMN_MAP, db, &BOOK_QUERY_CACHE).await?;
+///
+/// // after a book is changed
+/// BOOK_QUERY_CACHE.invalidate::<book::Entity, _>().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn use_pebble_query_cached<C, E, M, S>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+    cache: &PebbleQueryCache<S>,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    S: PebbleCacheStore<PebbleQueryResult<E>>,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    // the query is resolved for the key and run at the same instant, so that the cached result is the one of the key.
+    let options = PebbleQueryOptions {
+        clock: PebbleClock::fixed(cache.clock.now()),
+        ..Default::default()
+    };
+    let resolved_query = resolve_relative_times(&query, field_to_column_map, &options.clock)?;
+    let key = PebbleCacheKey::new(&initial_select, field_to_column_map, &resolved_query)
+        .with_dependencies(&cache.dependencies);
+
+    if let Some(mut cached_result) = cache.store.get(&key).await? {
+        cached_result.metadata.cache_hit = Some(true);
+        return Ok(cached_result);
+    }
+
+    let mut result = use_pebble_query_with_options(
+        initial_select,
+        Some(query),
+        field_to_column_map,
+        db,
+        &options,
+    )
+    .await?;
+    result.metadata.cache_hit = Some(false);
+    cache.store.insert(key, result.clone(), cache.ttl).await?;
+    Ok(result)
+}
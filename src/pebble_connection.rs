@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{
+    Condition, EntityTrait, IdenStatic, Iterable, ModelTrait, PrimaryKeyToColumn, QueryFilter,
+    QueryOrder, QuerySelect, QueryTrait, Select,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::{InvalidCursor, InvalidField, InvalidOperator};
+use crate::pebble_query_config::PebbleFieldPolicy;
+use crate::pebble_query_result::{PebbleQueryResult, PebbleQueryResultGeneric};
+use crate::pebble_utils::{field_map_key, is_string_column, sort_key_expr};
+use crate::sample_dto_structs::{SearchQuery, SearchResultMetadata, SortDirection, SortNulls};
+
+/// A Relay-style connection: the rows of a page as edges with cursors, and the page info.
+///
+/// It serializes with the camelCase field names Relay clients expect, e.g. `pageInfo { hasNextPage endCursor }`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PebbleConnection<T> {
+    pub edges: Vec<PebbleEdge<T>>,
+    pub page_info: PebblePageInfo,
+    pub total_count: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PebbleEdge<T> {
+    /// Send it back as `SearchQuery.after` to get the rows after this one.
+    pub cursor: String,
+    pub node: T,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PebblePageInfo {
+    pub has_next_page: bool,
+    pub has_previous_page: bool,
+    pub start_cursor: Option<String>,
+    pub end_cursor: Option<String>,
+}
+
+impl<T> PebbleConnection<T> {
+    /// Convert the nodes, keeping their cursors and the page info.
+    pub fn map_nodes<U, F: FnMut(T) -> U>(self, mut map: F) -> PebbleConnection<U> {
+        PebbleConnection {
+            edges: self
+                .edges
+                .into_iter()
+                .map(|edge| PebbleEdge {
+                    cursor: edge.cursor,
+                    node: map(edge.node),
+                })
+                .collect(),
+            page_info: self.page_info,
+            total_count: self.total_count,
+        }
+    }
+}
+
+/// Encode the sort key values of a row into an opaque cursor.
+pub fn encode_cursor(values: &[Option<String>]) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(values).unwrap_or_default())
+}
+
+/// Decode a cursor from `encode_cursor` back into the sort key values of its row.
+pub fn decode_cursor(cursor: &str) -> Result<Vec<Option<String>>, PebbleQueryError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| InvalidCursor(format!("{} is not a valid cursor", cursor)))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|_| InvalidCursor(format!("{} is not a valid cursor", cursor)))
+}
+
+/// The columns the cursors of a query are built from, each with whether it is sorted descending.
+///
+/// These are the sort column of the query, if any, followed by the primary key columns, which break the ties between rows with the same sort value.
+pub fn cursor_key_columns<E: EntityTrait>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Result<Vec<(E::Column, bool)>, PebbleQueryError> {
+    let mut columns: Vec<(E::Column, bool)> = Vec::new();
+
+    if let Some(sort) = &query.sort {
+        let column = field_to_column_map
+            .get(&field_map_key(field_to_column_map, &sort.field))
+            .ok_or_else(|| {
+                InvalidField(format!(
+                    "Column {} not provided in field_to_column_map",
+                    sort.field
+                ))
+            })?
+            .1;
+        let descending = SortDirection::try_from(sort.order)
+            .map_err(|_| InvalidOperator(format!("Invalid sort direction: {}", sort.order)))?
+            == SortDirection::Desc;
+        columns.push((column, descending));
+    }
+
+    for primary_key in E::PrimaryKey::iter() {
+        let column = primary_key.into_column();
+        if !columns
+            .iter()
+            .any(|(key_column, _)| key_column.as_str() == column.as_str())
+        {
+            columns.push((column, false));
+        }
+    }
+    Ok(columns)
+}
+
+/// Order the select by the primary key after the sort of the query, so that rows with the same sort value always come in the same order and their cursors are unique.
+pub fn add_tiebreak_order_to_select<E: EntityTrait>(
+    select: Select<E>,
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Result<Select<E>, PebbleQueryError> {
+    let mut select = select;
+    // the sort column itself is already ordered by `try_add_sort_to_select`.
+    let sort_column = query
+        .sort
+        .as_ref()
+        .and_then(|sort| field_to_column_map.get(&field_map_key(field_to_column_map, &sort.field)))
+        .map(|(_, column)| column.as_str());
+    for primary_key in E::PrimaryKey::iter() {
+        let column = primary_key.into_column();
+        if sort_column != Some(column.as_str()) {
+            select = select.order_by_asc(column);
+        }
+    }
+    Ok(select)
+}
+
+/// # Restrict a select to the rows after a cursor
+///
+/// The rows after the cursor are the ones whose sort keys come after the cursor's values in the order of the query, i.e. `(sort > v) OR (sort = v AND id > v_id)` for an ascending sort. The offset of the select is removed, and one more row than `length` is fetched, so that `has_next_page` can be told without counting.
+///
+/// When the sort sets `nulls`, the rows with a NULL sort value are paged through where they were placed. Otherwise sort keys are expected to be non-nullable: if the sort value in the cursor is NULL, only the following rows with a NULL sort value are returned.
+///
+/// # Arguments
+/// * `select`: The select with the conditions, pagination, and sorting of the query applied, see `try_query_to_select`.
+/// * `after`: The cursor, e.g. the `endCursor` of the previous page.
+/// * `query`: The SearchQuery, whose sort must be the same as the one the cursor was built with.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+pub fn try_add_cursor_to_select<E: EntityTrait>(
+    select: Select<E>,
+    after: &str,
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Result<Select<E>, PebbleQueryError> {
+    try_add_cursor_to_select_with_policy(
+        select,
+        after,
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+    )
+}
+
+/// Restrict a select to the rows after a cursor like `try_add_cursor_to_select`, comparing the sort values with the collations of the field policy, which must be the one the select was sorted with.
+pub fn try_add_cursor_to_select_with_policy<E: EntityTrait>(
+    select: Select<E>,
+    after: &str,
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+) -> Result<Select<E>, PebbleQueryError> {
+    if let Some(sort) = &query.sort {
+        if !field_to_column_map.contains_key(&sort.field)
+            && field_policy.computed_field(&sort.field).is_some()
+        {
+            return Err(InvalidCursor(format!(
+                "{} is a computed field, page through it with offset instead of after",
+                sort.field
+            )));
+        }
+    }
+    let values = decode_cursor(after)?;
+    let columns = cursor_key_columns(query, field_to_column_map)?;
+    if values.len() != columns.len() {
+        return Err(InvalidCursor(format!(
+            "{} has {} keys but the query sorts by {}",
+            after,
+            values.len(),
+            columns.len()
+        )));
+    }
+
+    // only the sort column can hold NULLs, the primary key columns after it cannot.
+    let sort_nulls = query
+        .sort
+        .as_ref()
+        .and_then(|sort| SortNulls::try_from(sort.nulls).ok())
+        .unwrap_or(SortNulls::Unspecified);
+    // the sort values are compared the same way the rows are ordered, e.g. lowercased.
+    let sort_key = |index: usize, expr: SimpleExpr| match &query.sort {
+        Some(sort) if index == 0 => sort_key_expr(
+            expr,
+            sort.case_insensitive && is_string_column(&columns[0].0),
+            field_policy.sort_collation(&sort.field),
+        ),
+        _ => expr,
+    };
+
+    let mut after_condition = Condition::any();
+    for index in 0..columns.len() {
+        let mut key_condition = Condition::all();
+        for (key_index, ((column, _), value)) in
+            columns.iter().zip(values.iter()).enumerate().take(index)
+        {
+            let column_key = sort_key(key_index, Expr::col((E::default(), *column)).into());
+            key_condition = key_condition.add(match value {
+                Some(value) => {
+                    Expr::expr(column_key).eq(sort_key(key_index, Expr::val(value).into()))
+                }
+                None => Expr::expr(column_key).is_null(),
+            });
+        }
+        let (column, descending) = columns[index];
+        let column_key = sort_key(index, Expr::col((E::default(), column)).into());
+        let nulls = if index == 0 && query.sort.is_some() {
+            sort_nulls
+        } else {
+            SortNulls::Unspecified
+        };
+        let after_value = match &values[index] {
+            Some(value) if descending => {
+                Expr::expr(column_key).lt(sort_key(index, Expr::val(value).into()))
+            }
+            Some(value) => Expr::expr(column_key).gt(sort_key(index, Expr::val(value).into())),
+            // the NULLs placed first come before every value.
+            None if nulls == SortNulls::First => Expr::col((E::default(), column)).is_not_null(),
+            // nothing sorts after NULL within the same key, so only the next key can decide.
+            None => continue,
+        };
+        key_condition = match (&values[index], nulls) {
+            // the NULLs placed last come after every value.
+            (Some(_), SortNulls::Last) => key_condition.add(
+                Condition::any()
+                    .add(after_value)
+                    .add(Expr::col((E::default(), column)).is_null()),
+            ),
+            _ => key_condition.add(after_value),
+        };
+        after_condition = after_condition.add(key_condition);
+    }
+
+    let mut select = select.filter(after_condition);
+    QueryTrait::query(&mut select).reset_offset();
+    if query.length > 0 {
+        select = select.limit(query.length as u64 + 1);
+    }
+    Ok(select)
+}
+
+/// The cursor of a model, built from the values of its cursor key columns.
+pub fn model_cursor<E: EntityTrait>(model: &E::Model, columns: &[(E::Column, bool)]) -> String {
+    let values: Vec<Option<String>> = columns
+        .iter()
+        .map(
+            |(column, _)| match sea_orm::sea_query::sea_value_to_json_value(&model.get(*column)) {
+                Value::Null => None,
+                Value::String(value) => Some(value),
+                value => Some(value.to_string()),
+            },
+        )
+        .collect();
+    encode_cursor(&values)
+}
+
+pub(crate) fn page_info_from_metadata(
+    metadata: &SearchResultMetadata,
+    start_cursor: Option<String>,
+    end_cursor: Option<String>,
+) -> PebblePageInfo {
+    let after = metadata
+        .query
+        .as_ref()
+        .and_then(|query| query.after.as_ref());
+    PebblePageInfo {
+        has_next_page: metadata
+            .has_next_page
+            .unwrap_or(metadata.offset + metadata.result_items < metadata.result_total_items),
+        has_previous_page: after.is_some() || metadata.offset > 0,
+        start_cursor,
+        end_cursor,
+    }
+}
+
+/// # Convert a PebbleQueryResult into a Relay-style connection
+///
+/// The cursor of each row is built from its values of the sort column of the query and the primary key. Send the `endCursor` of the page back as `SearchQuery.after`, with the same sort and filter, to get the next page.
+///
+/// Run the query with `PebbleQueryOptions::connection_paging`, so that its rows are ordered the same way as the rows after the cursors.
+///
+/// # Arguments
+/// * `result`: The result of `use_pebble_query`.
+/// * `field_to_column_map`: The map the query was run with.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_connection::pebble_connection;
+/// # use pebble_query::pebble_query::use_pebble_query_with_options;
+/// # use pebble_query::pebble_query_options::PebbleQueryOptions;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// # async fn example(db: &DatabaseConnection, query: SearchQuery) -> Result<(), PebbleQueryError> {
+/// let options = PebbleQueryOptions {
+///     connection_paging: true,
+///     ..Default::default()
+/// };
+/// let result = use_pebble_query_with_options(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, db, &options).await?;
+/// let connection = pebble_connection(result, &BOOK_COLUMN_MAP)?.map_nodes(|book| book.title);
+/// # Ok(())
+/// # }
+/// ```
+pub fn pebble_connection<E: EntityTrait>(
+    result: PebbleQueryResult<E>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Result<PebbleConnection<E::Model>, PebbleQueryError> {
+    let query = result.metadata.query.clone().unwrap_or_default();
+    let columns = cursor_key_columns(&query, field_to_column_map)?;
+    Ok(pebble_connection_from_generic(
+        PebbleQueryResultGeneric {
+            metadata: result.metadata,
+            results: result.results,
+        },
+        |model| model_cursor::<E>(model, &columns),
+    ))
+}
+
+/// # Convert a PebbleQueryResultGeneric into a Relay-style connection
+///
+/// The rows are no longer entity models, so the cursor of each row is given by `cursor_of`. For the cursor to be accepted back as `SearchQuery.after`, build it with `encode_cursor` from the same keys as `cursor_key_columns`, or convert the result with `pebble_connection` before mapping the nodes.
+pub fn pebble_connection_from_generic<T, F>(
+    result: PebbleQueryResultGeneric<T>,
+    mut cursor_of: F,
+) -> PebbleConnection<T>
+where
+    F: FnMut(&T) -> String,
+{
+    let edges: Vec<PebbleEdge<T>> = result
+        .results
+        .into_iter()
+        .map(|node| PebbleEdge {
+            cursor: cursor_of(&node),
+            node,
+        })
+        .collect();
+    let page_info = page_info_from_metadata(
+        &result.metadata,
+        edges.first().map(|edge| edge.cursor.clone()),
+        edges.last().map(|edge| edge.cursor.clone()),
+    );
+    PebbleConnection {
+        edges,
+        page_info,
+        total_count: result.metadata.result_total_items,
+    }
+}
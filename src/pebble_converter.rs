@@ -1,4 +1,4 @@
-use crate::sample_dto_structs::{SearchQuery, SearchResultMetadata};
+use crate::sample_dto_structs::{SearchCountAccuracy, SearchQuery, SearchResultMetadata};
 
 use sea_orm::EntityTrait;
 
@@ -31,6 +31,11 @@ impl PebbleConverterTrait for PebbleConverter {
 
             filter_count: None,
             filter_reason: None,
+            cache_hit: None,
+            has_next_page: None,
+            scanned_items: None,
+            next_offset: None,
+            total_items_accuracy: SearchCountAccuracy::Exact as i32,
         }
     }
 }
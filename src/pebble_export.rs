@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use sea_orm::EntityTrait;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::ExportError;
+use crate::pebble_json::json_path_prefix;
+use crate::pebble_query_result::PebbleQueryResultGeneric;
+use crate::sample_dto_structs::SearchResultMetadata;
+
+/// Where the `SearchResultMetadata` is written in an export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PebbleMetadataPlacement {
+    /// Do not write the metadata.
+    #[default]
+    Omit,
+    /// Write the metadata before the rows: a `# {...}` comment line in CSV, or a `{"metadata": {...}}` record in NDJSON.
+    Header,
+    /// Write the metadata after the rows, in the same format as `Header`.
+    Trailer,
+}
+
+/// The CSV columns of an entity: the field names of its `field_to_column_map`, sorted. The JSON path fields of `meta.*` entries are not columns of the rows and are left out.
+///
+/// The field names must be the names the rows are serialized with. When a field is renamed, e.g. with `#[serde(rename_all = "camelCase")]`, use `csv_columns_from_row` instead.
+pub fn csv_columns_from_field_map<E: EntityTrait>(
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Vec<String> {
+    let mut columns: Vec<String> = field_to_column_map
+        .keys()
+        .filter(|field| json_path_prefix(field).is_none())
+        .cloned()
+        .collect();
+    columns.sort();
+    columns
+}
+
+/// The CSV columns of a row: the fields it is serialized with, in the order of its JSON object.
+///
+/// Returns `PebbleQueryError::ExportError` if the row is not serialized as an object.
+pub fn csv_columns_from_row<T: Serialize>(row: &T) -> Result<Vec<String>, PebbleQueryError> {
+    match serde_json::to_value(row).map_err(to_export_error)? {
+        Value::Object(fields) => Ok(fields.keys().cloned().collect()),
+        value => Err(ExportError(format!(
+            "{} is not an object and has no columns",
+            value
+        ))),
+    }
+}
+
+/// # Write rows as CSV
+///
+/// Each row is serialized with serde and the values of `columns` are written in that order, under a header of the column names. Use `csv_columns_from_field_map` for all the queryable fields of an entity, or any projection of the row's fields.
+///
+/// Strings are written as is, `null` as empty cells, and nested objects and arrays as JSON. A column that is not a field of a serialized row is refused with `PebbleQueryError::ExportError`, rather than written as empty cells.
+///
+/// # Arguments
+/// * `writer`: Where to write the CSV, e.g. a file or a response body.
+/// * `rows`: The rows to write, e.g. `result.results` or rows collected from `use_pebble_query_stream`.
+/// * `columns`: The fields of the rows to write, in order.
+/// * `metadata`: The metadata to write as a `# ` comment line according to `placement`.
+/// * `placement`: Where to write the metadata.
+pub fn write_csv<W, T, I>(
+    writer: W,
+    rows: I,
+    columns: &[String],
+    metadata: Option<&SearchResultMetadata>,
+    placement: PebbleMetadataPlacement,
+) -> Result<(), PebbleQueryError>
+where
+    W: Write,
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+{
+    let mut writer = writer;
+
+    if placement == PebbleMetadataPlacement::Header {
+        write_csv_metadata_comment(&mut writer, metadata)?;
+    }
+
+    let mut csv_writer = csv::WriterBuilder::new().from_writer(&mut writer);
+    csv_writer.write_record(columns).map_err(to_export_error)?;
+    for row in rows {
+        let row = serde_json::to_value(row).map_err(to_export_error)?;
+        let record = columns
+            .iter()
+            .map(|column| match row.get(column) {
+                Some(value) => Ok(csv_cell(value)),
+                None => Err(ExportError(format!(
+                    "{} is not a field of the serialized row",
+                    column
+                ))),
+            })
+            .collect::<Result<Vec<String>, PebbleQueryError>>()?;
+        csv_writer.write_record(&record).map_err(to_export_error)?;
+    }
+    // flush the buffered records before the trailer so that it is not written out of order.
+    csv_writer.flush().map_err(to_export_error)?;
+    drop(csv_writer);
+
+    if placement == PebbleMetadataPlacement::Trailer {
+        write_csv_metadata_comment(&mut writer, metadata)?;
+    }
+
+    writer.flush().map_err(to_export_error)?;
+    Ok(())
+}
+
+/// # Write rows as NDJSON
+///
+/// Each row is serialized with serde as one JSON object per line. The metadata, if placed, is written as a `{"metadata": {...}}` record before or after the rows.
+pub fn write_ndjson<W, T, I>(
+    mut writer: W,
+    rows: I,
+    metadata: Option<&SearchResultMetadata>,
+    placement: PebbleMetadataPlacement,
+) -> Result<(), PebbleQueryError>
+where
+    W: Write,
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+{
+    if placement == PebbleMetadataPlacement::Header {
+        write_ndjson_metadata_record(&mut writer, metadata)?;
+    }
+
+    for row in rows {
+        serde_json::to_writer(&mut writer, &row).map_err(to_export_error)?;
+        writer.write_all(b"\n").map_err(to_export_error)?;
+    }
+
+    if placement == PebbleMetadataPlacement::Trailer {
+        write_ndjson_metadata_record(&mut writer, metadata)?;
+    }
+
+    writer.flush().map_err(to_export_error)?;
+    Ok(())
+}
+
+/// Write the rows of a query result as CSV with its metadata, see `write_csv`.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_export::{write_result_csv, PebbleMetadataPlacement};
+/// # use pebble_query::pebble_query::try_use_pebble_query;
+/// # use pebble_query::pebble_query_result::PebbleQueryResultUtilTrait;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>, response_body: &mut Vec<u8>) -> Result<(), PebbleQueryError> {
+/// let result = try_use_pebble_query(book::Entity::find(), query, &BOOK_COLUMN_MAP, db)
+///     .await?
+///     .map_into_generic(|book| Some(book), None);
+/// let columns = ["id", "title", "price"].map(String::from);
+/// write_result_csv(response_body, &result, &columns, PebbleMetadataPlacement::Header)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_result_csv<W, T>(
+    writer: W,
+    result: &PebbleQueryResultGeneric<T>,
+    columns: &[String],
+    placement: PebbleMetadataPlacement,
+) -> Result<(), PebbleQueryError>
+where
+    W: Write,
+    T: Serialize,
+{
+    write_csv(
+        writer,
+        &result.results,
+        columns,
+        Some(&result.metadata),
+        placement,
+    )
+}
+
+/// Write the rows of a query result as NDJSON with its metadata, see `write_ndjson`.
+pub fn write_result_ndjson<W, T>(
+    writer: W,
+    result: &PebbleQueryResultGeneric<T>,
+    placement: PebbleMetadataPlacement,
+) -> Result<(), PebbleQueryError>
+where
+    W: Write,
+    T: Serialize,
+{
+    write_ndjson(writer, &result.results, Some(&result.metadata), placement)
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn write_csv_metadata_comment<W: Write>(
+    writer: &mut W,
+    metadata: Option<&SearchResultMetadata>,
+) -> Result<(), PebbleQueryError> {
+    if let Some(metadata) = metadata {
+        let metadata = serde_json::to_string(metadata).map_err(to_export_error)?;
+        writeln!(writer, "# {}", metadata).map_err(to_export_error)?;
+    }
+    Ok(())
+}
+
+fn write_ndjson_metadata_record<W: Write>(
+    writer: &mut W,
+    metadata: Option<&SearchResultMetadata>,
+) -> Result<(), PebbleQueryError> {
+    if let Some(metadata) = metadata {
+        serde_json::to_writer(&mut *writer, &serde_json::json!({ "metadata": metadata }))
+            .map_err(to_export_error)?;
+        writer.write_all(b"\n").map_err(to_export_error)?;
+    }
+    Ok(())
+}
+
+fn to_export_error<E: std::fmt::Display>(err: E) -> PebbleQueryError {
+    ExportError(err.to_string())
+}
@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::{Enum, ErrorExtensions, InputObject, OutputType, SimpleObject};
+use sea_orm::{ConnectionTrait, DbErr, EntityTrait, FromQueryResult, Select};
+
+use crate::errors::PebbleQueryError;
+use crate::pebble_connection::{cursor_key_columns, model_cursor, page_info_from_metadata};
+use crate::pebble_query::{run_pebble_query, use_pebble_query_with_options};
+use crate::pebble_query_config::PebbleQueryConfig;
+use crate::pebble_query_options::PebbleQueryOptions;
+use crate::pebble_query_result::{PebbleQueryResult, PebbleQueryResultGeneric};
+use crate::sample_dto_structs::{
+    SearchCondition, SearchCountAccuracy, SearchFilter, SearchOperator, SearchQuery,
+    SearchRelation, SearchResultMetadata, SearchSortOption, SortDirection, SortNulls,
+};
+
+/// GraphQL equivalent of `SearchOperator`, without the `Unspecified` variant.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+#[graphql(name = "SearchOperator")]
+pub enum GraphqlSearchOperator {
+    Like,
+    NotLike,
+    Ilike,
+    NotIlike,
+    Similar,
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    LessThan,
+    LessThanOrEquals,
+    In,
+    NotIn,
+    Contains,
+    NotContains,
+    StartsWith,
+    NotStartsWith,
+    EndsWith,
+    NotEndsWith,
+    Exists,
+    NotExists,
+    IsNull,
+    IsNotNull,
+    IsTrue,
+    IsFalse,
+    Between,
+    NotBetween,
+}
+
+impl From<GraphqlSearchOperator> for SearchOperator {
+    fn from(operator: GraphqlSearchOperator) -> Self {
+        match operator {
+            GraphqlSearchOperator::Like => SearchOperator::Like,
+            GraphqlSearchOperator::NotLike => SearchOperator::NotLike,
+            GraphqlSearchOperator::Ilike => SearchOperator::Ilike,
+            GraphqlSearchOperator::NotIlike => SearchOperator::NotIlike,
+            GraphqlSearchOperator::Similar => SearchOperator::Similar,
+            GraphqlSearchOperator::Equals => SearchOperator::Equals,
+            GraphqlSearchOperator::NotEquals => SearchOperator::NotEquals,
+            GraphqlSearchOperator::GreaterThan => SearchOperator::GreaterThan,
+            GraphqlSearchOperator::GreaterThanOrEquals => SearchOperator::GreaterThanOrEquals,
+            GraphqlSearchOperator::LessThan => SearchOperator::LessThan,
+            GraphqlSearchOperator::LessThanOrEquals => SearchOperator::LessThanOrEquals,
+            GraphqlSearchOperator::In => SearchOperator::In,
+            GraphqlSearchOperator::NotIn => SearchOperator::NotIn,
+            GraphqlSearchOperator::Contains => SearchOperator::Contains,
+            GraphqlSearchOperator::NotContains => SearchOperator::NotContains,
+            GraphqlSearchOperator::StartsWith => SearchOperator::StartsWith,
+            GraphqlSearchOperator::NotStartsWith => SearchOperator::NotStartsWith,
+            GraphqlSearchOperator::EndsWith => SearchOperator::EndsWith,
+            GraphqlSearchOperator::NotEndsWith => SearchOperator::NotEndsWith,
+            GraphqlSearchOperator::Exists => SearchOperator::Exists,
+            GraphqlSearchOperator::NotExists => SearchOperator::NotExists,
+            GraphqlSearchOperator::IsNull => SearchOperator::IsNull,
+            GraphqlSearchOperator::IsNotNull => SearchOperator::IsNotNull,
+            GraphqlSearchOperator::IsTrue => SearchOperator::IsTrue,
+            GraphqlSearchOperator::IsFalse => SearchOperator::IsFalse,
+            GraphqlSearchOperator::Between => SearchOperator::Between,
+            GraphqlSearchOperator::NotBetween => SearchOperator::NotBetween,
+        }
+    }
+}
+
+/// GraphQL equivalent of `SortDirection`. An omitted direction is sent on as `SortDirection::Unspecified`.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+#[graphql(name = "SortDirection")]
+pub enum GraphqlSortDirection {
+    Asc,
+    Desc,
+}
+
+impl From<GraphqlSortDirection> for SortDirection {
+    fn from(direction: GraphqlSortDirection) -> Self {
+        match direction {
+            GraphqlSortDirection::Asc => SortDirection::Asc,
+            GraphqlSortDirection::Desc => SortDirection::Desc,
+        }
+    }
+}
+
+/// GraphQL equivalent of `SortNulls`. An omitted value is sent on as `SortNulls::Unspecified`, which leaves the NULLs where the database puts them.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+#[graphql(name = "SortNulls")]
+pub enum GraphqlSortNulls {
+    First,
+    Last,
+}
+
+impl From<GraphqlSortNulls> for SortNulls {
+    fn from(nulls: GraphqlSortNulls) -> Self {
+        match nulls {
+            GraphqlSortNulls::First => SortNulls::First,
+            GraphqlSortNulls::Last => SortNulls::Last,
+        }
+    }
+}
+
+/// GraphQL equivalent of `SearchCountAccuracy`, used in the output metadata.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+#[graphql(name = "SearchCountAccuracy")]
+pub enum GraphqlSearchCountAccuracy {
+    Unspecified,
+    Exact,
+    Estimated,
+    LowerBound,
+}
+
+impl From<SearchCountAccuracy> for GraphqlSearchCountAccuracy {
+    fn from(accuracy: SearchCountAccuracy) -> Self {
+        match accuracy {
+            SearchCountAccuracy::Unspecified => GraphqlSearchCountAccuracy::Unspecified,
+            SearchCountAccuracy::Exact => GraphqlSearchCountAccuracy::Exact,
+            SearchCountAccuracy::Estimated => GraphqlSearchCountAccuracy::Estimated,
+            SearchCountAccuracy::LowerBound => GraphqlSearchCountAccuracy::LowerBound,
+        }
+    }
+}
+
+/// GraphQL equivalent of `SearchCondition`.
+///
+/// Custom operators are not in `GraphqlSearchOperator`, so they are named in `operator_name` and `operator` is left out.
+#[derive(InputObject, Clone, Debug)]
+#[graphql(name = "SearchConditionInput")]
+pub struct GraphqlSearchCondition {
+    pub field: String,
+    pub operator: Option<GraphqlSearchOperator>,
+    pub operator_name: Option<String>,
+    pub threshold: Option<f32>,
+    pub value: Option<String>,
+    pub value_to: Option<String>,
+    #[graphql(default)]
+    pub value_list: Vec<String>,
+}
+
+impl From<GraphqlSearchCondition> for SearchCondition {
+    fn from(condition: GraphqlSearchCondition) -> Self {
+        SearchCondition {
+            field: condition.field,
+            operator: condition
+                .operator
+                .map(SearchOperator::from)
+                .unwrap_or(SearchOperator::Unspecified) as i32,
+            operator_name: condition.operator_name,
+            threshold: condition.threshold,
+            value: condition.value,
+            value_to: condition.value_to,
+            value_list: condition.value_list,
+        }
+    }
+}
+
+/// GraphQL equivalent of `SearchFilter`.
+#[derive(InputObject, Clone, Debug, Default)]
+#[graphql(name = "SearchFilterInput")]
+pub struct GraphqlSearchFilter {
+    #[graphql(default)]
+    pub must: Vec<GraphqlSearchCondition>,
+    #[graphql(default)]
+    pub any: Vec<GraphqlSearchCondition>,
+}
+
+impl From<GraphqlSearchFilter> for SearchFilter {
+    fn from(filter: GraphqlSearchFilter) -> Self {
+        SearchFilter {
+            must: filter.must.into_iter().map(SearchCondition::from).collect(),
+            any: filter.any.into_iter().map(SearchCondition::from).collect(),
+        }
+    }
+}
+
+/// GraphQL equivalent of `SearchSortOption`.
+#[derive(InputObject, Clone, Debug)]
+#[graphql(name = "SearchSortOptionInput")]
+pub struct GraphqlSearchSortOption {
+    pub field: String,
+    pub order: Option<GraphqlSortDirection>,
+    pub nulls: Option<GraphqlSortNulls>,
+    pub case_insensitive: Option<bool>,
+}
+
+impl From<GraphqlSearchSortOption> for SearchSortOption {
+    fn from(sort: GraphqlSearchSortOption) -> Self {
+        SearchSortOption {
+            field: sort.field,
+            order: sort
+                .order
+                .map(SortDirection::from)
+                .unwrap_or(SortDirection::Unspecified) as i32,
+            nulls: sort
+                .nulls
+                .map(SortNulls::from)
+                .unwrap_or(SortNulls::Unspecified) as i32,
+            case_insensitive: sort.case_insensitive.unwrap_or_default(),
+        }
+    }
+}
+
+/// GraphQL equivalent of `SearchRelation`.
+#[derive(InputObject, Clone, Debug, Default)]
+#[graphql(name = "SearchRelationInput")]
+pub struct GraphqlSearchRelation {
+    #[graphql(default)]
+    pub parent_ids: Vec<i32>,
+    #[graphql(default)]
+    pub child_type: Vec<String>,
+}
+
+impl From<GraphqlSearchRelation> for SearchRelation {
+    fn from(relation: GraphqlSearchRelation) -> Self {
+        SearchRelation {
+            parent_ids: relation.parent_ids,
+            child_type: relation.child_type,
+        }
+    }
+}
+
+/// GraphQL equivalent of `SearchQuery`. The omitted fields take the same defaults as in the prost message.
+#[derive(InputObject, Clone, Debug, Default)]
+#[graphql(name = "SearchQueryInput")]
+pub struct GraphqlSearchQuery {
+    pub relation: Option<GraphqlSearchRelation>,
+    pub sort: Option<GraphqlSearchSortOption>,
+    #[graphql(default)]
+    pub offset: i32,
+    #[graphql(default)]
+    pub length: i32,
+    #[graphql(default)]
+    pub page: i32,
+    pub filter: Option<GraphqlSearchFilter>,
+    #[graphql(default)]
+    pub find_one: bool,
+    #[graphql(default)]
+    pub find_all: bool,
+    #[graphql(default)]
+    pub include_deleted: bool,
+    #[graphql(default)]
+    pub only_deleted: bool,
+    pub after: Option<String>,
+    #[graphql(default)]
+    pub include: Vec<String>,
+    #[graphql(default)]
+    pub distinct_on: Vec<String>,
+    #[graphql(default)]
+    pub group_by: Vec<String>,
+}
+
+impl From<GraphqlSearchQuery> for SearchQuery {
+    fn from(query: GraphqlSearchQuery) -> Self {
+        SearchQuery {
+            relation: query.relation.map(SearchRelation::from),
+            sort: query.sort.map(SearchSortOption::from),
+            offset: query.offset,
+            length: query.length,
+            page: query.page,
+            filter: query.filter.map(SearchFilter::from),
+            find_one: query.find_one,
+            find_all: query.find_all,
+            include_deleted: query.include_deleted,
+            only_deleted: query.only_deleted,
+            after: query.after,
+            include: query.include,
+            distinct_on: query.distinct_on,
+            group_by: query.group_by,
+        }
+    }
+}
+
+/// GraphQL output of `SearchResultMetadata`. The echoed `query` is left out, since the client already has it.
+#[derive(SimpleObject, Clone, Debug)]
+#[graphql(name = "SearchResultMetadata")]
+pub struct GraphqlSearchResultMetadata {
+    pub result_items: i32,
+    pub offset: i32,
+    pub length: i32,
+    pub page: i32,
+    pub result_total_pages: i32,
+    pub result_total_items: i32,
+    pub filter_count: Option<i32>,
+    pub filter_reason: Option<String>,
+    pub cache_hit: Option<bool>,
+    pub total_items_accuracy: GraphqlSearchCountAccuracy,
+    pub has_next_page: Option<bool>,
+    pub scanned_items: Option<i32>,
+    pub next_offset: Option<i32>,
+}
+
+impl From<SearchResultMetadata> for GraphqlSearchResultMetadata {
+    fn from(metadata: SearchResultMetadata) -> Self {
+        GraphqlSearchResultMetadata {
+            result_items: metadata.result_items,
+            offset: metadata.offset,
+            length: metadata.length,
+            page: metadata.page,
+            result_total_pages: metadata.result_total_pages,
+            result_total_items: metadata.result_total_items,
+            filter_count: metadata.filter_count,
+            filter_reason: metadata.filter_reason,
+            cache_hit: metadata.cache_hit,
+            total_items_accuracy: SearchCountAccuracy::try_from(metadata.total_items_accuracy)
+                .unwrap_or(SearchCountAccuracy::Unspecified)
+                .into(),
+            has_next_page: metadata.has_next_page,
+            scanned_items: metadata.scanned_items,
+            next_offset: metadata.next_offset,
+        }
+    }
+}
+
+/// The fields a `PebbleGraphqlConnection` carries on top of its edges and page info.
+#[derive(SimpleObject, Clone, Debug)]
+#[graphql(name = "PebbleConnectionFields")]
+pub struct PebbleGraphqlConnectionFields {
+    pub total_count: i32,
+    pub metadata: GraphqlSearchResultMetadata,
+}
+
+/// A GraphQL connection of the rows of a pebble query, named `<Node>Connection`.
+///
+/// The cursor of each edge is the pebble cursor of its row, see `pebble_connection`: send the `endCursor` back as `after` to get the rows after it.
+pub type PebbleGraphqlConnection<T> = Connection<String, T, PebbleGraphqlConnectionFields>;
+
+/// Build a connection from the metadata and rows of a pebble query, with the cursor of each row given by `cursor_of`, like `pebble_connection_from_generic`.
+pub fn pebble_graphql_connection<T, F>(
+    result: PebbleQueryResultGeneric<T>,
+    mut cursor_of: F,
+) -> PebbleGraphqlConnection<T>
+where
+    T: OutputType,
+    F: FnMut(&T) -> String,
+{
+    let edges = result
+        .results
+        .into_iter()
+        .map(|node| (cursor_of(&node), node))
+        .collect();
+    connection_from_edges(result.metadata, edges)
+}
+
+fn connection_from_edges<T: OutputType>(
+    metadata: SearchResultMetadata,
+    edges: Vec<(String, T)>,
+) -> PebbleGraphqlConnection<T> {
+    let page_info = page_info_from_metadata(&metadata, None, None);
+    let mut connection = Connection::with_additional_fields(
+        page_info.has_previous_page,
+        page_info.has_next_page,
+        PebbleGraphqlConnectionFields {
+            total_count: metadata.result_total_items,
+            metadata: metadata.into(),
+        },
+    );
+    connection.edges.extend(
+        edges
+            .into_iter()
+            .map(|(cursor, node)| Edge::new(cursor, node)),
+    );
+    connection
+}
+
+/// The connection of the rows of a pebble query, each converted into its GraphQL node with `into_node`.
+///
+/// The cursors are built from the sort key and the primary key of the models. Rows sorted by a computed field cannot be paged by keyset, so their cursor is their position in the whole result instead, i.e. the offset of the page plus their index in the page.
+fn model_connection<E, T, F>(
+    result: PebbleQueryResult<E>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    mut into_node: F,
+) -> PebbleGraphqlConnection<T>
+where
+    E: EntityTrait,
+    T: OutputType,
+    F: FnMut(E::Model) -> T,
+{
+    let query = result.metadata.query.clone().unwrap_or_default();
+    // the sort of a computed field has no column to build the cursors from.
+    let columns = cursor_key_columns(&query, field_to_column_map).ok();
+    let offset = result.metadata.offset.max(0) as usize;
+    let edges = result
+        .results
+        .into_iter()
+        .enumerate()
+        .map(|(index, model)| {
+            let cursor = match &columns {
+                Some(columns) => model_cursor::<E>(&model, columns),
+                None => (offset + index).to_string(),
+            };
+            (cursor, into_node(model))
+        })
+        .collect();
+    connection_from_edges(result.metadata, edges)
+}
+
+/// Convert a PebbleQueryError into a GraphQL error with a `code` extension.
+///
+/// The codes follow the same split as the tonic status mapping: `BAD_USER_INPUT` for invalid queries and queries exceeding the `PebbleQueryLimits`, `FORBIDDEN` for scope errors and deleted rows that are not allowed, `NOT_FOUND` for unknown entities and missing records, and `INTERNAL_SERVER_ERROR` otherwise.
+impl ErrorExtensions for PebbleQueryError {
+    fn extend(&self) -> async_graphql::Error {
+        let code = match self {
+            PebbleQueryError::InvalidConditionOperator(_)
+            | PebbleQueryError::InvalidOperator(_)
+            | PebbleQueryError::InvalidField(_)
+            | PebbleQueryError::MissingValue(_)
+            | PebbleQueryError::LeadingWildcardLike(_)
+            | PebbleQueryError::EmptyFilter(_)
+            | PebbleQueryError::InvalidCursor(_)
+            | PebbleQueryError::InvalidInclude(_)
+            | PebbleQueryError::InvalidValue(_)
+            | PebbleQueryError::InvalidGrouping(_)
+            | PebbleQueryError::TooManyConditions(_)
+            | PebbleQueryError::InListTooLarge(_)
+            | PebbleQueryError::PageLengthTooLarge(_)
+            | PebbleQueryError::OffsetTooLarge(_) => "BAD_USER_INPUT",
+            PebbleQueryError::ScopeError(_) | PebbleQueryError::DeletedRowsNotAllowed(_) => {
+                "FORBIDDEN"
+            }
+            PebbleQueryError::UnknownEntity(_)
+            | PebbleQueryError::SeaOrmDbError(DbErr::RecordNotFound(_)) => "NOT_FOUND",
+            PebbleQueryError::ExportError(_)
+            | PebbleQueryError::SerializationError(_)
+            | PebbleQueryError::CacheError(_)
+            | PebbleQueryError::SeaOrmDbError(_) => "INTERNAL_SERVER_ERROR",
+        };
+        async_graphql::Error::new(self.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", code);
+        })
+    }
+}
+
+/// # Run a GraphQL SearchQuery input with Pebble Query and return a connection
+///
+/// This converts the `SearchQueryInput` into a `SearchQuery`, runs it with `PebbleQueryOptions::connection_paging` against the initial select, converts each row into its GraphQL node with `into_node`, and wraps the rows into a `PebbleGraphqlConnection`.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query, e.g. `Entity::find()`.
+/// * `query`: Option<GraphqlSearchQuery> - the query argument of the resolver. If none, all entity entries will be returned.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection.
+/// * `into_node`: The conversion from an entity model to its GraphQL output type.
+///
+/// # Returns
+/// * `PebbleGraphqlConnection<T>`: The connection with the rows, the page info and the metadata.
+/// * `async_graphql::Error`: The error converted from `PebbleQueryError`, with a `code` extension.
+///
+/// # Example
+///
+/// ```rust
+/// # use async_graphql::{Context, Object, SimpleObject};
+/// # use pebble_query::pebble_graphql::{pebble_graphql_query, GraphqlSearchQuery, PebbleGraphqlConnection};
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// #[derive(SimpleObject)]
+/// pub struct Book {
+///     pub id: i32,
+///     pub title: String,
+/// }
+///
+/// impl From<book::Model> for Book {
+///     fn from(book: book::Model) -> Self {
+///         Book { id: book.id, title: book.title }
+///     }
+/// }
+///
+/// pub struct QueryRoot;
+///
+/// #[Object]
+/// impl QueryRoot {
+///     async fn books(
+///         &self,
+///         ctx: &Context<'_>,
+///         query: Option<GraphqlSearchQuery>,
+///     ) -> async_graphql::Result<PebbleGraphqlConnection<Book>> {
+///         let db = ctx.data::<DatabaseConnection>()?;
+///         pebble_graphql_query(book::Entity::find(), query, &BOOK_COLUMN_MAP, db, Book::from).await
+///     }
+/// }
+/// ```
+pub async fn pebble_graphql_query<C, E, M, T, F>(
+    initial_select: Select<E>,
+    query: Option<GraphqlSearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+    into_node: F,
+) -> async_graphql::Result<PebbleGraphqlConnection<T>>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    T: OutputType,
+    F: FnMut(M) -> T,
+{
+    let query = query.map(SearchQuery::from);
+    let options = PebbleQueryOptions {
+        connection_paging: true,
+        ..Default::default()
+    };
+
+    let result =
+        use_pebble_query_with_options(initial_select, query, field_to_column_map, db, &options)
+            .await
+            .map_err(|err| err.extend())?;
+
+    Ok(model_connection(result, field_to_column_map, into_node))
+}
+
+/// `pebble_graphql_query` with the query configuration of the entity: the scopes computed from `context`, the soft-delete column, the field policy (e.g. computed fields and custom operators) and the options, like `use_pebble_query_with_config`, with `PebbleQueryOptions::connection_paging`.
+pub async fn pebble_graphql_query_with_config<C, E, M, T, F, Ctx>(
+    initial_select: Select<E>,
+    query: Option<GraphqlSearchQuery>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+    into_node: F,
+) -> async_graphql::Result<PebbleGraphqlConnection<T>>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    T: OutputType,
+    F: FnMut(M) -> T,
+{
+    let query = query.map(SearchQuery::from).unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    let options = PebbleQueryOptions {
+        connection_paging: true,
+        ..config.options.clone()
+    };
+
+    let scoped_select = config
+        .apply_scopes(initial_select, context)
+        .map_err(|err| err.extend())?;
+    let scoped_select = config
+        .apply_soft_delete(scoped_select, &query)
+        .map_err(|err| err.extend())?;
+    let result = run_pebble_query(
+        scoped_select,
+        Some(query),
+        &config.field_to_column_map,
+        &config.field_policy,
+        db,
+        &options,
+    )
+    .await
+    .map_err(|err| err.extend())?;
+
+    Ok(model_connection(
+        result,
+        &config.field_to_column_map,
+        into_node,
+    ))
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use sea_orm::sea_query::{Alias, Expr, SimpleExpr};
+use sea_orm::{
+    ConnectionTrait, EntityTrait, FromQueryResult, Order, PaginatorTrait, QueryFilter, QueryResult,
+    QuerySelect, QueryTrait, Select,
+};
+use serde_json::Value;
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::{InvalidField, InvalidGrouping, InvalidOperator};
+use crate::pebble_converter::{PebbleConverter, PebbleConverterTrait};
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::pebble_query_options::PebbleQueryOptions;
+use crate::pebble_query_result::PebbleQueryResultGeneric;
+use crate::pebble_relative_time::{resolve_relative_times, PebbleClock};
+use crate::pebble_utils::{
+    is_string_column, normalize_query, order_by_with_nulls, sort_key_expr,
+    try_query_to_seaorm_conditions_with_policy, DEFAULT_PAGE_SIZE,
+};
+use crate::sample_dto_structs::{SearchQuery, SortDirection};
+
+/// The name of the column holding the number of rows of each group in the rows of a `group_by` query.
+///
+/// It starts with an underscore so that it cannot be mistaken for a field of the entity; a field with this name cannot be grouped by.
+pub const GROUP_COUNT_FIELD: &str = "_count";
+
+/// # Apply and run a distinct or group-by SearchQuery
+///
+/// With `distinct_on`, this selects the distinct combinations of the listed fields among the rows matching the filter, e.g. the distinct authors of the books matching the filter. With `group_by`, this selects one row per group of the listed fields, with the number of rows in the group as `_count`, see `GROUP_COUNT_FIELD`.
+///
+/// The rows are returned as JSON objects keyed by the field names. They can only be sorted by one of the listed fields or, with `group_by`, by `_count`.
+///
+/// The pagination counts are computed over the distinct or grouped rows, not over the rows of the entity, so `result_total_items` is the number of groups.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query.
+/// * `query`: The SearchQuery with either `distinct_on` or `group_by`.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection.
+///
+/// # Example
+///
+/// ```rust
+/// use pebble_query::errors::PebbleQueryError;
+/// use pebble_query::pebble_grouping::{use_pebble_query_grouped, GROUP_COUNT_FIELD};
+/// use pebble_query::sample_dto_structs::{SearchQuery, SearchSortOption, SortDirection};
+/// use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// use sea_orm::{DatabaseConnection, EntityTrait};
+///
+/// async fn books_per_author(db: &DatabaseConnection) -> Result<(), PebbleQueryError> {
+///     let query = SearchQuery {
+///         group_by: vec!["author_id".to_string()],
+///         sort: Some(SearchSortOption {
+///             field: GROUP_COUNT_FIELD.to_string(),
+///             order: SortDirection::Desc as i32,
+///             ..Default::default()
+///         }),
+///         ..Default::default()
+///     };
+///     let result = use_pebble_query_grouped(book::Entity::find(), query, &BOOK_COLUMN_MAP, db).await?;
+///     // result.results: [{"author_id": 3, "_count": 12}, {"author_id": 1, "_count": 7}, ...]
+///     println!("{:?}", result.results);
+///     Ok(())
+/// }
+/// ```
+pub async fn use_pebble_query_grouped<C, E>(
+    initial_select: Select<E>,
+    query: SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+) -> Result<PebbleQueryResultGeneric<Value>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+{
+    run_pebble_query_grouped(
+        initial_select,
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+        db,
+        &PebbleQueryOptions::default(),
+    )
+    .await
+}
+
+/// # Apply and run a distinct or group-by SearchQuery with the entity's query configuration
+///
+/// This is `use_pebble_query_grouped` restricted to the scopes and soft-delete column of a `PebbleQueryConfig`, with its field policy, limits and clock, like `use_pebble_query_with_config`. Computed fields can be grouped by like the fields of the field map.
+pub async fn use_pebble_query_grouped_with_config<C, E, Ctx>(
+    initial_select: Select<E>,
+    query: SearchQuery,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+) -> Result<PebbleQueryResultGeneric<Value>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+{
+    let scoped_select = config.apply_scopes(initial_select, context)?;
+    let scoped_select = config.apply_soft_delete(scoped_select, &query)?;
+    run_pebble_query_grouped(
+        scoped_select,
+        query,
+        &config.field_to_column_map,
+        &config.field_policy,
+        db,
+        &config.options,
+    )
+    .await
+}
+
+async fn run_pebble_query_grouped<C, E>(
+    initial_select: Select<E>,
+    query: SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    db: &C,
+    options: &PebbleQueryOptions,
+) -> Result<PebbleQueryResultGeneric<Value>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+{
+    options.limits.validate(&query)?;
+    let backend_policy = field_policy.for_backend(db.get_database_backend());
+    let field_policy: &PebbleFieldPolicy = &backend_policy;
+    let resolved_query = resolve_relative_times(&query, field_to_column_map, &options.clock)?;
+    let grouped_select = try_query_to_grouped_select_with_policy(
+        &resolved_query,
+        field_to_column_map,
+        field_policy,
+        initial_select,
+    )?;
+
+    let page_select = paginate_grouped_select(grouped_select.clone(), &query);
+    let rows = db
+        .query_all(page_select.build(db.get_database_backend()))
+        .await?;
+    let normalized_query = normalize_query(&query);
+    let mut results: Vec<Value> = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut result = Value::from_query_result(&row, "")?;
+        // SQLite does not report a type for expressions such as `COUNT(*)` or computed fields, so their values are read explicitly.
+        if !query.group_by.is_empty() {
+            result[GROUP_COUNT_FIELD] = row.try_get::<i64>("", GROUP_COUNT_FIELD)?.into();
+        }
+        for field in normalized_query
+            .distinct_on
+            .iter()
+            .chain(normalized_query.group_by.iter())
+        {
+            if result.get(field).is_none() {
+                result[field] = untyped_value(&row, field);
+            }
+        }
+        results.push(result);
+    }
+
+    let page_size = match query.length > 0 {
+        true => query.length as u64,
+        false => DEFAULT_PAGE_SIZE,
+    };
+    let total_items_and_pages_number = grouped_select
+        .into_json()
+        .paginate(db, page_size)
+        .num_items_and_pages()
+        .await?;
+
+    let metadata = PebbleConverter::query_many_result_to_standard_query_result::<E>(
+        results.len() as i32,
+        total_items_and_pages_number.number_of_items as i32,
+        total_items_and_pages_number.number_of_pages as i32,
+        query,
+    );
+    Ok(PebbleQueryResultGeneric { metadata, results })
+}
+
+/// Read a column the database reports no type for, trying the JSON value types in turn.
+fn untyped_value(row: &QueryResult, column: &str) -> Value {
+    if let Ok(Some(value)) = row.try_get::<Option<i64>>("", column) {
+        return value.into();
+    }
+    if let Ok(Some(value)) = row.try_get::<Option<f64>>("", column) {
+        return value.into();
+    }
+    if let Ok(Some(value)) = row.try_get::<Option<String>>("", column) {
+        return value.into();
+    }
+    Value::Null
+}
+
+/// Limit a distinct or group-by select to the page of the SearchQuery.
+fn paginate_grouped_select<E: EntityTrait>(
+    grouped_select: Select<E>,
+    query: &SearchQuery,
+) -> Select<E> {
+    let mut page_select = grouped_select;
+    if query.find_one {
+        page_select = page_select.limit(1);
+    } else if query.length > 0 {
+        page_select = page_select.limit(query.length as u64);
+    }
+    if query.offset > 0 {
+        page_select = page_select.offset(query.offset as u64);
+    }
+    page_select
+}
+
+/// Build the distinct or group-by select of a SearchQuery, with its filter and sort but without pagination.
+///
+/// Relative date and time values are resolved against the system clock.
+pub fn try_query_to_grouped_select<E: EntityTrait>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    initial_select: Select<E>,
+) -> Result<Select<E>, PebbleQueryError> {
+    let resolved_query =
+        resolve_relative_times(query, field_to_column_map, &PebbleClock::default())?;
+    try_query_to_grouped_select_with_policy(
+        &resolved_query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+        initial_select,
+    )
+}
+
+/// Build the distinct or group-by select of a SearchQuery like `try_query_to_grouped_select`, with the computed fields, sort collations and custom operators of the field policy.
+///
+/// Like `try_query_to_select_with_policy`, relative date and time values are not resolved, see `resolve_relative_times`.
+pub fn try_query_to_grouped_select_with_policy<E: EntityTrait>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    initial_select: Select<E>,
+) -> Result<Select<E>, PebbleQueryError> {
+    if query
+        .distinct_on
+        .iter()
+        .chain(query.group_by.iter())
+        .any(|field| field == GROUP_COUNT_FIELD)
+    {
+        return Err(InvalidGrouping(format!(
+            "{} is the name of the group size and cannot be a grouped field",
+            GROUP_COUNT_FIELD
+        )));
+    }
+    let normalized_query = normalize_query(query);
+    let (fields, is_group_by) = match (
+        normalized_query.distinct_on.is_empty(),
+        normalized_query.group_by.is_empty(),
+    ) {
+        (false, true) => (&normalized_query.distinct_on, false),
+        (true, false) => (&normalized_query.group_by, true),
+        (false, false) => {
+            return Err(InvalidGrouping(
+                "distinct_on and group_by cannot be used together".to_string(),
+            ))
+        }
+        (true, true) => {
+            return Err(InvalidGrouping(
+                "either distinct_on or group_by is required".to_string(),
+            ))
+        }
+    };
+
+    // each field with its expression and whether a case-insensitive sort lowercases it, which only string columns and text computed fields are.
+    let mut field_exprs: Vec<(&String, SimpleExpr, bool)> = Vec::new();
+    for field in fields {
+        let (expr, lowercased) = match field_to_column_map.get(field) {
+            Some(column_tuple) => (
+                Expr::col(*column_tuple).into(),
+                is_string_column(&column_tuple.1),
+            ),
+            None => (
+                field_policy.computed_field(field).cloned().ok_or_else(|| {
+                    InvalidField(format!(
+                        "Column {} not provided in field_to_column_map",
+                        field
+                    ))
+                })?,
+                field_policy.is_text_computed_field(field),
+            ),
+        };
+        field_exprs.push((field, expr, lowercased));
+    }
+
+    let (must_conditions, any_conditions) =
+        try_query_to_seaorm_conditions_with_policy(query, field_to_column_map, field_policy)?;
+    let mut select = initial_select.select_only();
+    if !must_conditions.is_empty() {
+        select = select.filter(must_conditions);
+    }
+    if !any_conditions.is_empty() {
+        select = select.filter(any_conditions);
+    }
+
+    for (field, expr, _) in &field_exprs {
+        select = select.column_as(expr.clone(), field.as_str());
+    }
+    if is_group_by {
+        for (_, expr, _) in &field_exprs {
+            select = select.group_by(expr.clone());
+        }
+        select = select.column_as(Expr::cust("COUNT(*)"), GROUP_COUNT_FIELD);
+    } else {
+        select = select.distinct();
+    }
+
+    // the sort field is compared before it is normalized, as normalizing drops the leading underscore of `_count`.
+    let sort_by_count = query
+        .sort
+        .as_ref()
+        .is_some_and(|sort| sort.field == GROUP_COUNT_FIELD);
+    if let Some(sort) = &normalized_query.sort {
+        let order = match SortDirection::try_from(sort.order)
+            .map_err(|_| InvalidOperator(format!("Invalid sort direction: {}", sort.order)))?
+        {
+            SortDirection::Desc => Order::Desc,
+            SortDirection::Asc | SortDirection::Unspecified => Order::Asc,
+        };
+        let sort_expr = if is_group_by && sort_by_count {
+            Expr::col(Alias::new(GROUP_COUNT_FIELD)).into()
+        } else {
+            field_exprs
+                .iter()
+                .find(|(field, _, _)| **field == sort.field)
+                .map(|(field, expr, lowercased)| {
+                    sort_key_expr(
+                        expr.clone(),
+                        sort.case_insensitive && *lowercased,
+                        field_policy.sort_collation(field),
+                    )
+                })
+                .ok_or_else(|| {
+                    InvalidField(format!(
+                        "{} is not one of the distinct or grouped fields and cannot be sorted",
+                        sort.field
+                    ))
+                })?
+        };
+        select = order_by_with_nulls(select, sort_expr, order, sort.nulls)?;
+    }
+
+    Ok(select)
+}
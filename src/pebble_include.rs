@@ -0,0 +1,467 @@
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::{
+    ConnectionTrait, EntityTrait, FromQueryResult, LoaderTrait, Related, RelationType, Select,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::{InvalidInclude, SerializationError};
+use crate::pebble_query::{try_use_pebble_query, use_pebble_query_with_config};
+use crate::pebble_query_config::PebbleQueryConfig;
+use crate::pebble_query_result::PebbleQueryResult;
+use crate::sample_dto_structs::{SearchQuery, SearchResultMetadata};
+
+/// Batch-loads one relation of the rows of a page, as JSON.
+///
+/// `context` is the request context of `use_pebble_query_with_includes_with_config`, e.g. to restrict the related rows to the scopes of the user, or `()`.
+#[async_trait]
+pub trait PebbleIncludeLoader<E: EntityTrait, C, Ctx = ()>: Send + Sync {
+    /// Load the related models of each row, in the order of the rows: an object or `null` for a has-one or belongs-to relation, an array otherwise.
+    async fn load(
+        &self,
+        models: &[E::Model],
+        context: &Ctx,
+        db: &C,
+    ) -> Result<Vec<Value>, PebbleQueryError>;
+}
+
+/// The loader of a direct relation `E -> R`, with `LoaderTrait::load_one` or `LoaderTrait::load_many` depending on the relation type.
+pub struct PebbleRelatedLoader<R>(PhantomData<R>);
+
+/// The loader of a many-to-many relation `E -> V -> R`, with `LoaderTrait::load_many_to_many`.
+pub struct PebbleRelatedViaLoader<R, V>(PhantomData<(R, V)>);
+
+/// The loader of a direct relation `E -> R` like `PebbleRelatedLoader`, which only loads the related rows within the scopes and soft delete of their query configuration.
+pub struct PebbleConfiguredRelatedLoader<R: EntityTrait, Ctx> {
+    pub config: PebbleQueryConfig<R, Ctx>,
+}
+
+/// The loader of a many-to-many relation `E -> V -> R` like `PebbleRelatedViaLoader`, which only loads the related rows within the scopes and soft delete of their query configuration.
+pub struct PebbleConfiguredRelatedViaLoader<R: EntityTrait, V, Ctx> {
+    pub config: PebbleQueryConfig<R, Ctx>,
+    via: PhantomData<V>,
+}
+
+/// The select of the related rows `R` the user may see: the ones within the scopes computed from `context` that are not soft-deleted.
+fn configured_select<R: EntityTrait, Ctx>(
+    config: &PebbleQueryConfig<R, Ctx>,
+    context: &Ctx,
+) -> Result<Select<R>, PebbleQueryError> {
+    let scoped_select = config.apply_scopes(R::find(), context)?;
+    config.apply_soft_delete(scoped_select, &SearchQuery::default())
+}
+
+/// Load a direct relation from `related_select`, with `LoaderTrait::load_one` or `LoaderTrait::load_many` depending on the relation type.
+async fn load_related<E, R, C>(
+    models: &[E::Model],
+    related_select: Select<R>,
+    db: &C,
+) -> Result<Vec<Value>, PebbleQueryError>
+where
+    E: EntityTrait + Related<R>,
+    E::Model: Sync,
+    R: EntityTrait,
+    R::Model: Serialize + Send + Sync,
+    C: ConnectionTrait,
+{
+    let values = if <E as Related<R>>::to().rel_type == RelationType::HasOne {
+        models
+            .load_one(related_select, db)
+            .await?
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<Value>, serde_json::Error>>()
+    } else {
+        models
+            .load_many(related_select, db)
+            .await?
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<Value>, serde_json::Error>>()
+    };
+    values.map_err(|err| SerializationError(err.to_string()))
+}
+
+/// Load a many-to-many relation through `V` from `related_select`, with `LoaderTrait::load_many_to_many`.
+async fn load_related_via<E, R, V, C>(
+    models: &[E::Model],
+    related_select: Select<R>,
+    db: &C,
+) -> Result<Vec<Value>, PebbleQueryError>
+where
+    E: EntityTrait + Related<R>,
+    E::Model: Sync,
+    R: EntityTrait,
+    R::Model: Serialize + Send + Sync,
+    V: EntityTrait,
+    V::Model: Send + Sync,
+    C: ConnectionTrait,
+{
+    models
+        .load_many_to_many(related_select, V::default(), db)
+        .await?
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, serde_json::Error>>()
+        .map_err(|err| SerializationError(err.to_string()))
+}
+
+#[async_trait]
+impl<E, R, C, Ctx> PebbleIncludeLoader<E, C, Ctx> for PebbleRelatedLoader<R>
+where
+    E: EntityTrait + Related<R>,
+    E::Model: Sync,
+    R: EntityTrait,
+    R::Model: Serialize + Send + Sync,
+    C: ConnectionTrait,
+    Ctx: Sync,
+{
+    async fn load(
+        &self,
+        models: &[E::Model],
+        _context: &Ctx,
+        db: &C,
+    ) -> Result<Vec<Value>, PebbleQueryError> {
+        load_related::<E, R, C>(models, R::find(), db).await
+    }
+}
+
+#[async_trait]
+impl<E, R, V, C, Ctx> PebbleIncludeLoader<E, C, Ctx> for PebbleRelatedViaLoader<R, V>
+where
+    E: EntityTrait + Related<R>,
+    E::Model: Sync,
+    R: EntityTrait,
+    R::Model: Serialize + Send + Sync,
+    V: EntityTrait,
+    V::Model: Send + Sync,
+    C: ConnectionTrait,
+    Ctx: Sync,
+{
+    async fn load(
+        &self,
+        models: &[E::Model],
+        _context: &Ctx,
+        db: &C,
+    ) -> Result<Vec<Value>, PebbleQueryError> {
+        load_related_via::<E, R, V, C>(models, R::find(), db).await
+    }
+}
+
+#[async_trait]
+impl<E, R, C, Ctx> PebbleIncludeLoader<E, C, Ctx> for PebbleConfiguredRelatedLoader<R, Ctx>
+where
+    E: EntityTrait + Related<R>,
+    E::Model: Sync,
+    R: EntityTrait,
+    R::Model: Serialize + Send + Sync,
+    C: ConnectionTrait,
+    Ctx: Sync,
+{
+    async fn load(
+        &self,
+        models: &[E::Model],
+        context: &Ctx,
+        db: &C,
+    ) -> Result<Vec<Value>, PebbleQueryError> {
+        let related_select = configured_select(&self.config, context)?;
+        load_related::<E, R, C>(models, related_select, db).await
+    }
+}
+
+#[async_trait]
+impl<E, R, V, C, Ctx> PebbleIncludeLoader<E, C, Ctx> for PebbleConfiguredRelatedViaLoader<R, V, Ctx>
+where
+    E: EntityTrait + Related<R>,
+    E::Model: Sync,
+    R: EntityTrait,
+    R::Model: Serialize + Send + Sync,
+    V: EntityTrait,
+    V::Model: Send + Sync,
+    C: ConnectionTrait,
+    Ctx: Sync,
+{
+    async fn load(
+        &self,
+        models: &[E::Model],
+        context: &Ctx,
+        db: &C,
+    ) -> Result<Vec<Value>, PebbleQueryError> {
+        let related_select = configured_select(&self.config, context)?;
+        load_related_via::<E, R, V, C>(models, related_select, db).await
+    }
+}
+
+/// # The relations of an entity that clients can include in a SearchQuery
+///
+/// Each relation is registered under the name clients put into `SearchQuery.include`. The related models must implement `Serialize`, as they are returned as JSON.
+///
+/// With `with_include_with_config` and `with_include_via_with_config`, only the related rows within the scopes and soft delete of their own query configuration are loaded; the scopes are computed from the context given to `use_pebble_query_with_includes_with_config`, which `Ctx` is the type of.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::pebble_include::PebbleIncludes;
+/// # use pebble_query::sample_entities::{author, book};
+/// # use sea_orm::DatabaseConnection;
+/// let book_includes: PebbleIncludes<book::Entity, DatabaseConnection> =
+///     PebbleIncludes::new().with_include::<author::Entity>("author");
+/// let author_includes: PebbleIncludes<author::Entity, DatabaseConnection> =
+///     PebbleIncludes::new().with_include::<book::Entity>("books");
+/// ```
+pub struct PebbleIncludes<E: EntityTrait, C, Ctx = ()> {
+    loaders: HashMap<String, Box<dyn PebbleIncludeLoader<E, C, Ctx>>>,
+}
+
+impl<E: EntityTrait, C, Ctx> Default for PebbleIncludes<E, C, Ctx> {
+    fn default() -> Self {
+        PebbleIncludes {
+            loaders: HashMap::new(),
+        }
+    }
+}
+
+impl<E, C, Ctx> PebbleIncludes<E, C, Ctx>
+where
+    E: EntityTrait,
+    E::Model: Sync,
+    C: ConnectionTrait,
+    Ctx: Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a has-one, belongs-to or has-many relation to `R` under `name`.
+    pub fn with_include<R>(mut self, name: &str) -> Self
+    where
+        E: Related<R>,
+        R: EntityTrait,
+        R::Model: Serialize + Send + Sync,
+    {
+        self.loaders.insert(
+            name.to_string(),
+            Box::new(PebbleRelatedLoader::<R>(PhantomData)),
+        );
+        self
+    }
+
+    /// Register a many-to-many relation to `R` through the junction entity `V` under `name`.
+    pub fn with_include_via<R, V>(mut self, name: &str) -> Self
+    where
+        E: Related<R>,
+        R: EntityTrait,
+        R::Model: Serialize + Send + Sync,
+        V: EntityTrait,
+        V::Model: Send + Sync,
+    {
+        self.loaders.insert(
+            name.to_string(),
+            Box::new(PebbleRelatedViaLoader::<R, V>(PhantomData)),
+        );
+        self
+    }
+
+    /// Register a has-one, belongs-to or has-many relation to `R` under `name`, loading only the related rows within the scopes and soft delete of `config`.
+    pub fn with_include_with_config<R>(
+        mut self,
+        name: &str,
+        config: PebbleQueryConfig<R, Ctx>,
+    ) -> Self
+    where
+        E: Related<R>,
+        R: EntityTrait,
+        R::Model: Serialize + Send + Sync,
+    {
+        self.loaders.insert(
+            name.to_string(),
+            Box::new(PebbleConfiguredRelatedLoader { config }),
+        );
+        self
+    }
+
+    /// Register a many-to-many relation to `R` through the junction entity `V` under `name`, loading only the related rows within the scopes and soft delete of `config`.
+    pub fn with_include_via_with_config<R, V>(
+        mut self,
+        name: &str,
+        config: PebbleQueryConfig<R, Ctx>,
+    ) -> Self
+    where
+        E: Related<R>,
+        R: EntityTrait,
+        R::Model: Serialize + Send + Sync,
+        V: EntityTrait,
+        V::Model: Send + Sync,
+    {
+        self.loaders.insert(
+            name.to_string(),
+            Box::new(PebbleConfiguredRelatedViaLoader::<R, V, Ctx> {
+                config,
+                via: PhantomData,
+            }),
+        );
+        self
+    }
+
+    /// Register a custom loader under `name`.
+    pub fn with_loader<L>(mut self, name: &str, loader: L) -> Self
+    where
+        L: PebbleIncludeLoader<E, C, Ctx> + 'static,
+    {
+        self.loaders.insert(name.to_string(), Box::new(loader));
+        self
+    }
+
+    /// Check that every name in `include` is registered.
+    pub fn validate(&self, include: &[String]) -> Result<(), PebbleQueryError> {
+        match include
+            .iter()
+            .find(|name| !self.loaders.contains_key(*name))
+        {
+            Some(name) => Err(InvalidInclude(format!(
+                "{} is not a registered relation",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Load the included relations of the rows, one map from relation name to JSON per row.
+    pub async fn load(
+        &self,
+        include: &[String],
+        models: &[E::Model],
+        context: &Ctx,
+        db: &C,
+    ) -> Result<Vec<BTreeMap<String, Value>>, PebbleQueryError> {
+        self.validate(include)?;
+        let mut included: Vec<BTreeMap<String, Value>> = vec![BTreeMap::new(); models.len()];
+        for name in include {
+            let values = self.loaders[name].load(models, context, db).await?;
+            for (row_included, value) in included.iter_mut().zip(values) {
+                row_included.insert(name.clone(), value);
+            }
+        }
+        Ok(included)
+    }
+}
+
+/// A row with its included related models, keyed by the relation name.
+#[derive(Clone, Debug, Serialize)]
+pub struct PebbleRowWithIncludes<M> {
+    pub row: M,
+    pub included: BTreeMap<String, Value>,
+}
+
+/// The result of `use_pebble_query_with_includes`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PebbleQueryResultWithIncludes<M> {
+    pub metadata: SearchResultMetadata,
+    pub results: Vec<PebbleRowWithIncludes<M>>,
+}
+
+/// # Apply and run SearchQuery, and batch-load the relations listed in its `include`
+///
+/// This runs `use_pebble_query`, so the pagination and the counts are those of the rows alone, then loads each included relation for the whole page with one query per relation instead of one per row.
+///
+/// The names in `include` are checked against `includes` before any query is run, and an unregistered name is a `PebbleQueryError::InvalidInclude`.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query.
+/// * `query`: Option<SearchQuery> object - the query to be applied to the select. If none, all entity entries will be returned without relations.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `includes`: The relations clients can include.
+/// * `db`: The database connection.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::sync::LazyLock;
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_include::{use_pebble_query_with_includes, PebbleIncludes};
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{author, book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// static BOOK_INCLUDES: LazyLock<PebbleIncludes<book::Entity, DatabaseConnection>> =
+///     LazyLock::new(|| PebbleIncludes::new().with_include::<author::Entity>("author"));
+///
+/// # async fn example(db: &DatabaseConnection, query: SearchQuery) -> Result<(), PebbleQueryError> {
+/// // query.include = vec!["author".to_string()]
+/// let result = use_pebble_query_with_includes(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, &BOOK_INCLUDES, db).await?;
+/// let author = &result.results[0].included["author"];
+/// # Ok(())
+/// # }
+/// ```
+pub async fn use_pebble_query_with_includes<C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    includes: &PebbleIncludes<E, C>,
+    db: &C,
+) -> Result<PebbleQueryResultWithIncludes<M>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    let include = query
+        .as_ref()
+        .map(|query| query.include.clone())
+        .unwrap_or_default();
+    includes.validate(&include)?;
+
+    let result = try_use_pebble_query(initial_select, query, field_to_column_map, db).await?;
+    let included = includes.load(&include, &result.results, &(), db).await?;
+
+    Ok(with_includes(result, included))
+}
+
+/// `use_pebble_query_with_includes` with the query configuration of the entity: the scopes computed from `context`, the soft-delete column, the field policy and the options, like `use_pebble_query_with_config`.
+///
+/// The relations registered with `with_include_with_config` are restricted to the scopes and soft delete of their own configuration, computed from the same `context`.
+pub async fn use_pebble_query_with_includes_with_config<C, E, M, Ctx>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    includes: &PebbleIncludes<E, C, Ctx>,
+    context: &Ctx,
+    db: &C,
+) -> Result<PebbleQueryResultWithIncludes<M>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    Ctx: Sync + 'static,
+{
+    let include = query
+        .as_ref()
+        .map(|query| query.include.clone())
+        .unwrap_or_default();
+    includes.validate(&include)?;
+
+    let result = use_pebble_query_with_config(initial_select, query, config, context, db).await?;
+    let included = includes
+        .load(&include, &result.results, context, db)
+        .await?;
+
+    Ok(with_includes(result, included))
+}
+
+fn with_includes<E: EntityTrait>(
+    result: PebbleQueryResult<E>,
+    included: Vec<BTreeMap<String, Value>>,
+) -> PebbleQueryResultWithIncludes<E::Model> {
+    PebbleQueryResultWithIncludes {
+        metadata: result.metadata,
+        results: result
+            .results
+            .into_iter()
+            .zip(included)
+            .map(|(row, included)| PebbleRowWithIncludes { row, included })
+            .collect(),
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use sea_orm::sea_query::{Alias, BinOper, Expr, Func, Query, SimpleExpr};
+use sea_orm::{DbBackend, EntityTrait};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::{InvalidField, MissingValue};
+use crate::sample_dto_structs::SearchCondition;
+
+/// How the JSON path fields of the field map, e.g. `meta.color` for a `meta.*` entry, are read from their JSON column.
+///
+/// Queries use the dialect of the database they run on, unless `PebbleQueryConfig::with_json_dialect` overrides it. Selects built without a connection, e.g. by `try_query_to_select`, use SQLite's.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PebbleJsonDialect {
+    /// `json_extract(meta, '$."color"')`, `json_type` for typed comparisons, and `json_each` for containment.
+    #[default]
+    Sqlite,
+    /// `meta #>> '{"color"}'`, `jsonb_typeof` for typed comparisons, and `meta @> '{"tags": ["red"]}'` for containment, which need a `jsonb` column.
+    Postgres,
+    /// `JSON_EXTRACT(meta, '$."color"')`, and `JSON_CONTAINS` for containment.
+    MySql,
+}
+
+impl From<DbBackend> for PebbleJsonDialect {
+    fn from(backend: DbBackend) -> Self {
+        match backend {
+            DbBackend::Sqlite => PebbleJsonDialect::Sqlite,
+            DbBackend::Postgres => PebbleJsonDialect::Postgres,
+            DbBackend::MySql => PebbleJsonDialect::MySql,
+        }
+    }
+}
+
+/// The prefix of the JSON path fields of a field map entry, e.g. `meta` for `meta.*`, or `None` if the entry is a plain field.
+pub fn json_path_prefix(field: &str) -> Option<&str> {
+    field.strip_suffix(".*")
+}
+
+/// The JSON column and the path of a JSON path field, e.g. the `meta` column and `dims.width` for `meta.dims.width` with a `meta.*` entry in the field map.
+pub fn json_path_column<'a, E: EntityTrait>(
+    field: &'a str,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Option<((E, <E as EntityTrait>::Column), &'a str)> {
+    let (prefix, path) = field.split_once('.')?;
+    field_to_column_map
+        .get(&format!("{}.*", prefix))
+        .map(|column| (*column, path))
+}
+
+/// Split a JSON path into its keys. Keys that are numbers are array indexes.
+fn json_path_segments(path: &str) -> Result<Vec<&str>, PebbleQueryError> {
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments
+        .iter()
+        .any(|segment| segment.is_empty() || segment.contains(['"', '\\']))
+    {
+        return Err(InvalidField(format!(
+            "{} is not a valid JSON path, e.g. dims.width or tags.0",
+            path
+        )));
+    }
+    Ok(segments)
+}
+
+fn is_array_index(segment: &str) -> bool {
+    segment.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// The path in the syntax of `json_extract`, e.g. `$."dims"."width"` or `$."tags"[0]`.
+fn json_extract_path(segments: &[&str]) -> String {
+    segments.iter().fold("$".to_string(), |path, segment| {
+        match is_array_index(segment) {
+            true => format!("{}[{}]", path, segment),
+            false => format!("{}.\"{}\"", path, segment),
+        }
+    })
+}
+
+/// The Postgres path of `segments`, e.g. `'{"dims","width"}'::text[]`.
+fn postgres_path(segments: &[&str]) -> SimpleExpr {
+    let path = segments
+        .iter()
+        .map(|segment| format!("\"{}\"", segment))
+        .collect::<Vec<String>>()
+        .join(",");
+    Expr::val(format!("{{{}}}", path)).cast_as(Alias::new("text[]"))
+}
+
+/// The value at `path` in the JSON column `column`: typed on SQLite and MySQL, text on Postgres.
+fn json_value_expr(
+    column: SimpleExpr,
+    segments: &[&str],
+    dialect: PebbleJsonDialect,
+) -> SimpleExpr {
+    match dialect {
+        PebbleJsonDialect::Sqlite | PebbleJsonDialect::MySql => {
+            Func::cust(Alias::new("json_extract"))
+                .arg(column)
+                .arg(json_extract_path(segments))
+                .into()
+        }
+        PebbleJsonDialect::Postgres => {
+            column.binary(BinOper::Custom("#>>"), postgres_path(segments))
+        }
+    }
+}
+
+/// The type of JSON value a condition compares the value at its path with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum JsonValueType {
+    Number,
+    Boolean,
+}
+
+/// The value at `path` if it is a JSON value of `value_type`, and `NULL` otherwise, so that e.g. `meta.zip > 5000` skips the zip codes stored as strings rather than failing to cast them on Postgres.
+fn json_typed_value_expr(
+    column: SimpleExpr,
+    segments: &[&str],
+    dialect: PebbleJsonDialect,
+    value_type: JsonValueType,
+) -> SimpleExpr {
+    let guard = match dialect {
+        PebbleJsonDialect::Sqlite => {
+            let json_types = match value_type {
+                JsonValueType::Number => ["integer", "real"],
+                JsonValueType::Boolean => ["true", "false"],
+            };
+            Expr::expr(
+                Func::cust(Alias::new("json_type"))
+                    .arg(column.clone())
+                    .arg(json_extract_path(segments)),
+            )
+            .is_in(json_types)
+        }
+        PebbleJsonDialect::Postgres => {
+            let json_type = match value_type {
+                JsonValueType::Number => "number",
+                JsonValueType::Boolean => "boolean",
+            };
+            Expr::expr(
+                Func::cust(Alias::new("jsonb_typeof")).arg(
+                    column
+                        .clone()
+                        .binary(BinOper::Custom("#>"), postgres_path(segments)),
+                ),
+            )
+            .eq(json_type)
+        }
+        // `JSON_EXTRACT` returns JSON values, which MySQL only compares with values of their own type.
+        PebbleJsonDialect::MySql => return json_value_expr(column, segments, dialect),
+    };
+    let value = match (dialect, value_type) {
+        (PebbleJsonDialect::Postgres, JsonValueType::Number) => {
+            Expr::expr(json_value_expr(column, segments, dialect)).cast_as(Alias::new("numeric"))
+        }
+        (PebbleJsonDialect::Postgres, JsonValueType::Boolean) => {
+            Expr::expr(json_value_expr(column, segments, dialect)).cast_as(Alias::new("boolean"))
+        }
+        _ => json_value_expr(column, segments, dialect),
+    };
+    Expr::case(guard, value).into()
+}
+
+/// The string inside the double quotes of a quoted condition value, e.g. `02134` for `"02134"`.
+fn unquoted(value: &str) -> Option<&str> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+}
+
+/// The JSON value a condition value stands for: a string if it is quoted, e.g. `"02134"`, or else a number or a boolean if it parses as one, and a string otherwise.
+fn json_scalar(value: &str) -> JsonValue {
+    if let Some(string) = unquoted(value) {
+        return string.into();
+    }
+    if let Ok(number) = value.parse::<i64>() {
+        return number.into();
+    }
+    if let Ok(number) = value.parse::<f64>() {
+        return number.into();
+    }
+    match value {
+        "true" => true.into(),
+        "false" => false.into(),
+        _ => value.into(),
+    }
+}
+
+/// # The comparison of a condition on a JSON path field
+///
+/// Returns the expression the condition compares with its values, the condition with its values unquoted, and whether the values are compared as numbers or booleans rather than strings.
+///
+/// When all the values of the condition are numbers, or all are booleans, they are only compared with the JSON values of the same type, e.g. `meta.width > 9` skips the rows where `width` is a string. Otherwise, or when any value is quoted, e.g. `meta.zip = "02134"`, the values are compared as strings.
+pub fn json_comparison(
+    column: SimpleExpr,
+    path: &str,
+    condition: &SearchCondition,
+    dialect: PebbleJsonDialect,
+) -> Result<(SimpleExpr, SearchCondition, bool), PebbleQueryError> {
+    let segments = json_path_segments(path)?;
+    let values: Vec<&String> = condition
+        .value
+        .iter()
+        .chain(condition.value_to.iter())
+        .chain(condition.value_list.iter())
+        .collect();
+
+    if values.iter().any(|value| unquoted(value).is_some()) {
+        let unquote = |value: &String| unquoted(value).unwrap_or(value).to_string();
+        let condition = SearchCondition {
+            value: condition.value.as_ref().map(unquote),
+            value_to: condition.value_to.as_ref().map(unquote),
+            value_list: condition.value_list.iter().map(unquote).collect(),
+            ..condition.clone()
+        };
+        return Ok((
+            json_value_expr(column, &segments, dialect),
+            condition,
+            false,
+        ));
+    }
+
+    let scalars: Vec<JsonValue> = values.iter().map(|value| json_scalar(value)).collect();
+    let value_type = match scalars.is_empty() {
+        true => None,
+        false if scalars.iter().all(JsonValue::is_number) => Some(JsonValueType::Number),
+        false if scalars.iter().all(JsonValue::is_boolean) => Some(JsonValueType::Boolean),
+        false => None,
+    };
+    Ok(match value_type {
+        Some(value_type) => (
+            json_typed_value_expr(column, &segments, dialect, value_type),
+            condition.clone(),
+            true,
+        ),
+        None => (
+            json_value_expr(column, &segments, dialect),
+            condition.clone(),
+            false,
+        ),
+    })
+}
+
+/// # Whether the JSON array at `path` contains the value of the condition
+///
+/// This is what `SearchOperator::Contains` means on JSON path fields, e.g. `meta.tags` contains `red`. The path cannot have array indexes.
+pub fn json_contains_expr(
+    column: SimpleExpr,
+    path: &str,
+    condition: &SearchCondition,
+    dialect: PebbleJsonDialect,
+) -> Result<SimpleExpr, PebbleQueryError> {
+    let segments = json_path_segments(path)?;
+    if segments.iter().any(|segment| is_array_index(segment)) {
+        return Err(InvalidField(format!(
+            "{} cannot have array indexes to be used with contains",
+            path
+        )));
+    }
+    let value = json_scalar(
+        condition
+            .value
+            .as_deref()
+            .ok_or_else(|| MissingValue("value is required for contains".to_string()))?,
+    );
+
+    Ok(match dialect {
+        PebbleJsonDialect::Sqlite => {
+            let element = Alias::new("json_element");
+            Expr::exists(
+                Query::select()
+                    .expr(Expr::val(1))
+                    .from_function(
+                        Func::cust(Alias::new("json_each"))
+                            .arg(column)
+                            .arg(json_extract_path(&segments)),
+                        element.clone(),
+                    )
+                    .and_where(Expr::col((element, Alias::new("value"))).eq(value_to_sql(&value)))
+                    .to_owned(),
+            )
+        }
+        // the whole column is tested with `@>`, so that a GIN index on it can be used.
+        PebbleJsonDialect::Postgres => {
+            let document =
+                segments
+                    .iter()
+                    .rev()
+                    .fold(JsonValue::Array(vec![value]), |document, segment| {
+                        JsonValue::Object(Map::from_iter([(segment.to_string(), document)]))
+                    });
+            column.binary(
+                BinOper::Custom("@>"),
+                Expr::val(document.to_string()).cast_as(Alias::new("jsonb")),
+            )
+        }
+        PebbleJsonDialect::MySql => Func::cust(Alias::new("JSON_CONTAINS"))
+            .arg(column)
+            .arg(value.to_string())
+            .arg(json_extract_path(&segments))
+            .into(),
+    })
+}
+
+/// The SQL value of a JSON scalar, as `json_each` returns it.
+fn value_to_sql(value: &JsonValue) -> sea_orm::Value {
+    match value {
+        JsonValue::Bool(boolean) => (*boolean).into(),
+        JsonValue::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into(),
+            None => number.as_f64().into(),
+        },
+        JsonValue::String(string) => string.as_str().into(),
+        _ => value.to_string().into(),
+    }
+}
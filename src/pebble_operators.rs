@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use sea_orm::sea_query::SimpleExpr;
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::InvalidOperator;
+use crate::sample_dto_structs::{SearchCondition, SearchOperator};
+
+/// Builds the condition of a custom operator from the expression of the field, i.e. its column or computed expression, and the condition with the client's values.
+pub type PebbleOperatorFn =
+    Arc<dyn Fn(SimpleExpr, &SearchCondition) -> Result<SimpleExpr, PebbleQueryError> + Send + Sync>;
+
+/// An operator of the application that is not in `SearchOperator`, e.g. "within N days of" or "has tag".
+#[derive(Clone)]
+pub struct PebbleCustomOperator {
+    /// The value clients send in `SearchCondition.operator`.
+    pub code: i32,
+    /// The name clients can send in `SearchCondition.operator_name` instead of the code.
+    pub name: String,
+    pub build: PebbleOperatorFn,
+}
+
+/// # The custom operators of an entity, keyed by code
+///
+/// When the operator of a condition is not one of `SearchOperator`, its code is looked up here before the condition is rejected with `InvalidOperator`. Clients can also name the operator in `operator_name`, e.g. from GraphQL or TypeScript where the operator is an enum. The codes of `SearchOperator` cannot be registered, so use codes above them, e.g. from 1000.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::errors::PebbleQueryError::InvalidOperator;
+/// # use pebble_query::pebble_operators::PebbleOperatorRegistry;
+/// # use sea_orm::sea_query::Expr;
+/// const WITHIN: i32 = 1000;
+///
+/// // `price WITHIN 10 OF 100` is `price BETWEEN 90 AND 110`.
+/// let operators = PebbleOperatorRegistry::new().with_operator(WITHIN, "within", |expr, condition| {
+///     let parse = |value: Option<&str>| {
+///         value.unwrap_or_default().parse::<f64>().map_err(|_| InvalidOperator("within takes two numbers".to_string()))
+///     };
+///     let (value, distance) = (parse(condition.value.as_deref())?, parse(condition.value_to.as_deref())?);
+///     Ok(Expr::expr(expr).between(value - distance, value + distance))
+/// });
+/// assert_eq!(operators.code("within"), Some(WITHIN));
+/// ```
+#[derive(Clone, Default)]
+pub struct PebbleOperatorRegistry {
+    operators: HashMap<i32, PebbleCustomOperator>,
+}
+
+impl fmt::Debug for PebbleOperatorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut operators: Vec<(&i32, &str)> = self
+            .operators
+            .iter()
+            .map(|(code, operator)| (code, operator.name.as_str()))
+            .collect();
+        operators.sort();
+        f.debug_struct("PebbleOperatorRegistry")
+            .field("operators", &operators)
+            .finish()
+    }
+}
+
+impl PebbleOperatorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the operator `name` under `code`, replacing any operator registered under the same code.
+    ///
+    /// # Panics
+    ///
+    /// If `code` is the code of a `SearchOperator`, including `Unspecified` (0), see `try_with_operator`.
+    pub fn with_operator<F>(self, code: i32, name: &str, build: F) -> Self
+    where
+        F: Fn(SimpleExpr, &SearchCondition) -> Result<SimpleExpr, PebbleQueryError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.try_with_operator(code, name, build)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Register the operator `name` under `code` like `with_operator`, or return `InvalidOperator` if `code` is the code of a `SearchOperator`.
+    pub fn try_with_operator<F>(
+        mut self,
+        code: i32,
+        name: &str,
+        build: F,
+    ) -> Result<Self, PebbleQueryError>
+    where
+        F: Fn(SimpleExpr, &SearchCondition) -> Result<SimpleExpr, PebbleQueryError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        if let Ok(search_operator) = SearchOperator::try_from(code) {
+            return Err(InvalidOperator(format!(
+                "{} cannot be registered under {}, the code of {}",
+                name,
+                code,
+                search_operator.as_str_name()
+            )));
+        }
+        self.operators.insert(
+            code,
+            PebbleCustomOperator {
+                code,
+                name: name.to_string(),
+                build: Arc::new(build),
+            },
+        );
+        Ok(self)
+    }
+
+    /// The operator registered under `code`, if any.
+    pub fn get(&self, code: i32) -> Option<&PebbleCustomOperator> {
+        self.operators.get(&code)
+    }
+
+    /// The code of the operator registered as `name`, e.g. to translate the operator names of another API.
+    pub fn code(&self, name: &str) -> Option<i32> {
+        self.operators
+            .values()
+            .find(|operator| operator.name == name)
+            .map(|operator| operator.code)
+    }
+
+    /// The registered operators, ordered by code.
+    pub fn operators(&self) -> Vec<&PebbleCustomOperator> {
+        let mut operators: Vec<&PebbleCustomOperator> = self.operators.values().collect();
+        operators.sort_by_key(|operator| operator.code);
+        operators
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty()
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::sample_dto_structs::SearchQuery;
+use crate::sample_dto_structs::{SearchCountAccuracy, SearchQuery, SearchSortOption};
 use sea_orm::prelude::async_trait::async_trait;
 use sea_orm::EntityTrait;
 use sea_orm::FromQueryResult;
@@ -9,9 +9,19 @@ use sea_orm::Select;
 use sea_orm::{ConnectionTrait, DbErr};
 use sea_orm::{QuerySelect, QueryTrait};
 
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::InvalidGrouping;
+use crate::pebble_connection::{
+    add_tiebreak_order_to_select, try_add_cursor_to_select_with_policy,
+};
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::pebble_query_options::{PebbleExecutionMode, PebbleQueryOptions};
 use crate::pebble_query_result::PebbleQueryResult;
+use crate::pebble_relative_time::resolve_relative_times;
 use crate::pebble_utils::{
-    add_sort_to_select, get_query_pagination_numbers, query_to_seaorm_conditions,
+    fetch_query_results, fetch_query_results_with_window_count,
+    get_query_pagination_numbers_with_strategy, try_add_sort_expr_to_select,
+    try_add_sort_to_select_with_collation, try_query_to_seaorm_conditions_with_policy,
 };
 
 /// Apply SearchQuery to a SearOrm Select<Entity> with conditions
@@ -31,6 +41,8 @@ use crate::pebble_utils::{
 ///
 /// In order to avoid ambiguous id error, put the tuple `(annotation::Entity, annotation::Column::Id)` (which is converted into ``annotation`.`id``), not `annotation::Column::Id` in the map.
 ///
+/// The select returns models, so a query with `distinct_on` or `group_by`, whose rows are values, panics with the `InvalidGrouping` error of `try_query_to_select`. Run such queries with `use_pebble_query_grouped`, or build their select with `try_query_to_grouped_select`.
+///
 /// # Returns
 ///
 /// * `Condition`: The condition for `must` filters, i.e. `Condition::all().add`
@@ -45,7 +57,46 @@ pub fn query_to_select<E, M>(
 where
     E: EntityTrait<Model = M>,
 {
-    let (must_conditions, any_conditions) = query_to_seaorm_conditions(query, field_to_column_map);
+    try_query_to_select(query, field_to_column_map, select_with_conditions)
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Apply SearchQuery to a SearOrm Select<Entity> with conditions, or return an error if the query is invalid.
+///
+/// This is the fallible version of `query_to_select`: instead of panicking on a field that is not in `field_to_column_map` or an invalid condition, it returns the corresponding `PebbleQueryError`.
+pub fn try_query_to_select<E, M>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    select_with_conditions: Select<E>,
+) -> Result<Select<E>, PebbleQueryError>
+where
+    E: EntityTrait<Model = M>,
+{
+    try_query_to_select_with_policy(
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+        select_with_conditions,
+    )
+}
+
+/// Apply SearchQuery to a SearOrm Select<Entity> like `try_query_to_select`, with the computed fields and sort collations of the field policy.
+pub fn try_query_to_select_with_policy<E, M>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    select_with_conditions: Select<E>,
+) -> Result<Select<E>, PebbleQueryError>
+where
+    E: EntityTrait<Model = M>,
+{
+    if !query.distinct_on.is_empty() || !query.group_by.is_empty() {
+        return Err(InvalidGrouping(
+            "distinct_on and group_by queries return values, not models; run them with use_pebble_query_grouped".to_string(),
+        ));
+    }
+    let (must_conditions, any_conditions) =
+        try_query_to_seaorm_conditions_with_policy(query, field_to_column_map, field_policy)?;
 
     let mut select_with_conditions = select_with_conditions
         .apply_if(
@@ -72,15 +123,38 @@ where
         select_with_conditions = select_with_conditions.offset(query.offset as u64)
     }
 
-    let query_sort = query.sort.clone();
-    if query_sort.is_some() {
-        select_with_conditions = add_sort_to_select(
-            select_with_conditions,
-            query_sort.unwrap(),
-            field_to_column_map,
-        );
+    if let Some(query_sort) = query.sort.clone() {
+        let collation = field_policy.sort_collation(&query_sort.field);
+        let computed_field = match field_to_column_map.contains_key(&query_sort.field) {
+            true => None,
+            false => field_policy.computed_field(&query_sort.field),
+        };
+        select_with_conditions = match computed_field {
+            Some(computed_field) => try_add_sort_expr_to_select(
+                select_with_conditions,
+                &SearchSortOption {
+                    case_insensitive: query_sort.case_insensitive
+                        && field_policy.is_text_computed_field(&query_sort.field),
+                    ..query_sort.clone()
+                },
+                computed_field.clone(),
+                collation,
+            )?,
+            None => try_add_sort_to_select_with_collation(
+                select_with_conditions,
+                query_sort,
+                field_to_column_map,
+                collation,
+            )?,
+        };
     };
-    select_with_conditions
+
+    // order the rows with the same sort value by the primary key, so that the rows after a cursor are the ones after it on the previous page.
+    if query.after.is_some() {
+        select_with_conditions =
+            add_tiebreak_order_to_select(select_with_conditions, query, field_to_column_map)?;
+    }
+    Ok(select_with_conditions)
 }
 
 /// # Apply and run SearchQuery to a SearOrm Select<Entity> with conditions
@@ -99,33 +173,127 @@ where
 ///
 /// # Returns
 /// * `PebbleQueryResult`: The result of the query.
+/// * `DbErr`: If the database returns an error, or as `DbErr::Custom` if a field is not in `field_to_column_map` or a condition is invalid. Use `try_use_pebble_query` to tell them apart with `PebbleQueryError`.
 ///
 /// # Example
 ///
 /// ```rust
+/// # use pebble_query::pebble_query::use_pebble_query;
+/// # use pebble_query::pebble_query_result::PebbleQueryResult;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{author, book, BOOK_COLUMN_MAP};
+/// # use sea_orm::prelude::Expr;
+/// # use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect, RelationTrait};
+/// # use sea_orm::sea_query::IntoCondition;
+/// // Find the books of some authors
+/// pub async fn query_books_by_authors(
+///     db: &DatabaseConnection,
+///     author_ids: Vec<i32>,
+///     query: Option<SearchQuery>,
+/// ) -> Result<PebbleQueryResult<book::Entity>, DbErr> {
+///     let select = book::Entity::find()
+///         .join(sea_orm::JoinType::InnerJoin, book::Relation::Author.def())
+///         .filter(
+///             Expr::col((author::Entity, author::Column::Id))
+///                 .is_in(author_ids)
+///                 .into_condition(),
+///         ); // any select can be used as the initial condition
 ///
-/// // Find all annotations for a document
-/// pub async fn query_annotations_by_documents(&self, doc_ids: Vec<i32>, query: Option<SearchQuery>) -> Result<
-///         PebbleQueryResultGeneric<CommonAnnotation>, CarrelOrmErr> {
-///         let db = self.conn();
+///     use_pebble_query(select, query, &BOOK_COLUMN_MAP, db).await
+/// }
+/// ```
+pub async fn use_pebble_query<C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+) -> Result<PebbleQueryResult<E>, DbErr>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    Ok(try_use_pebble_query(initial_select, query, field_to_column_map, db).await?)
+}
+
+/// # Apply and run SearchQuery to a SearOrm Select<Entity>, returning a PebbleQueryError
+///
+/// This is `use_pebble_query` with the errors of the query, e.g. `InvalidField`, kept apart from the database errors, so that they can be returned to the client, e.g. as an invalid argument.
+pub async fn try_use_pebble_query<C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    use_pebble_query_with_options(
+        initial_select,
+        query,
+        field_to_column_map,
+        db,
+        &PebbleQueryOptions::default(),
+    )
+    .await
+}
+
+/// # Apply and run SearchQuery to a SearOrm Select<Entity> with backend options
 ///
-///         let select = annotation::Entity::find()
-///   .inner_join(document::Entity)
-///   .filter(
-///       Expr::col( (document::Entity, document::Column::Id)).is_in(doc_ids).into_condition()
-///   ); // any select can be used as the initial condition
+/// This is `use_pebble_query` with `PebbleQueryOptions`, e.g. to run the data query and the count query concurrently or as a single query with `PebbleExecutionMode`.
 ///
-///         let result = run_query(db, query, &ANNOTATION_COLUMN_MAP, select).await?;
+/// # Example
 ///
-///         Ok(result.map_into_generic(|annotation| Some(annotation.into_common_type()), None))
-///     }
+/// ```rust
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_query::use_pebble_query_with_options;
+/// # use pebble_query::pebble_query_options::{PebbleExecutionMode, PebbleQueryOptions};
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>) -> Result<(), PebbleQueryError> {
+/// let options = PebbleQueryOptions {
+///     execution_mode: PebbleExecutionMode::Concurrent,
+///     ..Default::default()
+/// };
+/// let result = use_pebble_query_with_options(book::Entity::find(), query, &BOOK_COLUMN_MAP, db, &options).await?;
+/// # Ok(())
+/// # }
 /// ```
-pub async fn use_pebble_query<C, E, M>(
+pub async fn use_pebble_query_with_options<C, E, M>(
     initial_select: Select<E>,
     query: Option<SearchQuery>,
     field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
     db: &C,
-) -> Result<PebbleQueryResult<E>, DbErr>
+    options: &PebbleQueryOptions,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    run_pebble_query(
+        initial_select,
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+        db,
+        options,
+    )
+    .await
+}
+
+/// The body of `use_pebble_query_with_options`, with the field policy of `use_pebble_query_with_config`.
+pub(crate) async fn run_pebble_query<C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    db: &C,
+    options: &PebbleQueryOptions,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
 where
     C: ConnectionTrait,
     E: EntityTrait<Model = M>,
@@ -135,37 +303,134 @@ where
         find_all: true,
         ..Default::default()
     });
+    options.limits.validate(&query)?;
+    // the JSON path fields are read with the JSON functions of the database, unless the policy says otherwise.
+    let backend_policy = field_policy.for_backend(db.get_database_backend());
+    let field_policy: &PebbleFieldPolicy = &backend_policy;
+    // relative dates are resolved for the SQL only; the metadata echoes the query as the client sent it.
+    let unresolved_query = query;
+    let query = resolve_relative_times(&unresolved_query, field_to_column_map, &options.clock)?;
     // parse query and add all contions, pagination, and sortings to the initial selection and return the modified selected.
-    let select_with_conditions = query_to_select(&query, field_to_column_map, initial_select);
-
-    let query_result: Vec<<E as EntityTrait>::Model>;
-
-    // check find_one and find_all fields in the SearchQuery before execute the queries receive data back.
-    if query.find_one {
-        let result = select_with_conditions.clone().one(db).await?;
-        // if exists return the result in Vec, else return empty Vec
-        if result.is_some() {
-            let result = result.unwrap();
-            query_result = vec![result]
-        } else {
-            query_result = vec![]
-        }
-    } else {
-        let result = select_with_conditions.clone().all(db).await?;
-        query_result = result
+    let mut select_with_conditions =
+        try_query_to_select_with_policy(&query, field_to_column_map, field_policy, initial_select)?;
+    // the first page of a connection must be ordered like the pages after its cursors.
+    if options.connection_paging && query.after.is_none() {
+        select_with_conditions =
+            add_tiebreak_order_to_select(select_with_conditions, &query, field_to_column_map)?;
     }
+    // with a cursor, only the data query starts after it; the count query still counts all the matching rows.
+    let data_select = match &query.after {
+        Some(after) => try_add_cursor_to_select_with_policy(
+            select_with_conditions.clone(),
+            after,
+            &query,
+            field_to_column_map,
+            field_policy,
+        )?,
+        None => select_with_conditions.clone(),
+    };
+    // the window count would only count the rows after the cursor.
+    let execution_mode = match options.execution_mode {
+        PebbleExecutionMode::WindowCount if query.after.is_some() => {
+            PebbleExecutionMode::Sequential
+        }
+        execution_mode => execution_mode,
+    };
 
-    // use the same (cloned) query to execute for a second time purely to get the total counts and page numbers using the same conditions.
-    let total_items_and_pages_number =
-        get_query_pagination_numbers(db, &query, select_with_conditions).await?;
+    let (mut query_result, (total_items_and_pages_number, total_items_accuracy)) =
+        match execution_mode {
+            PebbleExecutionMode::Sequential => {
+                let query_result = fetch_query_results(db, &query, data_select).await?;
+                // use the same (cloned) query to execute for a second time purely to get the total counts and page numbers using the same conditions.
+                let total_items_and_pages_number = get_query_pagination_numbers_with_strategy(
+                    db,
+                    &query,
+                    select_with_conditions,
+                    options.count_strategy,
+                )
+                .await?;
+                (query_result, total_items_and_pages_number)
+            }
+            PebbleExecutionMode::Concurrent => futures::try_join!(
+                fetch_query_results(db, &query, data_select),
+                get_query_pagination_numbers_with_strategy(
+                    db,
+                    &query,
+                    select_with_conditions,
+                    options.count_strategy,
+                ),
+            )?,
+            PebbleExecutionMode::WindowCount => {
+                let (query_result, total_items_and_pages_number) =
+                    fetch_query_results_with_window_count(db, &query, select_with_conditions)
+                        .await?;
+                (
+                    query_result,
+                    (total_items_and_pages_number, SearchCountAccuracy::Exact),
+                )
+            }
+        };
 
-    // construct the pebble_query_result from the data (first run) and the metadata (second run) from the DB.
-    let pebble_query_result =
-        PebbleQueryResult::from(query, query_result, total_items_and_pages_number);
+    // a page after a cursor is fetched with one extra row, which tells whether there is a next page.
+    let has_next_page = match query.after {
+        Some(_) if query.length > 0 && query_result.len() > query.length as usize => {
+            query_result.truncate(query.length as usize);
+            Some(true)
+        }
+        Some(_) => Some(false),
+        None => None,
+    };
+
+    // construct the pebble_query_result from the data and the metadata from the DB.
+    let mut pebble_query_result =
+        PebbleQueryResult::from(unresolved_query, query_result, total_items_and_pages_number);
+    pebble_query_result.metadata.total_items_accuracy = total_items_accuracy as i32;
+    pebble_query_result.metadata.has_next_page = has_next_page;
 
     Ok(pebble_query_result)
 }
 
+/// # Apply and run SearchQuery to a SearOrm Select<Entity> with the entity's query configuration
+///
+/// This is `use_pebble_query_with_options` with the field map and options of a `PebbleQueryConfig`. Before the SearchQuery is applied, the select is restricted to the scopes of the configuration computed from `context`, so a query can never reach rows outside them, whatever the client sends.
+///
+/// If the configuration declares a soft-delete column, deleted rows are filtered out unless the query asks for them with `include_deleted` or `only_deleted` and the field policy allows it. The sort field is compared with its collation in the field policy, if any.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query.
+/// * `query`: Option<SearchQuery> object - the query to be applied to the select. If none, all entity entries within the scopes will be returned.
+/// * `config`: The query configuration of the entity.
+/// * `context`: The request context the scopes are computed from.
+/// * `db`: The database connection.
+pub async fn use_pebble_query_with_config<C, E, M, Ctx>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+) -> Result<PebbleQueryResult<E>, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    let scoped_select = config.apply_scopes(initial_select, context)?;
+    let scoped_select = config.apply_soft_delete(scoped_select, &query)?;
+    run_pebble_query(
+        scoped_select,
+        Some(query),
+        &config.field_to_column_map,
+        &config.field_policy,
+        db,
+        &config.options,
+    )
+    .await
+}
+
 #[async_trait]
 pub trait RunQueryExt<C, E, M>
 where
@@ -179,6 +444,21 @@ where
         field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
         db: &C,
     ) -> Result<PebbleQueryResult<E>, DbErr>;
+
+    async fn try_pebble_query(
+        self,
+        query: Option<SearchQuery>,
+        field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+        db: &C,
+    ) -> Result<PebbleQueryResult<E>, PebbleQueryError>;
+
+    async fn pebble_query_with_options(
+        self,
+        query: Option<SearchQuery>,
+        field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+        db: &C,
+        options: &PebbleQueryOptions,
+    ) -> Result<PebbleQueryResult<E>, PebbleQueryError>;
 }
 
 #[async_trait]
@@ -196,4 +476,23 @@ where
     ) -> Result<PebbleQueryResult<E>, DbErr> {
         use_pebble_query(self, query, field_to_column_map, db).await
     }
+
+    async fn try_pebble_query(
+        self,
+        query: Option<SearchQuery>,
+        field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+        db: &C,
+    ) -> Result<PebbleQueryResult<E>, PebbleQueryError> {
+        try_use_pebble_query(self, query, field_to_column_map, db).await
+    }
+
+    async fn pebble_query_with_options(
+        self,
+        query: Option<SearchQuery>,
+        field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+        db: &C,
+        options: &PebbleQueryOptions,
+    ) -> Result<PebbleQueryResult<E>, PebbleQueryError> {
+        use_pebble_query_with_options(self, query, field_to_column_map, db, options).await
+    }
 }
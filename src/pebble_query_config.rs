@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::prelude::Expr;
+use sea_orm::sea_query::SimpleExpr;
+use sea_orm::{Condition, DbBackend, EntityTrait, QueryFilter, Select};
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::DeletedRowsNotAllowed;
+use crate::pebble_json::PebbleJsonDialect;
+use crate::pebble_operators::PebbleOperatorRegistry;
+use crate::pebble_query_options::PebbleQueryOptions;
+use crate::pebble_utils::field_map_key;
+use crate::sample_dto_structs::{SearchCondition, SearchQuery};
+
+/// A mandatory condition computed from the request context, e.g. `tenant_id = current_tenant`.
+pub type PebbleScope<Ctx> = Box<dyn Fn(&Ctx) -> Result<Condition, PebbleQueryError> + Send + Sync>;
+
+/// How the values of a field are compared when sorting by it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PebbleSortCollation {
+    /// Sort by `LOWER(field)`, which works on every database.
+    CaseInsensitive,
+    /// Sort by `field COLLATE <collation>`.
+    ///
+    /// The collation is written into the SQL as is, e.g. `NOCASE` on SQLite, `"und-x-icu"` on Postgres or `utf8mb4_0900_ai_ci` on MySQL, so it must never come from client input.
+    Collate(String),
+}
+
+/// What clients may do with the fields of an entity beyond filtering and sorting by the field map.
+#[derive(Clone, Debug, Default)]
+pub struct PebbleFieldPolicy {
+    /// Whether clients may ask for soft-deleted rows with `include_deleted` or `only_deleted`.
+    pub allow_deleted: bool,
+    /// How to compare the values of a field when sorting by it, keyed by field name, e.g. `"title" => PebbleSortCollation::Collate("NOCASE".to_string())` so that "apple" comes before "Zebra". Fields without an entry sort with the column's own collation.
+    pub sort_collations: HashMap<String, PebbleSortCollation>,
+    /// Named computed expressions that clients can sort and filter by like the fields of the field map, e.g. `"title_length" => LENGTH(title)`, a `CASE` priority mapping or a full-text rank.
+    pub computed_fields: HashMap<String, SimpleExpr>,
+    /// The computed fields whose values are strings. A case-insensitive sort only lowercases these; the others, e.g. `LENGTH(title)`, are sorted by their own values.
+    pub text_computed_fields: HashSet<String>,
+    /// The operators of the application that clients can use in conditions besides the ones of `SearchOperator`.
+    pub custom_operators: PebbleOperatorRegistry,
+    /// How the JSON path fields of the field map, e.g. `meta.color` for a `meta.*` entry, are read from their column. `None` reads them with the JSON functions of the database the query runs on.
+    pub json_dialect: Option<PebbleJsonDialect>,
+}
+
+impl PebbleFieldPolicy {
+    /// The collation to sort `field` with, if any. Like the fields of the field map, `field` is also looked up in snake case, e.g. `publicationYear` finds the collation of `publication_year`.
+    pub fn sort_collation(&self, field: &str) -> Option<&PebbleSortCollation> {
+        self.sort_collations
+            .get(&field_map_key(&self.sort_collations, field))
+    }
+
+    /// The expression of the computed field named `field`, if any.
+    pub fn computed_field(&self, field: &str) -> Option<&SimpleExpr> {
+        self.computed_fields.get(field)
+    }
+
+    /// Whether `field` is a computed field declared as text, see `PebbleQueryConfig::with_text_computed_field`.
+    pub fn is_text_computed_field(&self, field: &str) -> bool {
+        self.text_computed_fields.contains(field)
+    }
+
+    /// The policy a query running on `backend` is built with: this one, with the JSON dialect of `backend` unless `json_dialect` is set.
+    pub(crate) fn for_backend(&self, backend: DbBackend) -> Cow<'_, PebbleFieldPolicy> {
+        match self.json_dialect {
+            Some(_) => Cow::Borrowed(self),
+            None => Cow::Owned(PebbleFieldPolicy {
+                json_dialect: Some(backend.into()),
+                ..self.clone()
+            }),
+        }
+    }
+}
+
+/// The query configuration of an entity: its field map, the options its queries run with, the scopes every query against it is restricted to, and how it handles soft-deleted rows.
+///
+/// `Ctx` is the request context the scopes are computed from, e.g. the current user and tenant.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::sync::LazyLock;
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_query::use_pebble_query_with_config;
+/// # use pebble_query::pebble_query_config::PebbleQueryConfig;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait};
+/// pub struct RequestContext {
+///     pub tenant_id: i32,
+/// }
+///
+/// static BOOK_QUERY_CONFIG: LazyLock<PebbleQueryConfig<book::Entity, RequestContext>> = LazyLock::new(|| {
+///     PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone()).with_scope(|ctx: &RequestContext| {
+///         Ok(Condition::all().add(book::Column::TenantId.eq(ctx.tenant_id)))
+///     })
+/// });
+///
+/// # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>, ctx: RequestContext) -> Result<(), PebbleQueryError> {
+/// let result = use_pebble_query_with_config(book::Entity::find(), query, &BOOK_QUERY_CONFIG, &ctx, db).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PebbleQueryConfig<E: EntityTrait, Ctx = ()> {
+    pub field_to_column_map: HashMap<String, (E, <E as EntityTrait>::Column)>,
+    pub options: PebbleQueryOptions,
+    pub scopes: Vec<PebbleScope<Ctx>>,
+    /// The nullable column that is set when a row is soft-deleted, e.g. `deleted_at`.
+    pub soft_delete_column: Option<(E, <E as EntityTrait>::Column)>,
+    pub field_policy: PebbleFieldPolicy,
+}
+
+impl<E: EntityTrait, Ctx> PebbleQueryConfig<E, Ctx> {
+    pub fn new(field_to_column_map: HashMap<String, (E, <E as EntityTrait>::Column)>) -> Self {
+        PebbleQueryConfig {
+            field_to_column_map,
+            options: PebbleQueryOptions::default(),
+            scopes: vec![],
+            soft_delete_column: None,
+            field_policy: PebbleFieldPolicy::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: PebbleQueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Register a mandatory condition that is ANDed into every query run with this configuration.
+    pub fn with_scope<F>(mut self, scope: F) -> Self
+    where
+        F: Fn(&Ctx) -> Result<Condition, PebbleQueryError> + Send + Sync + 'static,
+    {
+        self.scopes.push(Box::new(scope));
+        self
+    }
+
+    /// Declare the column that marks soft-deleted rows, so that they are filtered out unless the query asks for them.
+    pub fn with_soft_delete(mut self, column: (E, <E as EntityTrait>::Column)) -> Self {
+        self.soft_delete_column = Some(column);
+        self
+    }
+
+    pub fn with_field_policy(mut self, field_policy: PebbleFieldPolicy) -> Self {
+        self.field_policy = field_policy;
+        self
+    }
+
+    /// Register a computed expression under `name`, so that clients can sort and filter by it as if it were a field.
+    ///
+    /// Fields of the field map take precedence over computed fields with the same name. Cursors cannot be built from computed fields, so queries sorted by one are paged with `offset`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use pebble_query::pebble_query_config::PebbleQueryConfig;
+    /// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+    /// # use sea_orm::sea_query::{Alias, Expr, Func};
+    /// # use sea_orm::ColumnTrait;
+    /// let config: PebbleQueryConfig<book::Entity, ()> = PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+    ///     .with_computed_field(
+    ///         "title_length",
+    ///         Func::cust(Alias::new("LENGTH")).arg(Expr::col((book::Entity, book::Column::Title))),
+    ///     )
+    ///     // the books without a price come last when sorted by `unpriced`.
+    ///     .with_computed_field(
+    ///         "unpriced",
+    ///         Expr::case(book::Column::Price.is_null(), 1).finally(0),
+    ///     );
+    /// ```
+    pub fn with_computed_field<T: Into<SimpleExpr>>(mut self, name: &str, expr: T) -> Self {
+        self.field_policy
+            .computed_fields
+            .insert(name.to_string(), expr.into());
+        self
+    }
+
+    /// Register a computed field whose values are strings, e.g. `COALESCE(nickname, name)`, like `with_computed_field`. A case-insensitive sort lowercases it, which it does not for the other computed fields.
+    pub fn with_text_computed_field<T: Into<SimpleExpr>>(mut self, name: &str, expr: T) -> Self {
+        self.field_policy
+            .text_computed_fields
+            .insert(name.to_string());
+        self.with_computed_field(name, expr)
+    }
+
+    /// Register a custom operator under `code`, see `PebbleOperatorRegistry::with_operator`, which panics if `code` is the code of a `SearchOperator`.
+    pub fn with_custom_operator<F>(mut self, code: i32, name: &str, build: F) -> Self
+    where
+        F: Fn(SimpleExpr, &SearchCondition) -> Result<SimpleExpr, PebbleQueryError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.field_policy.custom_operators = self
+            .field_policy
+            .custom_operators
+            .with_operator(code, name, build);
+        self
+    }
+
+    /// Register a custom operator under `code` like `with_custom_operator`, or return `InvalidOperator` if `code` is the code of a `SearchOperator`.
+    pub fn try_with_custom_operator<F>(
+        mut self,
+        code: i32,
+        name: &str,
+        build: F,
+    ) -> Result<Self, PebbleQueryError>
+    where
+        F: Fn(SimpleExpr, &SearchCondition) -> Result<SimpleExpr, PebbleQueryError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.field_policy.custom_operators = self
+            .field_policy
+            .custom_operators
+            .try_with_operator(code, name, build)?;
+        Ok(self)
+    }
+
+    /// Read the JSON path fields with the JSON functions of another database than the one queries run on, e.g. `with_json_dialect(DbBackend::Postgres.into())` behind a proxy that reports another backend.
+    pub fn with_json_dialect(mut self, json_dialect: PebbleJsonDialect) -> Self {
+        self.field_policy.json_dialect = Some(json_dialect);
+        self
+    }
+
+    /// Filter the soft-deleted rows out of the select, or keep only them, according to `include_deleted` and `only_deleted` of the query.
+    ///
+    /// Asking for deleted rows is an error unless the field policy allows it. `only_deleted` takes precedence over `include_deleted`. Without a soft-delete column, the select is returned as is.
+    pub fn apply_soft_delete(
+        &self,
+        select: Select<E>,
+        query: &SearchQuery,
+    ) -> Result<Select<E>, PebbleQueryError> {
+        let condition = self.soft_delete_condition(query)?;
+        Ok(match condition.is_empty() {
+            true => select,
+            false => select.filter(condition),
+        })
+    }
+
+    /// The condition `apply_soft_delete` filters the select with, e.g. for `update_many()` and `delete_many()`, which are not selects. It is empty when every row is kept.
+    pub fn soft_delete_condition(
+        &self,
+        query: &SearchQuery,
+    ) -> Result<Condition, PebbleQueryError> {
+        let soft_delete_column = match self.soft_delete_column {
+            Some(soft_delete_column) => soft_delete_column,
+            None => return Ok(Condition::all()),
+        };
+
+        if (query.include_deleted || query.only_deleted) && !self.field_policy.allow_deleted {
+            return Err(DeletedRowsNotAllowed(format!(
+                "{} does not allow querying deleted rows",
+                E::default().table_name()
+            )));
+        }
+
+        let condition = match (query.only_deleted, query.include_deleted) {
+            (true, _) => Condition::all().add(Expr::col(soft_delete_column).is_not_null()),
+            (false, true) => Condition::all(),
+            (false, false) => Condition::all().add(Expr::col(soft_delete_column).is_null()),
+        };
+        Ok(condition)
+    }
+
+    /// All the scopes computed from the context, ANDed into one condition.
+    pub fn scope_condition(&self, context: &Ctx) -> Result<Condition, PebbleQueryError> {
+        let mut condition = Condition::all();
+        for scope in &self.scopes {
+            condition = condition.add(scope(context)?);
+        }
+        Ok(condition)
+    }
+
+    /// Filter the select by all the scopes computed from the context.
+    ///
+    /// Each scope is a separate `AND` on the select, so the `must` and `any` conditions of the client's SearchQuery, which are added afterwards, can only narrow the scoped rows down.
+    pub fn apply_scopes(
+        &self,
+        select: Select<E>,
+        context: &Ctx,
+    ) -> Result<Select<E>, PebbleQueryError> {
+        let mut scoped_select = select;
+        for scope in &self.scopes {
+            scoped_select = scoped_select.filter(scope(context)?);
+        }
+        Ok(scoped_select)
+    }
+}
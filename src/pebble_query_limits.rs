@@ -0,0 +1,111 @@
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::{
+    InListTooLarge, LeadingWildcardLike, OffsetTooLarge, PageLengthTooLarge, TooManyConditions,
+};
+use crate::sample_dto_structs::{SearchCondition, SearchOperator, SearchQuery};
+
+/// Limits on what a client may ask for in a SearchQuery. A `None` limit is not enforced.
+///
+/// The limits are checked by `validate` before any SQL is built, see `PebbleQueryOptions::limits`.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::pebble_query_limits::PebbleQueryLimits;
+/// let limits = PebbleQueryLimits {
+///     max_conditions: Some(20),
+///     max_in_list_size: Some(500),
+///     max_page_length: Some(100),
+///     max_offset: Some(10_000),
+///     forbid_leading_wildcard_like: true,
+/// };
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PebbleQueryLimits {
+    /// The maximum number of `must` and `any` conditions together.
+    pub max_conditions: Option<usize>,
+    /// The maximum number of values in the `value_list` of a condition.
+    pub max_in_list_size: Option<usize>,
+    /// The maximum `length`. A length of 0, which fetches all the rows, is rejected unless the query asks for `find_one`.
+    pub max_page_length: Option<i32>,
+    /// The maximum `offset`.
+    pub max_offset: Option<i32>,
+    /// Reject patterns starting with a wildcard, including `Contains`, which cannot use an index.
+    pub forbid_leading_wildcard_like: bool,
+}
+
+impl PebbleQueryLimits {
+    /// Check the query against the limits, returning the error of the first limit exceeded.
+    pub fn validate(&self, query: &SearchQuery) -> Result<(), PebbleQueryError> {
+        if let Some(max_page_length) = self.max_page_length {
+            if query.length > max_page_length || (query.length <= 0 && !query.find_one) {
+                return Err(PageLengthTooLarge(format!(
+                    "length must be between 1 and {}, got {}",
+                    max_page_length, query.length
+                )));
+            }
+        }
+
+        if let Some(max_offset) = self.max_offset {
+            if query.offset > max_offset {
+                return Err(OffsetTooLarge(format!(
+                    "offset must be at most {}, got {}",
+                    max_offset, query.offset
+                )));
+            }
+        }
+
+        let conditions: Vec<&SearchCondition> = match &query.filter {
+            Some(filter) => filter.must.iter().chain(filter.any.iter()).collect(),
+            None => vec![],
+        };
+
+        if let Some(max_conditions) = self.max_conditions {
+            if conditions.len() > max_conditions {
+                return Err(TooManyConditions(format!(
+                    "at most {} conditions are allowed, got {}",
+                    max_conditions,
+                    conditions.len()
+                )));
+            }
+        }
+
+        for condition in conditions {
+            if let Some(max_in_list_size) = self.max_in_list_size {
+                if condition.value_list.len() > max_in_list_size {
+                    return Err(InListTooLarge(format!(
+                        "at most {} values are allowed for field {}, got {}",
+                        max_in_list_size,
+                        condition.field,
+                        condition.value_list.len()
+                    )));
+                }
+            }
+
+            if self.forbid_leading_wildcard_like && has_leading_wildcard(condition) {
+                return Err(LeadingWildcardLike(format!(
+                    "patterns starting with a wildcard are not allowed for field {}",
+                    condition.field
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the condition is a pattern match that starts with a wildcard.
+fn has_leading_wildcard(condition: &SearchCondition) -> bool {
+    let value = condition.value.as_deref().unwrap_or_default();
+    match SearchOperator::try_from(condition.operator).unwrap_or(SearchOperator::Unspecified) {
+        SearchOperator::Contains
+        | SearchOperator::NotContains
+        | SearchOperator::EndsWith
+        | SearchOperator::NotEndsWith => true,
+        SearchOperator::Like
+        | SearchOperator::NotLike
+        | SearchOperator::Ilike
+        | SearchOperator::NotIlike => value.starts_with('%') || value.starts_with('_'),
+        _ => false,
+    }
+}
@@ -0,0 +1,46 @@
+use crate::pebble_query_limits::PebbleQueryLimits;
+use crate::pebble_relative_time::PebbleClock;
+
+/// How `use_pebble_query_with_options` runs the data query and the count query.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PebbleExecutionMode {
+    /// Run the data query, then the count query. This works everywhere, including inside a transaction.
+    #[default]
+    Sequential,
+    /// Run the data query and the count query at the same time. Use this with a connection pool, where the two queries can run on different connections.
+    Concurrent,
+    /// Run a single query that also selects `COUNT(*) OVER ()` for the total number of items.
+    ///
+    /// Window functions are supported by Postgres, SQLite 3.25+ and MySQL 8+. When the requested page is past the last row, no row carries the total, so a separate count query is run.
+    WindowCount,
+}
+
+/// How the total number of items in `SearchResultMetadata` is counted.
+///
+/// `total_items_accuracy` in the metadata tells which kind of total was returned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PebbleCountStrategy {
+    /// Count all the matching rows with `COUNT(*)`.
+    #[default]
+    Exact,
+    /// Use the row estimate of the query planner, the `EXPLAIN` plan rows on Postgres, which takes the filters into account.
+    ///
+    /// Falls back to an exact count on the other backends, which have no estimate of a filtered select.
+    Estimated,
+    /// Count the matching rows up to the given number. If the cap is reached, the total is a lower bound.
+    Capped(u64),
+}
+
+/// Options of a Pebble Query call that are decided by the backend rather than by the client's SearchQuery.
+#[derive(Clone, Debug, Default)]
+pub struct PebbleQueryOptions {
+    pub execution_mode: PebbleExecutionMode,
+    /// Ignored with `PebbleExecutionMode::WindowCount`, which always counts exactly.
+    pub count_strategy: PebbleCountStrategy,
+    /// Checked before any SQL is built.
+    pub limits: PebbleQueryLimits,
+    /// The current time that relative date and time values, e.g. `now-7d`, are resolved against.
+    pub clock: PebbleClock,
+    /// Order the rows with the same sort value by the primary key, so that the cursors of `pebble_connection` page through them without skipping or repeating any. Queries with `after` are always ordered this way.
+    pub connection_paging: bool,
+}
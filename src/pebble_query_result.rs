@@ -1,6 +1,10 @@
 use crate::pebble_converter::{PebbleConverter, PebbleConverterTrait};
 use crate::sample_dto_structs::{SearchQuery, SearchResultMetadata};
 
+use std::future::Future;
+
+use futures::future::join_all;
+use sea_orm::prelude::async_trait::async_trait;
 use sea_orm::{EntityTrait, ItemsAndPagesNumber};
 use serde::{Deserialize, Serialize};
 
@@ -27,6 +31,15 @@ pub struct PebbleQueryResult<T: EntityTrait> {
     pub results: Vec<T::Model>,
 }
 
+impl<T: EntityTrait> Clone for PebbleQueryResult<T> {
+    fn clone(&self) -> Self {
+        PebbleQueryResult {
+            metadata: self.metadata.clone(),
+            results: self.results.clone(),
+        }
+    }
+}
+
 //
 pub trait PebbleQueryResultUtilTrait<T: EntityTrait> {
     fn first(&self) -> Option<&T::Model>;
@@ -44,6 +57,27 @@ pub trait PebbleQueryResultUtilTrait<T: EntityTrait> {
         result_filter_map: fn(T::Model) -> Option<U>,
         filter_out_reason: Option<String>,
     ) -> PebbleQueryResultGeneric<U>;
+
+    /// The same as `map_into_generic`, but with a closure, which can capture context such as the user's permissions or locale. The filtered out results are added to the `filter_count` and `filter_reason` of earlier filters instead of replacing them.
+    fn map_into_generic_with<U, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> PebbleQueryResultGeneric<U>
+    where
+        F: FnMut(T::Model) -> Option<U>;
+
+    /// The same as `map_into_generic_with`, but the closure can fail. The first error is returned.
+    fn try_map_into_generic<U, Err, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> Result<PebbleQueryResultGeneric<U>, Err>
+    where
+        F: FnMut(T::Model) -> Result<Option<U>, Err>;
+
+    /// Move the models into a generic QueryResult as they are, e.g. to map them with `map_async`.
+    fn into_generic(self) -> PebbleQueryResultGeneric<T::Model>;
 }
 
 impl<T: EntityTrait> PebbleQueryResultUtilTrait<T> for PebbleQueryResult<T> {
@@ -75,6 +109,37 @@ impl<T: EntityTrait> PebbleQueryResultUtilTrait<T> for PebbleQueryResult<T> {
         };
         PebbleQueryResultGeneric { metadata, results }
     }
+
+    fn map_into_generic_with<U, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> PebbleQueryResultGeneric<U>
+    where
+        F: FnMut(T::Model) -> Option<U>,
+    {
+        self.into_generic()
+            .map_filter_result_with(result_filter_map, filter_out_reason)
+    }
+
+    fn try_map_into_generic<U, Err, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> Result<PebbleQueryResultGeneric<U>, Err>
+    where
+        F: FnMut(T::Model) -> Result<Option<U>, Err>,
+    {
+        self.into_generic()
+            .try_filter_map(result_filter_map, filter_out_reason)
+    }
+
+    fn into_generic(self) -> PebbleQueryResultGeneric<T::Model> {
+        PebbleQueryResultGeneric {
+            metadata: self.metadata,
+            results: self.results,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,6 +147,30 @@ pub struct PebbleQueryResultGeneric<T> {
     pub metadata: SearchResultMetadata,
     pub results: Vec<T>,
 }
+
+/// Update the result count of the metadata after `initial_result_count` results were filtered down to `results_count`.
+///
+/// The filtered out results are added to the `filter_count` of earlier filters, and the reasons are joined, so that chained filters are all accounted for. `map_filter_result` and `map_into_generic` replace them instead.
+fn filtered_metadata(
+    metadata: SearchResultMetadata,
+    initial_result_count: usize,
+    results_count: usize,
+    filter_out_reason: Option<String>,
+) -> SearchResultMetadata {
+    let filtered_out_count = initial_result_count.saturating_sub(results_count) as i32;
+    let filter_reason = match (metadata.filter_reason, filter_out_reason) {
+        (Some(previous_reason), Some(reason)) => Some(format!("{}; {}", previous_reason, reason)),
+        (previous_reason, reason) => reason.or(previous_reason),
+    };
+    SearchResultMetadata {
+        result_items: results_count as i32,
+        filter_count: Some(metadata.filter_count.unwrap_or(0) + filtered_out_count),
+        filter_reason,
+        ..metadata
+    }
+}
+
+#[async_trait]
 pub trait PebbleQueryResultGenericUtilTraits<O> {
     fn first(&self) -> Option<&O>;
 
@@ -90,8 +179,55 @@ pub trait PebbleQueryResultGenericUtilTraits<O> {
         result_filter_map: fn(O) -> Option<TARGET>,
         filter_out_reason: Option<String>,
     ) -> PebbleQueryResultGeneric<TARGET>;
+
+    /// The same as `map_filter_result`, but with a closure, which can capture context such as the user's permissions or locale. The filtered out results are added to the `filter_count` and `filter_reason` of earlier filters instead of replacing them.
+    fn map_filter_result_with<TARGET, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> PebbleQueryResultGeneric<TARGET>
+    where
+        F: FnMut(O) -> Option<TARGET>;
+
+    /// Map every result with a closure that can fail. The first error is returned. The metadata is kept as it is.
+    fn try_map<TARGET, Err, F>(
+        self,
+        result_map: F,
+    ) -> Result<PebbleQueryResultGeneric<TARGET>, Err>
+    where
+        F: FnMut(O) -> Result<TARGET, Err>;
+
+    /// The same as `map_filter_result_with`, but the closure can fail. The first error is returned.
+    fn try_filter_map<TARGET, Err, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> Result<PebbleQueryResultGeneric<TARGET>, Err>
+    where
+        F: FnMut(O) -> Result<Option<TARGET>, Err>;
+
+    /// Map every result with an async closure, e.g. to enrich it with another query. The futures run concurrently and the order of the results is kept. The metadata is kept as it is.
+    async fn map_async<TARGET, F, Fut>(self, result_map: F) -> PebbleQueryResultGeneric<TARGET>
+    where
+        O: Send,
+        TARGET: Send,
+        F: FnMut(O) -> Fut + Send,
+        Fut: Future<Output = TARGET> + Send;
+
+    /// The same as `map_filter_result_with`, but with an async closure. The futures run concurrently and the order of the results is kept.
+    async fn filter_map_async<TARGET, F, Fut>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> PebbleQueryResultGeneric<TARGET>
+    where
+        O: Send,
+        TARGET: Send,
+        F: FnMut(O) -> Fut + Send,
+        Fut: Future<Output = Option<TARGET>> + Send;
 }
 
+#[async_trait]
 impl<O> PebbleQueryResultGenericUtilTraits<O> for PebbleQueryResultGeneric<O> {
     fn first(&self) -> Option<&O> {
         self.results.first()
@@ -121,4 +257,108 @@ impl<O> PebbleQueryResultGenericUtilTraits<O> for PebbleQueryResultGeneric<O> {
         };
         PebbleQueryResultGeneric { metadata, results }
     }
+
+    fn map_filter_result_with<TARGET, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> PebbleQueryResultGeneric<TARGET>
+    where
+        F: FnMut(O) -> Option<TARGET>,
+    {
+        let initial_result_count = self.results.len();
+        let results: Vec<TARGET> = self
+            .results
+            .into_iter()
+            .filter_map(result_filter_map)
+            .collect();
+        let metadata = filtered_metadata(
+            self.metadata,
+            initial_result_count,
+            results.len(),
+            filter_out_reason,
+        );
+        PebbleQueryResultGeneric { metadata, results }
+    }
+
+    fn try_map<TARGET, Err, F>(self, result_map: F) -> Result<PebbleQueryResultGeneric<TARGET>, Err>
+    where
+        F: FnMut(O) -> Result<TARGET, Err>,
+    {
+        let results = self
+            .results
+            .into_iter()
+            .map(result_map)
+            .collect::<Result<Vec<TARGET>, Err>>()?;
+        Ok(PebbleQueryResultGeneric {
+            metadata: self.metadata,
+            results,
+        })
+    }
+
+    fn try_filter_map<TARGET, Err, F>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> Result<PebbleQueryResultGeneric<TARGET>, Err>
+    where
+        F: FnMut(O) -> Result<Option<TARGET>, Err>,
+    {
+        let initial_result_count = self.results.len();
+        let results: Vec<TARGET> = self
+            .results
+            .into_iter()
+            .map(result_filter_map)
+            .collect::<Result<Vec<Option<TARGET>>, Err>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        let metadata = filtered_metadata(
+            self.metadata,
+            initial_result_count,
+            results.len(),
+            filter_out_reason,
+        );
+        Ok(PebbleQueryResultGeneric { metadata, results })
+    }
+
+    async fn map_async<TARGET, F, Fut>(self, result_map: F) -> PebbleQueryResultGeneric<TARGET>
+    where
+        O: Send,
+        TARGET: Send,
+        F: FnMut(O) -> Fut + Send,
+        Fut: Future<Output = TARGET> + Send,
+    {
+        let results = join_all(self.results.into_iter().map(result_map)).await;
+        PebbleQueryResultGeneric {
+            metadata: self.metadata,
+            results,
+        }
+    }
+
+    async fn filter_map_async<TARGET, F, Fut>(
+        self,
+        result_filter_map: F,
+        filter_out_reason: Option<String>,
+    ) -> PebbleQueryResultGeneric<TARGET>
+    where
+        O: Send,
+        TARGET: Send,
+        F: FnMut(O) -> Fut + Send,
+        Fut: Future<Output = Option<TARGET>> + Send,
+    {
+        let initial_result_count = self.results.len();
+        let results: Vec<TARGET> = join_all(self.results.into_iter().map(result_filter_map))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        let metadata = filtered_metadata(
+            self.metadata,
+            initial_result_count,
+            results.len(),
+            filter_out_reason,
+        );
+        PebbleQueryResultGeneric { metadata, results }
+    }
 }
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::{ConnectionTrait, EntityTrait, FromQueryResult, Select};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::{SerializationError, UnknownEntity};
+use crate::pebble_query::use_pebble_query_with_config;
+use crate::pebble_query_config::PebbleQueryConfig;
+use crate::pebble_query_result::PebbleQueryResultGeneric;
+use crate::sample_dto_structs::SearchQuery;
+
+/// A type-erased Pebble Query executor for one entity, returning its rows as JSON.
+#[async_trait]
+pub trait PebbleDynQuery<C, Ctx>: Send + Sync
+where
+    C: ConnectionTrait + Send + Sync,
+    Ctx: Send + Sync,
+{
+    async fn query(
+        &self,
+        query: Option<SearchQuery>,
+        context: &Ctx,
+        db: &C,
+    ) -> Result<PebbleQueryResultGeneric<Value>, PebbleQueryError>;
+}
+
+/// The executor of an entity: its query configuration and the select its queries start from.
+///
+/// The configuration is shared, so that the same configuration can also be used outside of the registry, e.g. by the entity's own endpoints.
+pub struct PebbleEntityExecutor<E: EntityTrait, Ctx> {
+    pub config: Arc<PebbleQueryConfig<E, Ctx>>,
+    pub default_select: Box<dyn Fn() -> Select<E> + Send + Sync>,
+}
+
+#[async_trait]
+impl<C, E, M, Ctx> PebbleDynQuery<C, Ctx> for PebbleEntityExecutor<E, Ctx>
+where
+    C: ConnectionTrait + Send + Sync,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Serialize + Sized + Send + Sync,
+    Ctx: Send + Sync,
+{
+    async fn query(
+        &self,
+        query: Option<SearchQuery>,
+        context: &Ctx,
+        db: &C,
+    ) -> Result<PebbleQueryResultGeneric<Value>, PebbleQueryError> {
+        let result =
+            use_pebble_query_with_config((self.default_select)(), query, &self.config, context, db)
+                .await?;
+
+        let results = result
+            .results
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<Value>, serde_json::Error>>()
+            .map_err(|err| SerializationError(err.to_string()))?;
+        Ok(PebbleQueryResultGeneric {
+            metadata: result.metadata,
+            results,
+        })
+    }
+}
+
+/// # A runtime registry of the entities that can be queried by name
+///
+/// `use_pebble_query` is generic over the entity, so serving every entity from one endpoint, e.g. `/api/{entity}/search`, would need a match over all the entity types. The registry erases the entity type instead: each entity is registered once under a name with its `PebbleQueryConfig` and default select, and queried by that name with the rows returned as JSON.
+///
+/// The models of the registered entities must implement `Serialize`.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::sync::{Arc, LazyLock};
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_query_config::PebbleQueryConfig;
+/// # use pebble_query::pebble_registry::PebbleRegistry;
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{author, book, AUTHOR_COLUMN_MAP, BOOK_COLUMN_MAP};
+/// # use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+/// # pub struct RequestContext;
+/// static BOOK_QUERY_CONFIG: LazyLock<Arc<PebbleQueryConfig<book::Entity, RequestContext>>> =
+///     LazyLock::new(|| Arc::new(PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())));
+/// static AUTHOR_QUERY_CONFIG: LazyLock<Arc<PebbleQueryConfig<author::Entity, RequestContext>>> =
+///     LazyLock::new(|| Arc::new(PebbleQueryConfig::new(AUTHOR_COLUMN_MAP.clone())));
+///
+/// # async fn example(db: &DatabaseConnection, entity: String, query: SearchQuery, ctx: RequestContext) -> Result<(), PebbleQueryError> {
+/// let mut registry: PebbleRegistry<DatabaseConnection, RequestContext> = PebbleRegistry::new();
+/// registry.register("books", BOOK_QUERY_CONFIG.clone(), book::Entity::find);
+/// registry.register("authors", AUTHOR_QUERY_CONFIG.clone(), || author::Entity::find().filter(author::Column::Name.ne("")));
+///
+/// // in the handler of `/api/{entity}/search`
+/// let result = registry.query(&entity, Some(query), &ctx, db).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PebbleRegistry<C, Ctx = ()> {
+    executors: HashMap<String, Box<dyn PebbleDynQuery<C, Ctx>>>,
+}
+
+impl<C, Ctx> Default for PebbleRegistry<C, Ctx> {
+    fn default() -> Self {
+        PebbleRegistry {
+            executors: HashMap::new(),
+        }
+    }
+}
+
+impl<C, Ctx> PebbleRegistry<C, Ctx>
+where
+    C: ConnectionTrait + Send + Sync,
+    Ctx: Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an entity under `name`, replacing any entity registered under the same name.
+    pub fn register<E, M, F>(
+        &mut self,
+        name: &str,
+        config: Arc<PebbleQueryConfig<E, Ctx>>,
+        default_select: F,
+    ) -> &mut Self
+    where
+        E: EntityTrait<Model = M>,
+        M: FromQueryResult + Serialize + Sized + Send + Sync,
+        F: Fn() -> Select<E> + Send + Sync + 'static,
+    {
+        self.register_executor(
+            name,
+            PebbleEntityExecutor {
+                config,
+                default_select: Box::new(default_select),
+            },
+        )
+    }
+
+    /// Register a custom executor under `name`, replacing any entity registered under the same name.
+    pub fn register_executor<Q>(&mut self, name: &str, executor: Q) -> &mut Self
+    where
+        Q: PebbleDynQuery<C, Ctx> + 'static,
+    {
+        self.executors.insert(name.to_string(), Box::new(executor));
+        self
+    }
+
+    /// The names of the registered entities, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.executors.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.executors.contains_key(name)
+    }
+
+    /// Run the query against the entity registered under `name`, or return `PebbleQueryError::UnknownEntity`.
+    pub async fn query(
+        &self,
+        name: &str,
+        query: Option<SearchQuery>,
+        context: &Ctx,
+        db: &C,
+    ) -> Result<PebbleQueryResultGeneric<Value>, PebbleQueryError> {
+        let executor = self
+            .executors
+            .get(name)
+            .ok_or_else(|| UnknownEntity(format!("{} is not registered", name)))?;
+        executor.query(query, context, db).await
+    }
+}
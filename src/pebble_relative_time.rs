@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, SecondsFormat, TimeZone, Utc,
+};
+use convert_case::{Case, Casing};
+use sea_orm::sea_query::ColumnType;
+use sea_orm::{ColumnTrait, EntityTrait};
+
+use crate::errors::PebbleQueryError;
+use crate::errors::PebbleQueryError::InvalidValue;
+use crate::sample_dto_structs::{SearchCondition, SearchQuery};
+
+/// The source of the current time that relative date and time values are resolved against, see `resolve_relative_times`.
+///
+/// The default is the system clock in UTC. Use `PebbleClock::fixed` for deterministic tests, or `PebbleClock::new` for the time zone of the user, which decides where days, weeks and months start.
+#[derive(Clone)]
+pub struct PebbleClock {
+    now: Arc<dyn Fn() -> DateTime<FixedOffset> + Send + Sync>,
+}
+
+impl Default for PebbleClock {
+    fn default() -> Self {
+        PebbleClock::system()
+    }
+}
+
+impl fmt::Debug for PebbleClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PebbleClock")
+            .field("now", &self.now())
+            .finish()
+    }
+}
+
+impl PebbleClock {
+    pub fn new<F>(now: F) -> Self
+    where
+        F: Fn() -> DateTime<FixedOffset> + Send + Sync + 'static,
+    {
+        PebbleClock { now: Arc::new(now) }
+    }
+
+    /// The system clock in UTC.
+    pub fn system() -> Self {
+        PebbleClock::new(|| Utc::now().fixed_offset())
+    }
+
+    /// A clock that always returns `now`.
+    pub fn fixed(now: DateTime<FixedOffset>) -> Self {
+        PebbleClock::new(move || now)
+    }
+
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        (self.now)()
+    }
+}
+
+/// The anchors of relative expressions, longest first so that `start_of_day` is not read as another anchor's prefix.
+const ANCHORS: [&str; 10] = [
+    "start_of_month",
+    "start_of_week",
+    "start_of_year",
+    "end_of_month",
+    "start_of_day",
+    "end_of_week",
+    "end_of_year",
+    "end_of_day",
+    "today",
+    "now",
+];
+
+/// The largest amount of an offset, e.g. the `7` of `now-7d`, so that the offsets stay far within the range of `Duration`.
+pub const MAX_OFFSET_AMOUNT: u32 = 1_000_000_000;
+
+/// # Resolve a relative date and time expression
+///
+/// An expression is an anchor followed by any number of offsets, e.g. `now-7d`, `start_of_month` or `start_of_month-1M`.
+///
+/// * Anchors: `now`, `today` (the same as `start_of_day`), and `start_of_` or `end_of_` followed by `day`, `week` (starting on Monday), `month` or `year`. An end is the last microsecond of the period, so it can be used as the upper bound of `Between`.
+/// * Offsets: `+` or `-`, a number and a unit, `s` (seconds), `m` (minutes), `h`, `d`, `w`, `M` (months) or `y`. They are applied in order after the anchor.
+///
+/// # Returns
+/// * `None`: The value is not a relative expression, e.g. an absolute date, and is used as is.
+/// * `PebbleQueryError::InvalidValue`: The value starts with an anchor but its offsets are not valid, an amount is above `MAX_OFFSET_AMOUNT`, or the time is out of the supported range.
+pub fn resolve_relative_time(
+    value: &str,
+    now: DateTime<FixedOffset>,
+) -> Result<Option<DateTime<FixedOffset>>, PebbleQueryError> {
+    let anchor = match ANCHORS.iter().find(|anchor| value.starts_with(*anchor)) {
+        Some(anchor) => *anchor,
+        None => return Ok(None),
+    };
+    let offsets = &value[anchor.len()..];
+    if !offsets.is_empty() && !offsets.starts_with(['+', '-']) {
+        return Ok(None);
+    }
+
+    let start_of_day = |date: NaiveDate| {
+        now.offset()
+            .from_local_datetime(&date.and_time(Default::default()))
+            .unwrap()
+    };
+    let today = now.date_naive();
+    let start_of_week = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let start_of_month = today.with_day(1).unwrap();
+    let start_of_year = today.with_ordinal(1).unwrap();
+    let one_microsecond = Duration::microseconds(1);
+    let mut time = match anchor {
+        "now" => now,
+        "today" | "start_of_day" => start_of_day(today),
+        "end_of_day" => start_of_day(today + Duration::days(1)) - one_microsecond,
+        "start_of_week" => start_of_day(start_of_week),
+        "end_of_week" => start_of_day(start_of_week + Duration::days(7)) - one_microsecond,
+        "start_of_month" => start_of_day(start_of_month),
+        "end_of_month" => start_of_day(start_of_month + Months::new(1)) - one_microsecond,
+        "start_of_year" => start_of_day(start_of_year),
+        _ => start_of_day(start_of_year + Months::new(12)) - one_microsecond,
+    };
+
+    let invalid = || {
+        InvalidValue(format!(
+            "{} is not a valid relative time, e.g. now-7d or start_of_month+1M",
+            value
+        ))
+    };
+    let out_of_range = || InvalidValue(format!("{} is out of the range of supported times", value));
+    let mut rest = offsets;
+    while !rest.is_empty() {
+        let negative = rest.starts_with('-');
+        let digits = rest[1..]
+            .find(|character: char| !character.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let amount: u32 = rest[1..1 + digits].parse().map_err(|_| out_of_range())?;
+        if amount > MAX_OFFSET_AMOUNT {
+            return Err(out_of_range());
+        }
+        let unit = rest[1 + digits..].chars().next().ok_or_else(invalid)?;
+        let duration = match unit {
+            's' => Some(Duration::seconds(amount as i64)),
+            'm' => Some(Duration::minutes(amount as i64)),
+            'h' => Some(Duration::hours(amount as i64)),
+            'd' => Some(Duration::days(amount as i64)),
+            'w' => Some(Duration::weeks(amount as i64)),
+            'M' | 'y' => None,
+            _ => return Err(invalid()),
+        };
+        time = match (duration, negative) {
+            (Some(duration), false) => time.checked_add_signed(duration),
+            (Some(duration), true) => time.checked_sub_signed(duration),
+            (None, negative) => {
+                let months = match unit {
+                    'y' => amount.checked_mul(12).ok_or_else(out_of_range)?,
+                    _ => amount,
+                };
+                match negative {
+                    false => time.checked_add_months(Months::new(months)),
+                    true => time.checked_sub_months(Months::new(months)),
+                }
+            }
+        }
+        .ok_or_else(out_of_range)?;
+        rest = &rest[2 + digits..];
+        if !rest.is_empty() && !rest.starts_with(['+', '-']) {
+            return Err(invalid());
+        }
+    }
+    Ok(Some(time))
+}
+
+/// Write a resolved time the way the database stores values of the column type, or `None` if the column is not a date or time column.
+///
+/// Dates are the days of the clock's time zone. `DateTime` and `Timestamp` columns have no time zone, and are taken to hold UTC times, like `Utc::now()` writes them: the resolved time is converted to UTC, so that `now-1h` is the same instant whatever the zone of the clock, and `start_of_day` is the midnight of the clock's zone.
+fn format_for_column_type(time: DateTime<FixedOffset>, column_type: &ColumnType) -> Option<String> {
+    match column_type {
+        ColumnType::Date => Some(time.format("%Y-%m-%d").to_string()),
+        ColumnType::DateTime | ColumnType::Timestamp => {
+            Some(time.naive_utc().format("%Y-%m-%d %H:%M:%S%.f").to_string())
+        }
+        ColumnType::TimestampWithTimeZone => {
+            Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, false))
+        }
+        _ => None,
+    }
+}
+
+fn resolve_condition_values(
+    condition: &mut SearchCondition,
+    column_type: &ColumnType,
+    now: DateTime<FixedOffset>,
+) -> Result<(), PebbleQueryError> {
+    let resolve = |value: &mut String| -> Result<(), PebbleQueryError> {
+        if let Some(time) = resolve_relative_time(value, now)? {
+            if let Some(formatted) = format_for_column_type(time, column_type) {
+                *value = formatted;
+            }
+        }
+        Ok(())
+    };
+    if let Some(value) = condition.value.as_mut() {
+        resolve(value)?;
+    }
+    if let Some(value_to) = condition.value_to.as_mut() {
+        resolve(value_to)?;
+    }
+    for value in condition.value_list.iter_mut() {
+        resolve(value)?;
+    }
+    Ok(())
+}
+
+/// # Resolve the relative date and time values of a query
+///
+/// Returns a copy of the query where the values of the conditions on date and time columns that are relative expressions, e.g. `now-7d` or `end_of_month`, are replaced by the times they stand for according to `clock`, see `resolve_relative_time`. The other values are left as they are.
+///
+/// `use_pebble_query_with_options` runs the resolved query with the clock of `PebbleQueryOptions`, and echoes the unresolved query in `metadata.query`, so saved filters stay relative.
+///
+/// # Arguments
+/// * `query`: The SearchQuery.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column, which tells the column type of each field.
+/// * `clock`: The clock the expressions are resolved against. All the expressions of the query are resolved against the same time.
+pub fn resolve_relative_times<E: EntityTrait>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    clock: &PebbleClock,
+) -> Result<SearchQuery, PebbleQueryError> {
+    let mut resolved_query = query.clone();
+    let filter = match resolved_query.filter.as_mut() {
+        Some(filter) => filter,
+        None => return Ok(resolved_query),
+    };
+
+    let now = clock.now();
+    for condition in filter.must.iter_mut().chain(filter.any.iter_mut()) {
+        let column = field_to_column_map
+            .get(&condition.field)
+            .or_else(|| field_to_column_map.get(&condition.field.to_case(Case::Snake)));
+        if let Some((_, column)) = column {
+            resolve_condition_values(condition, column.def().get_column_type(), now)?;
+        }
+    }
+    Ok(resolved_query)
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use convert_case::{Case, Casing};
+use sea_orm::sea_query::ColumnType;
+use sea_orm::{ColumnTrait, EntityTrait};
+use serde_json::{json, Map, Value};
+
+use crate::pebble_json::json_path_prefix;
+use crate::pebble_operators::PebbleOperatorRegistry;
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::sample_dto_structs::{SearchOperator, SortDirection, SortNulls};
+
+/// The JSON Schema `type` and `format` describing the values accepted for a column type.
+///
+/// The values in `SearchCondition` are always sent as strings, so the `type` tells the client how the string will be interpreted, e.g. `("integer", None)` or `("string", Some("date-time"))`.
+pub fn column_type_to_json_schema_type(
+    column_type: &ColumnType,
+) -> (&'static str, Option<&'static str>) {
+    match column_type {
+        ColumnType::TinyInteger
+        | ColumnType::SmallInteger
+        | ColumnType::Integer
+        | ColumnType::BigInteger
+        | ColumnType::TinyUnsigned
+        | ColumnType::SmallUnsigned
+        | ColumnType::Unsigned
+        | ColumnType::BigUnsigned
+        | ColumnType::Year(_) => ("integer", None),
+        ColumnType::Float | ColumnType::Double | ColumnType::Decimal(_) | ColumnType::Money(_) => {
+            ("number", None)
+        }
+        ColumnType::Boolean => ("boolean", None),
+        ColumnType::DateTime | ColumnType::Timestamp | ColumnType::TimestampWithTimeZone => {
+            ("string", Some("date-time"))
+        }
+        ColumnType::Date => ("string", Some("date")),
+        ColumnType::Time => ("string", Some("time")),
+        ColumnType::Uuid => ("string", Some("uuid")),
+        ColumnType::Json | ColumnType::JsonBinary => ("object", None),
+        _ => ("string", None),
+    }
+}
+
+/// The operators `add_condition` supports for a column type.
+///
+/// Text columns accept the pattern operators (`Like`, `Contains`) on top of the comparison operators, and nullable columns accept `IsNull` and `IsNotNull`.
+pub fn operators_for_column_type(column_type: &ColumnType, nullable: bool) -> Vec<SearchOperator> {
+    let mut operators = vec![SearchOperator::Equals, SearchOperator::NotEquals];
+
+    match column_type {
+        ColumnType::Boolean | ColumnType::Json | ColumnType::JsonBinary => {}
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text => {
+            operators.extend([
+                SearchOperator::Like,
+                SearchOperator::Contains,
+                SearchOperator::In,
+                SearchOperator::NotIn,
+            ]);
+        }
+        _ => {
+            operators.extend([
+                SearchOperator::GreaterThan,
+                SearchOperator::GreaterThanOrEquals,
+                SearchOperator::LessThan,
+                SearchOperator::LessThanOrEquals,
+                SearchOperator::In,
+                SearchOperator::NotIn,
+                SearchOperator::Between,
+                SearchOperator::NotBetween,
+            ]);
+        }
+    }
+
+    if nullable {
+        operators.extend([SearchOperator::IsNull, SearchOperator::IsNotNull]);
+    }
+    operators
+}
+
+/// JSON Schema of a `SearchCondition` on one field of the field map: the field name, its permitted operators and its value type.
+fn condition_schema_for_field<C: ColumnTrait>(
+    field: &str,
+    column: &C,
+    custom_operators: &PebbleOperatorRegistry,
+) -> Value {
+    let column_def = column.def();
+    let column_type = column_def.get_column_type();
+    let (value_type, value_format) = column_type_to_json_schema_type(column_type);
+    let operators = operators_for_column_type(column_type, column_def.is_null());
+    condition_schema(
+        json!({ "type": "string", "const": field }),
+        &operators,
+        custom_operators,
+        value_type,
+        value_format,
+    )
+}
+
+/// JSON Schema of a `SearchCondition` on a computed field. The type of its expression is unknown, so it accepts the comparison operators on any value.
+fn condition_schema_for_computed_field(
+    field: &str,
+    custom_operators: &PebbleOperatorRegistry,
+) -> Value {
+    let operators = operators_for_column_type(&ColumnType::Double, true);
+    condition_schema(
+        json!({ "type": "string", "const": field }),
+        &operators,
+        custom_operators,
+        "string",
+        None,
+    )
+}
+
+/// JSON Schema of a `SearchCondition` on the JSON path fields of a `prefix.*` entry, e.g. `meta.color` for `meta.*`. Their values are compared as JSON numbers, booleans or strings, and `Contains` tests JSON arrays.
+fn condition_schema_for_json_path(
+    prefix: &str,
+    custom_operators: &PebbleOperatorRegistry,
+) -> Value {
+    let mut operators = operators_for_column_type(&ColumnType::Double, true);
+    operators.extend([SearchOperator::Contains, SearchOperator::NotContains]);
+    condition_schema(
+        json!({ "type": "string", "pattern": format!("^{}\\..+", prefix) }),
+        &operators,
+        custom_operators,
+        "string",
+        None,
+    )
+}
+
+/// The custom operators can be used on every field, either by code in `operator` or by name in `operator_name`.
+fn condition_schema(
+    field_schema: Value,
+    operators: &[SearchOperator],
+    custom_operators: &PebbleOperatorRegistry,
+    value_type: &str,
+    value_format: Option<&str>,
+) -> Value {
+    let custom_operators = custom_operators.operators();
+    let operator_codes: Vec<i32> = operators
+        .iter()
+        .map(|operator| *operator as i32)
+        .chain(custom_operators.iter().map(|operator| operator.code))
+        .collect();
+    let operator_names: Vec<&str> = operators
+        .iter()
+        .map(|operator| operator.as_str_name())
+        .chain(
+            custom_operators
+                .iter()
+                .map(|operator| operator.name.as_str()),
+        )
+        .collect();
+    let custom_operator_names: Vec<&str> = custom_operators
+        .iter()
+        .map(|operator| operator.name.as_str())
+        .collect();
+
+    let mut value_schema = Map::new();
+    value_schema.insert("type".to_string(), json!("string"));
+    value_schema.insert("x-pebble-value-type".to_string(), json!(value_type));
+    if let Some(value_format) = value_format {
+        value_schema.insert("format".to_string(), json!(value_format));
+    }
+    let value_schema = Value::Object(value_schema);
+
+    json!({
+        "type": "object",
+        "required": ["field"],
+        // a custom operator can be named in `operator_name` instead of `operator`.
+        "anyOf": [{ "required": ["operator"] }, { "required": ["operator_name"] }],
+        "properties": {
+            "field": field_schema,
+            "operator": {
+                "type": "integer",
+                "enum": operator_codes,
+                "x-enum-varnames": operator_names,
+            },
+            "operator_name": { "type": "string", "enum": custom_operator_names },
+            "value": value_schema,
+            "value_to": value_schema,
+            "value_list": { "type": "array", "items": value_schema },
+            "threshold": { "type": "number" },
+        },
+    })
+}
+
+/// # Generate the JSON Schema of the SearchQuery accepted for an entity
+///
+/// This describes the query surface of an entity from its `field_to_column_map` and the column types: the field names that can be filtered, the operators permitted for each field, the value type of each field, and the fields that can be sorted.
+///
+/// The schema is inlined without `$ref`s, so it is valid both as a JSON Schema and as an OpenAPI 3 schema object, see `query_openapi_components`.
+///
+/// # Arguments
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+///
+/// # Returns
+/// * `Value`: The JSON Schema of the SearchQuery for the entity.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::pebble_schema::query_json_schema;
+/// # use pebble_query::sample_entities::BOOK_COLUMN_MAP;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let schema = query_json_schema(&BOOK_COLUMN_MAP);
+/// std::fs::write("book_query.schema.json", serde_json::to_string_pretty(&schema)?)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn query_json_schema<E: EntityTrait>(
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Value {
+    query_json_schema_for_policy(field_to_column_map, &PebbleFieldPolicy::default())
+}
+
+/// `query_json_schema` with the field map of a `PebbleQueryConfig` and the computed fields and custom operators of its field policy, which can be used like the fields of the field map and the operators of `SearchOperator`.
+pub fn query_json_schema_with_config<E: EntityTrait, Ctx>(
+    config: &PebbleQueryConfig<E, Ctx>,
+) -> Value {
+    query_json_schema_for_policy(&config.field_to_column_map, &config.field_policy)
+}
+
+fn query_json_schema_for_policy<E: EntityTrait>(
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+) -> Value {
+    let fields = query_fields(field_to_column_map, field_policy);
+
+    let condition_schemas: Vec<Value> = fields
+        .iter()
+        .map(|field| match field_to_column_map.get(*field) {
+            Some((_, column)) => {
+                condition_schema_for_field(field, column, &field_policy.custom_operators)
+            }
+            None => condition_schema_for_computed_field(field, &field_policy.custom_operators),
+        })
+        .chain(
+            json_path_prefixes(field_to_column_map)
+                .into_iter()
+                .map(|prefix| {
+                    condition_schema_for_json_path(prefix, &field_policy.custom_operators)
+                }),
+        )
+        .collect();
+    let condition_list_schema = json!({
+        "type": "array",
+        "items": { "oneOf": condition_schemas },
+    });
+
+    let sort_directions = [
+        SortDirection::Unspecified,
+        SortDirection::Asc,
+        SortDirection::Desc,
+    ];
+    let sort_nulls = [SortNulls::Unspecified, SortNulls::First, SortNulls::Last];
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("{}SearchQuery", E::default().table_name().to_case(Case::Pascal)),
+        "type": "object",
+        "properties": {
+            "relation": {
+                "type": "object",
+                "properties": {
+                    "parent_ids": { "type": "array", "items": { "type": "integer" } },
+                    "child_type": { "type": "array", "items": { "type": "string" } },
+                },
+            },
+            "sort": {
+                "type": "object",
+                "required": ["field"],
+                "properties": {
+                    "field": { "type": "string", "enum": fields },
+                    "order": {
+                        "type": "integer",
+                        "enum": sort_directions.iter().map(|direction| *direction as i32).collect::<Vec<i32>>(),
+                        "x-enum-varnames": sort_directions.iter().map(|direction| direction.as_str_name()).collect::<Vec<&str>>(),
+                    },
+                    "nulls": {
+                        "type": "integer",
+                        "enum": sort_nulls.iter().map(|nulls| *nulls as i32).collect::<Vec<i32>>(),
+                        "x-enum-varnames": sort_nulls.iter().map(|nulls| nulls.as_str_name()).collect::<Vec<&str>>(),
+                    },
+                    "case_insensitive": { "type": "boolean" },
+                },
+            },
+            "offset": { "type": "integer", "minimum": 0 },
+            "length": { "type": "integer", "minimum": 0 },
+            "page": { "type": "integer", "minimum": 0 },
+            "filter": {
+                "type": "object",
+                "properties": {
+                    "must": condition_list_schema,
+                    "any": condition_list_schema,
+                },
+            },
+            "find_one": { "type": "boolean" },
+            "find_all": { "type": "boolean" },
+            "include_deleted": { "type": "boolean" },
+            "only_deleted": { "type": "boolean" },
+            "after": { "type": "string" },
+            "include": { "type": "array", "items": { "type": "string" } },
+            "distinct_on": { "type": "array", "items": { "type": "string", "enum": fields } },
+            "group_by": { "type": "array", "items": { "type": "string", "enum": fields } },
+        },
+    })
+}
+
+/// The names of the fields a query on the entity can use: the fields of the field map and the computed fields of the field policy, sorted so that the generated schemas are stable between runs. The `meta.*` entries of JSON path fields are left out, see `json_path_prefixes`.
+pub(crate) fn query_fields<'a, E: EntityTrait>(
+    field_to_column_map: &'a HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &'a PebbleFieldPolicy,
+) -> Vec<&'a String> {
+    let mut fields: Vec<&String> = field_to_column_map
+        .keys()
+        .filter(|field| json_path_prefix(field).is_none())
+        .chain(
+            field_policy
+                .computed_fields
+                .keys()
+                .filter(|field| !field_to_column_map.contains_key(*field)),
+        )
+        .collect();
+    fields.sort();
+    fields
+}
+
+/// The prefixes of the JSON path fields of the field map, e.g. `meta` for a `meta.*` entry, sorted.
+pub(crate) fn json_path_prefixes<E: EntityTrait>(
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Vec<&str> {
+    let mut prefixes: Vec<&str> = field_to_column_map
+        .keys()
+        .filter_map(|field| json_path_prefix(field))
+        .collect();
+    prefixes.sort();
+    prefixes
+}
+
+/// Wrap the schema from `query_json_schema` into an OpenAPI 3 `components` object under `schema_name`, ready to be merged into an OpenAPI document.
+pub fn query_openapi_components<E: EntityTrait>(
+    schema_name: &str,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Value {
+    openapi_components(schema_name, query_json_schema(field_to_column_map))
+}
+
+/// `query_openapi_components` with the schema from `query_json_schema_with_config`.
+pub fn query_openapi_components_with_config<E: EntityTrait, Ctx>(
+    schema_name: &str,
+    config: &PebbleQueryConfig<E, Ctx>,
+) -> Value {
+    openapi_components(schema_name, query_json_schema_with_config(config))
+}
+
+fn openapi_components(schema_name: &str, schema: Value) -> Value {
+    let mut schema = schema;
+    // `$schema` is not allowed inside an OpenAPI schema object.
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+    }
+    json!({
+        "components": {
+            "schemas": {
+                schema_name: schema,
+            },
+        },
+    })
+}
+
+/// Convert the schema from `query_json_schema` into a `utoipa` schema, so it can be registered in a `utoipa::OpenApi` document.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::pebble_schema::query_utoipa_schema;
+/// # use pebble_query::sample_entities::BOOK_COLUMN_MAP;
+/// # fn example(mut openapi: utoipa::openapi::OpenApi) -> Result<(), serde_json::Error> {
+/// openapi
+///     .components
+///     .get_or_insert_with(Default::default)
+///     .schemas
+///     .insert("BookSearchQuery".to_string(), query_utoipa_schema(&BOOK_COLUMN_MAP)?);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "utoipa")]
+pub fn query_utoipa_schema<E: EntityTrait>(
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> Result<utoipa::openapi::RefOr<utoipa::openapi::Schema>, serde_json::Error> {
+    utoipa_schema(query_json_schema(field_to_column_map))
+}
+
+/// `query_utoipa_schema` with the schema from `query_json_schema_with_config`.
+#[cfg(feature = "utoipa")]
+pub fn query_utoipa_schema_with_config<E: EntityTrait, Ctx>(
+    config: &PebbleQueryConfig<E, Ctx>,
+) -> Result<utoipa::openapi::RefOr<utoipa::openapi::Schema>, serde_json::Error> {
+    utoipa_schema(query_json_schema_with_config(config))
+}
+
+#[cfg(feature = "utoipa")]
+fn utoipa_schema(
+    schema: Value,
+) -> Result<utoipa::openapi::RefOr<utoipa::openapi::Schema>, serde_json::Error> {
+    let mut schema = schema;
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+    }
+    serde_json::from_value(schema)
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use futures::{Stream, TryStreamExt};
+use sea_orm::{ConnectionTrait, EntityTrait, FromQueryResult, QuerySelect, Select, StreamTrait};
+
+use crate::errors::PebbleQueryError;
+use crate::pebble_connection::{
+    add_tiebreak_order_to_select, try_add_cursor_to_select_with_policy,
+};
+use crate::pebble_converter::{PebbleConverter, PebbleConverterTrait};
+use crate::pebble_query::try_query_to_select_with_policy;
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::pebble_query_options::PebbleQueryOptions;
+use crate::pebble_relative_time::resolve_relative_times;
+use crate::pebble_utils::get_query_pagination_numbers;
+use crate::sample_dto_structs::{SearchQuery, SearchResultMetadata};
+
+/// # Apply SearchQuery to a SeaOrm Select<Entity> and stream the rows
+///
+/// This applies the filters, sorting and pagination of the query exactly like `use_pebble_query`, but instead of collecting the rows into a `Vec` it returns them as a stream with SeaOrm's `stream()`, so that large exports do not have to be held in memory.
+///
+/// The stream does not carry the pagination information. Use `use_pebble_query_metadata` with the same arguments to get it.
+///
+/// # Arguments
+/// * `initial_select`: The initial select query.
+/// * `query`: Option<SearchQuery> object - the query to be applied to the select. If none, all entity entries will be streamed.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection, which is borrowed for as long as the stream lives.
+///
+/// # Example
+///
+/// ```rust
+/// # use futures::TryStreamExt;
+/// # use pebble_query::errors::PebbleQueryError;
+/// # use pebble_query::pebble_stream::{use_pebble_query_metadata, use_pebble_query_stream};
+/// # use pebble_query::sample_dto_structs::SearchQuery;
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// # async fn example(db: &DatabaseConnection, query: Option<SearchQuery>) -> Result<(), PebbleQueryError> {
+/// let metadata = use_pebble_query_metadata(book::Entity::find(), query.clone(), &BOOK_COLUMN_MAP, db).await?;
+/// let mut books = use_pebble_query_stream(book::Entity::find(), query, &BOOK_COLUMN_MAP, db).await?;
+/// while let Some(book) = books.try_next().await? {
+///     println!("{}", book.title);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn use_pebble_query_stream<'a, C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &'a C,
+) -> Result<impl Stream<Item = Result<M, PebbleQueryError>> + 'a + Send, PebbleQueryError>
+where
+    C: ConnectionTrait + StreamTrait + Send,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync + 'a,
+{
+    let select_with_conditions = query_to_stream_select(
+        initial_select,
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default().for_backend(db.get_database_backend()),
+        &PebbleQueryOptions::default(),
+    )?;
+    let stream = select_with_conditions.stream(db).await?;
+    Ok(stream.map_err(PebbleQueryError::from))
+}
+
+/// # Stream the rows of a SearchQuery with the entity's query configuration
+///
+/// This is `use_pebble_query_stream` restricted to the scopes and soft-delete column of a `PebbleQueryConfig`, with its field policy, limits and clock, like `use_pebble_query_with_config`.
+pub async fn use_pebble_query_stream_with_config<'a, C, E, M, Ctx>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &'a C,
+) -> Result<impl Stream<Item = Result<M, PebbleQueryError>> + 'a + Send, PebbleQueryError>
+where
+    C: ConnectionTrait + StreamTrait + Send,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync + 'a,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    let scoped_select = config.apply_scopes(initial_select, context)?;
+    let scoped_select = config.apply_soft_delete(scoped_select, &query)?;
+    let select_with_conditions = query_to_stream_select(
+        scoped_select,
+        Some(query),
+        &config.field_to_column_map,
+        &config.field_policy.for_backend(db.get_database_backend()),
+        &config.options,
+    )?;
+    let stream = select_with_conditions.stream(db).await?;
+    Ok(stream.map_err(PebbleQueryError::from))
+}
+
+/// The select that the stream runs: the one `use_pebble_query_with_options` fetches its rows with.
+fn query_to_stream_select<E: EntityTrait>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    options: &PebbleQueryOptions,
+) -> Result<Select<E>, PebbleQueryError> {
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    options.limits.validate(&query)?;
+    let query = resolve_relative_times(&query, field_to_column_map, &options.clock)?;
+    let mut select_with_conditions =
+        try_query_to_select_with_policy(&query, field_to_column_map, field_policy, initial_select)?;
+    if options.connection_paging && query.after.is_none() {
+        select_with_conditions =
+            add_tiebreak_order_to_select(select_with_conditions, &query, field_to_column_map)?;
+    }
+    if let Some(after) = &query.after {
+        select_with_conditions = try_add_cursor_to_select_with_policy(
+            select_with_conditions,
+            after,
+            &query,
+            field_to_column_map,
+            field_policy,
+        )?;
+        // the stream does not tell whether there is a next page, so the extra row is not needed.
+        if query.length > 0 {
+            select_with_conditions = select_with_conditions.limit(query.length as u64);
+        }
+    }
+    if query.find_one {
+        select_with_conditions = select_with_conditions.limit(1);
+    }
+    Ok(select_with_conditions)
+}
+
+/// # Get the metadata of a SearchQuery without fetching the rows
+///
+/// This runs only the count query of `use_pebble_query`, and returns the metadata that `use_pebble_query` would return with it. `result_items` is the number of rows the query would return, computed from the total, the offset and the length.
+///
+/// This is the metadata counterpart of `use_pebble_query_stream`.
+pub async fn use_pebble_query_metadata<C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+) -> Result<SearchResultMetadata, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    run_pebble_query_metadata(
+        initial_select,
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+        db,
+        &PebbleQueryOptions::default(),
+    )
+    .await
+}
+
+/// The metadata counterpart of `use_pebble_query_stream_with_config`.
+pub async fn use_pebble_query_metadata_with_config<C, E, M, Ctx>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+) -> Result<SearchResultMetadata, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    let scoped_select = config.apply_scopes(initial_select, context)?;
+    let scoped_select = config.apply_soft_delete(scoped_select, &query)?;
+    run_pebble_query_metadata(
+        scoped_select,
+        Some(query),
+        &config.field_to_column_map,
+        &config.field_policy,
+        db,
+        &config.options,
+    )
+    .await
+}
+
+async fn run_pebble_query_metadata<C, E, M>(
+    initial_select: Select<E>,
+    query: Option<SearchQuery>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+    db: &C,
+    options: &PebbleQueryOptions,
+) -> Result<SearchResultMetadata, PebbleQueryError>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+{
+    let query = query.unwrap_or(SearchQuery {
+        find_all: true,
+        ..Default::default()
+    });
+    options.limits.validate(&query)?;
+    let backend_policy = field_policy.for_backend(db.get_database_backend());
+    let field_policy: &PebbleFieldPolicy = &backend_policy;
+    let resolved_query = resolve_relative_times(&query, field_to_column_map, &options.clock)?;
+    let select_with_conditions = try_query_to_select_with_policy(
+        &resolved_query,
+        field_to_column_map,
+        field_policy,
+        initial_select,
+    )?;
+
+    let total_items_and_pages_number =
+        get_query_pagination_numbers(db, &resolved_query, select_with_conditions).await?;
+
+    let total_items = total_items_and_pages_number.number_of_items as i32;
+    let mut result_items = total_items.saturating_sub(query.offset.max(0));
+    if query.find_one {
+        result_items = result_items.min(1);
+    } else if query.length > 0 {
+        result_items = result_items.min(query.length);
+    }
+
+    Ok(
+        PebbleConverter::query_many_result_to_standard_query_result::<E>(
+            result_items,
+            total_items,
+            total_items_and_pages_number.number_of_pages as i32,
+            query,
+        ),
+    )
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use sea_orm::{ConnectionTrait, DbErr, EntityTrait, FromQueryResult, Select};
+
+use crate::errors::PebbleQueryError;
+use crate::pebble_query::{try_use_pebble_query, use_pebble_query_with_config};
+use crate::pebble_query_config::PebbleQueryConfig;
+use crate::sample_dto_structs::{SearchQuery, SearchResultMetadata};
+
+/// A prost response message that carries the metadata and the rows of a pebble query.
+///
+/// Implement this for the response message of your gRPC method, e.g. a message with a `SearchResultMetadata metadata = 1;` and a `repeated Book results = 2;` field.
+pub trait PebbleTonicResponse<P: prost::Message>: prost::Message + Sized {
+    fn from_pebble_result(metadata: SearchResultMetadata, results: Vec<P>) -> Self;
+}
+
+/// Map a PebbleQueryError to the closest gRPC status.
+///
+/// Invalid fields, operators and missing values are the client's fault and become `InvalidArgument`, queries exceeding the `PebbleQueryLimits` become `OutOfRange`, scopes that cannot be computed from the request and requests for deleted rows that are not allowed become `PermissionDenied`, while database errors become `NotFound`, `Unavailable` or `Internal` depending on the cause.
+impl From<PebbleQueryError> for tonic::Status {
+    fn from(err: PebbleQueryError) -> Self {
+        match err {
+            PebbleQueryError::InvalidConditionOperator(_)
+            | PebbleQueryError::InvalidOperator(_)
+            | PebbleQueryError::InvalidField(_)
+            | PebbleQueryError::MissingValue(_)
+            | PebbleQueryError::LeadingWildcardLike(_)
+            | PebbleQueryError::EmptyFilter(_)
+            | PebbleQueryError::InvalidCursor(_)
+            | PebbleQueryError::InvalidInclude(_)
+            | PebbleQueryError::InvalidValue(_)
+            | PebbleQueryError::InvalidGrouping(_) => {
+                tonic::Status::invalid_argument(err.to_string())
+            }
+            PebbleQueryError::TooManyConditions(_)
+            | PebbleQueryError::InListTooLarge(_)
+            | PebbleQueryError::PageLengthTooLarge(_)
+            | PebbleQueryError::OffsetTooLarge(_) => tonic::Status::out_of_range(err.to_string()),
+            PebbleQueryError::ScopeError(_) | PebbleQueryError::DeletedRowsNotAllowed(_) => {
+                tonic::Status::permission_denied(err.to_string())
+            }
+            PebbleQueryError::UnknownEntity(_) => tonic::Status::not_found(err.to_string()),
+            PebbleQueryError::ExportError(_)
+            | PebbleQueryError::SerializationError(_)
+            | PebbleQueryError::CacheError(_) => tonic::Status::internal(err.to_string()),
+            PebbleQueryError::SeaOrmDbError(ref db_err) => match db_err {
+                DbErr::RecordNotFound(_) => tonic::Status::not_found(err.to_string()),
+                DbErr::Conn(_) | DbErr::ConnectionAcquire(_) => {
+                    tonic::Status::unavailable(err.to_string())
+                }
+                _ => tonic::Status::internal(err.to_string()),
+            },
+        }
+    }
+}
+
+/// # Run a gRPC SearchQuery request with Pebble Query and return the gRPC response
+///
+/// This unwraps the `SearchQuery` from the tonic request, runs it with `try_use_pebble_query` against the initial select, converts each row into its prost message with `into_message`, and wraps the metadata and rows into the response message `R`.
+///
+/// # Arguments
+/// * `request`: The tonic request carrying the SearchQuery.
+/// * `initial_select`: The initial select query, e.g. `Entity::find()`.
+/// * `field_to_column_map`: User-provided map of string field name to SeaOrm Column.
+/// * `db`: The database connection.
+/// * `into_message`: The conversion from an entity model to its prost message.
+///
+/// # Returns
+/// * `tonic::Response<R>`: The response message with metadata and rows.
+/// * `tonic::Status`: The status converted from `PebbleQueryError` if the query fails.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::pebble_tonic::{pebble_tonic_query, PebbleTonicResponse};
+/// # use pebble_query::sample_dto_structs::{SearchQuery, SearchResultMetadata};
+/// # use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+/// # use sea_orm::{DatabaseConnection, EntityTrait};
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # pub struct Book {
+/// #     #[prost(int32, tag = "1")]
+/// #     pub id: i32,
+/// #     #[prost(string, tag = "2")]
+/// #     pub title: String,
+/// # }
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # pub struct SearchBooksResponse {
+/// #     #[prost(message, optional, tag = "1")]
+/// #     pub metadata: Option<SearchResultMetadata>,
+/// #     #[prost(message, repeated, tag = "2")]
+/// #     pub results: Vec<Book>,
+/// # }
+/// impl PebbleTonicResponse<Book> for SearchBooksResponse {
+///     fn from_pebble_result(metadata: SearchResultMetadata, results: Vec<Book>) -> Self {
+///         SearchBooksResponse { metadata: Some(metadata), results }
+///     }
+/// }
+///
+/// // the body of the `SearchBooks` method of the tonic service
+/// async fn search_books(
+///     db: &DatabaseConnection,
+///     request: tonic::Request<SearchQuery>,
+/// ) -> Result<tonic::Response<SearchBooksResponse>, tonic::Status> {
+///     pebble_tonic_query(request, book::Entity::find(), &BOOK_COLUMN_MAP, db, |book| Book {
+///         id: book.id,
+///         title: book.title,
+///     })
+///     .await
+/// }
+/// ```
+pub async fn pebble_tonic_query<C, E, M, P, R, F>(
+    request: tonic::Request<SearchQuery>,
+    initial_select: Select<E>,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    db: &C,
+    into_message: F,
+) -> Result<tonic::Response<R>, tonic::Status>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    P: prost::Message,
+    R: PebbleTonicResponse<P>,
+    F: FnMut(M) -> P,
+{
+    let query = request.into_inner();
+
+    let result = try_use_pebble_query(initial_select, Some(query), field_to_column_map, db).await?;
+
+    let results: Vec<P> = result.results.into_iter().map(into_message).collect();
+
+    Ok(tonic::Response::new(R::from_pebble_result(
+        result.metadata,
+        results,
+    )))
+}
+
+/// `pebble_tonic_query` with the query configuration of the entity: the scopes computed from `context`, the soft-delete column, the field policy (e.g. computed fields and custom operators) and the options, like `use_pebble_query_with_config`.
+pub async fn pebble_tonic_query_with_config<C, E, M, P, R, F, Ctx>(
+    request: tonic::Request<SearchQuery>,
+    initial_select: Select<E>,
+    config: &PebbleQueryConfig<E, Ctx>,
+    context: &Ctx,
+    db: &C,
+    into_message: F,
+) -> Result<tonic::Response<R>, tonic::Status>
+where
+    C: ConnectionTrait,
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    P: prost::Message,
+    R: PebbleTonicResponse<P>,
+    F: FnMut(M) -> P,
+{
+    let query = request.into_inner();
+
+    let result =
+        use_pebble_query_with_config(initial_select, Some(query), config, context, db).await?;
+
+    let results: Vec<P> = result.results.into_iter().map(into_message).collect();
+
+    Ok(tonic::Response::new(R::from_pebble_result(
+        result.metadata,
+        results,
+    )))
+}
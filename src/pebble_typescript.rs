@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use convert_case::{Case, Casing};
+use sea_orm::EntityTrait;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleQueryConfig};
+use crate::pebble_query_result::PebbleQueryResultGeneric;
+use crate::pebble_schema::{json_path_prefixes, query_fields};
+use crate::sample_dto_structs::{
+    SearchCondition, SearchCountAccuracy, SearchFilter, SearchOperator, SearchQuery,
+    SearchRelation, SearchResultMetadata, SearchSortOption, SortDirection, SortNulls,
+};
+
+/// # Generate the TypeScript definitions of the DTOs
+///
+/// This returns the content of a `.d.ts` file that mirrors the JSON shape of `SearchQuery`, `SearchRelation`, `SearchFilter`, `SearchCondition`, `SearchSortOption`, `SearchResultMetadata` and `PebbleQueryResultGeneric` as serialized by serde, so that web clients do not need to hand-write them.
+///
+/// `SearchOperator`, `SortDirection`, `SortNulls` and `SearchCountAccuracy` are serialized as numbers, so they are emitted as unions of their numeric values, each commented with its variant name.
+///
+/// # Example
+///
+/// ```rust
+/// # use pebble_query::pebble_typescript::{dto_typescript_definitions, entity_fields_typescript};
+/// # use pebble_query::sample_entities::BOOK_COLUMN_MAP;
+/// # fn example() -> std::io::Result<()> {
+/// let mut definitions = dto_typescript_definitions();
+/// definitions.push_str(&entity_fields_typescript("Book", &BOOK_COLUMN_MAP));
+/// std::fs::write("web/src/generated/pebble.d.ts", definitions)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn dto_typescript_definitions() -> String {
+    let mut output = String::new();
+    output.push_str("// This file is generated by pebble_query. Do not edit it by hand.\n\n");
+
+    let operators = [
+        SearchOperator::Unspecified,
+        SearchOperator::Like,
+        SearchOperator::NotLike,
+        SearchOperator::Ilike,
+        SearchOperator::NotIlike,
+        SearchOperator::Similar,
+        SearchOperator::Equals,
+        SearchOperator::NotEquals,
+        SearchOperator::GreaterThan,
+        SearchOperator::GreaterThanOrEquals,
+        SearchOperator::LessThan,
+        SearchOperator::LessThanOrEquals,
+        SearchOperator::In,
+        SearchOperator::NotIn,
+        SearchOperator::Contains,
+        SearchOperator::NotContains,
+        SearchOperator::StartsWith,
+        SearchOperator::NotStartsWith,
+        SearchOperator::EndsWith,
+        SearchOperator::NotEndsWith,
+        SearchOperator::Exists,
+        SearchOperator::NotExists,
+        SearchOperator::IsNull,
+        SearchOperator::IsNotNull,
+        SearchOperator::IsTrue,
+        SearchOperator::IsFalse,
+        SearchOperator::Between,
+        SearchOperator::NotBetween,
+    ];
+    write_numeric_enum(
+        &mut output,
+        "SearchOperator",
+        operators
+            .iter()
+            .map(|operator| (operator.as_str_name(), *operator as i32)),
+        "SEARCH_OPERATOR_",
+    );
+
+    let directions = [
+        SortDirection::Unspecified,
+        SortDirection::Asc,
+        SortDirection::Desc,
+    ];
+    write_numeric_enum(
+        &mut output,
+        "SortDirection",
+        directions
+            .iter()
+            .map(|direction| (direction.as_str_name(), *direction as i32)),
+        "SORT_DIRECTION_",
+    );
+
+    let nulls = [SortNulls::Unspecified, SortNulls::First, SortNulls::Last];
+    write_numeric_enum(
+        &mut output,
+        "SortNulls",
+        nulls
+            .iter()
+            .map(|nulls| (nulls.as_str_name(), *nulls as i32)),
+        "SORT_NULLS_",
+    );
+
+    let accuracies = [
+        SearchCountAccuracy::Unspecified,
+        SearchCountAccuracy::Exact,
+        SearchCountAccuracy::Estimated,
+        SearchCountAccuracy::LowerBound,
+    ];
+    write_numeric_enum(
+        &mut output,
+        "SearchCountAccuracy",
+        accuracies
+            .iter()
+            .map(|accuracy| (accuracy.as_str_name(), *accuracy as i32)),
+        "SEARCH_COUNT_ACCURACY_",
+    );
+
+    for interface in dto_interfaces() {
+        write_interface(&mut output, &interface);
+    }
+    output.push_str(
+        "export type SearchCondition<F extends string = string> = SearchConditionFields<F> &\n  ({ operator: SearchOperator; operator_name?: null } | { operator?: SearchOperator; operator_name: string });\n",
+    );
+    output
+}
+
+/// # Generate the TypeScript field unions of an entity
+///
+/// This returns `<Name>FilterField` and `<Name>SortField` string-literal unions of the field names in `field_to_column_map`, and a `<Name>SearchQuery` that only accepts these fields in its filter and sort. The JSON path fields of a `meta.*` entry can only be filtered by, as `` `meta.${string}` ``.
+///
+/// Append the output to the definitions from `dto_typescript_definitions`, which it depends on.
+pub fn entity_fields_typescript<E: EntityTrait>(
+    entity_name: &str,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+) -> String {
+    entity_fields_typescript_for_policy(
+        entity_name,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+    )
+}
+
+/// `entity_fields_typescript` with the field map of a `PebbleQueryConfig` and the computed fields of its field policy.
+pub fn entity_fields_typescript_with_config<E: EntityTrait, Ctx>(
+    entity_name: &str,
+    config: &PebbleQueryConfig<E, Ctx>,
+) -> String {
+    entity_fields_typescript_for_policy(
+        entity_name,
+        &config.field_to_column_map,
+        &config.field_policy,
+    )
+}
+
+fn entity_fields_typescript_for_policy<E: EntityTrait>(
+    entity_name: &str,
+    field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+) -> String {
+    let entity_name = entity_name.to_case(Case::Pascal);
+    let fields = query_fields(field_to_column_map, field_policy);
+    let sort_field_union = string_literal_union(&fields);
+    let filter_field_union = json_path_prefixes(field_to_column_map).iter().fold(
+        sort_field_union.clone(),
+        |union, prefix| {
+            let json_path_field = format!("`{}.${{string}}`", prefix);
+            match union.as_str() {
+                "never" => json_path_field,
+                _ => format!("{} | {}", union, json_path_field),
+            }
+        },
+    );
+
+    let mut output = String::new();
+    writeln!(output).unwrap();
+    writeln!(
+        output,
+        "export type {}FilterField = {};",
+        entity_name, filter_field_union
+    )
+    .unwrap();
+    writeln!(
+        output,
+        "export type {}SortField = {};",
+        entity_name, sort_field_union
+    )
+    .unwrap();
+    writeln!(output).unwrap();
+    writeln!(
+        output,
+        "export interface {0}SearchQuery extends Omit<SearchQuery, \"filter\" | \"sort\" | \"distinct_on\" | \"group_by\"> {{\n  filter?: SearchFilter<{0}FilterField> | null;\n  sort?: SearchSortOption<{0}SortField> | null;\n  distinct_on: {0}SortField[];\n  group_by: {0}SortField[];\n}}",
+        entity_name
+    )
+    .unwrap();
+    output
+}
+
+/// A DTO written as a TypeScript interface.
+///
+/// Its fields, and whether they are optional, are read from its serialized default value, so that the interface follows the DTO. `field_types` gives the TypeScript types that the default value does not tell, i.e. those of the fields that are `null`, arrays, nested DTOs or enumerations by default.
+struct TypescriptInterface {
+    declaration: &'static str,
+    default_value: Value,
+    field_types: &'static [(&'static str, &'static str)],
+}
+
+/// The interfaces of the DTOs. `SearchCondition` is written as `SearchConditionFields`, without `operator` and `operator_name`, which are added as the alternatives of the `SearchCondition` type.
+fn dto_interfaces() -> Vec<TypescriptInterface> {
+    let mut condition = to_value(SearchCondition::default());
+    if let Value::Object(fields) = &mut condition {
+        fields.remove("operator");
+        fields.remove("operator_name");
+    }
+    vec![
+        TypescriptInterface {
+            declaration: "SearchQuery",
+            default_value: to_value(SearchQuery::default()),
+            field_types: &[
+                ("relation", "SearchRelation"),
+                ("sort", "SearchSortOption"),
+                ("filter", "SearchFilter"),
+                ("after", "string"),
+                ("include", "string[]"),
+                ("distinct_on", "string[]"),
+                ("group_by", "string[]"),
+            ],
+        },
+        TypescriptInterface {
+            declaration: "SearchRelation",
+            default_value: to_value(SearchRelation::default()),
+            field_types: &[("parent_ids", "number[]"), ("child_type", "string[]")],
+        },
+        TypescriptInterface {
+            declaration: "SearchFilter<F extends string = string>",
+            default_value: to_value(SearchFilter::default()),
+            field_types: &[
+                ("must", "SearchCondition<F>[]"),
+                ("any", "SearchCondition<F>[]"),
+            ],
+        },
+        TypescriptInterface {
+            declaration: "SearchConditionFields<F extends string = string>",
+            default_value: condition,
+            field_types: &[
+                ("field", "F"),
+                ("threshold", "number"),
+                ("value", "string"),
+                ("value_to", "string"),
+                ("value_list", "string[]"),
+            ],
+        },
+        TypescriptInterface {
+            declaration: "SearchSortOption<F extends string = string>",
+            default_value: to_value(SearchSortOption::default()),
+            field_types: &[
+                ("field", "F"),
+                ("order", "SortDirection"),
+                ("nulls", "SortNulls"),
+            ],
+        },
+        TypescriptInterface {
+            declaration: "SearchResultMetadata",
+            default_value: to_value(SearchResultMetadata::default()),
+            field_types: &[
+                ("query", "SearchQuery"),
+                ("filter_count", "number"),
+                ("filter_reason", "string"),
+                ("cache_hit", "boolean"),
+                ("total_items_accuracy", "SearchCountAccuracy"),
+                ("has_next_page", "boolean"),
+                ("scanned_items", "number"),
+                ("next_offset", "number"),
+            ],
+        },
+        TypescriptInterface {
+            declaration: "PebbleQueryResultGeneric<T>",
+            default_value: to_value(PebbleQueryResultGeneric::<()> {
+                metadata: SearchResultMetadata::default(),
+                results: vec![],
+            }),
+            field_types: &[("metadata", "SearchResultMetadata"), ("results", "T[]")],
+        },
+    ]
+}
+
+fn to_value<T: Serialize>(dto: T) -> Value {
+    serde_json::to_value(dto).expect("the DTOs serialize to JSON")
+}
+
+/// Write the interface, with the fields that are `null` by default as optional. A field missing from `field_types` whose type the default value does not tell is typed `unknown`.
+fn write_interface(output: &mut String, interface: &TypescriptInterface) {
+    writeln!(output, "export interface {} {{", interface.declaration).unwrap();
+    if let Value::Object(fields) = &interface.default_value {
+        for (field, default_value) in fields {
+            let type_name = interface
+                .field_types
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, type_name)| *type_name)
+                .unwrap_or_else(|| default_value_type(default_value));
+            match default_value.is_null() {
+                true => writeln!(output, "  {}?: {} | null;", field, type_name),
+                false => writeln!(output, "  {}: {};", field, type_name),
+            }
+            .unwrap();
+        }
+    }
+    output.push_str("}\n\n");
+}
+
+/// The TypeScript type of a default value, `unknown` for `null`, arrays and objects.
+fn default_value_type(default_value: &Value) -> &'static str {
+    match default_value {
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        _ => "unknown",
+    }
+}
+
+fn string_literal_union(values: &[&String]) -> String {
+    if values.is_empty() {
+        return "never".to_string();
+    }
+    values
+        .iter()
+        .map(|value| format!("{:?}", value))
+        .collect::<Vec<String>>()
+        .join(" | ")
+}
+
+/// Write a prost enumeration as a union of its numeric values, each commented with its variant name.
+fn write_numeric_enum<'a>(
+    output: &mut String,
+    name: &str,
+    variants: impl Iterator<Item = (&'a str, i32)>,
+    prefix: &str,
+) {
+    writeln!(output, "export type {} =", name).unwrap();
+    for (str_name, value) in variants {
+        writeln!(
+            output,
+            "  | {} // {}",
+            value,
+            str_name.trim_start_matches(prefix).to_case(Case::Pascal)
+        )
+        .unwrap();
+    }
+    output.push_str("  ;\n\n");
+}
@@ -1,41 +1,87 @@
 use std::collections::HashMap;
 
+use crate::pebble_json::{json_comparison, json_contains_expr, json_path_column};
+use crate::pebble_operators::PebbleOperatorRegistry;
+use crate::pebble_query_config::{PebbleFieldPolicy, PebbleSortCollation};
+use crate::pebble_query_options::PebbleCountStrategy;
 use crate::sample_dto_structs::{
-    SearchCondition, SearchOperator, SearchQuery, SearchSortOption, SortDirection,
+    SearchCondition, SearchCountAccuracy, SearchOperator, SearchQuery, SearchSortOption,
+    SortDirection, SortNulls,
 };
 use convert_case::{Case, Casing};
 use sea_orm::prelude::Expr;
+use sea_orm::sea_query::{
+    Alias, BinOper, ColumnType, Func, NullOrdering, SelectStatement, SimpleExpr, WindowStatement,
+};
 use sea_orm::{
-    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, FromQueryResult,
-    ItemsAndPagesNumber, Linked, ModelTrait, PaginatorTrait, QueryFilter, QueryOrder,
-    QueryTrait, Select,
+    ColumnTrait, Condition, ConnectionTrait, DbBackend, DbErr, EntityTrait, FromQueryResult,
+    ItemsAndPagesNumber, Linked, ModelTrait, Order, PaginatorTrait, QueryFilter, QueryOrder,
+    QueryResult, QueryTrait, Select, Statement,
 };
 
 use crate::errors::PebbleQueryError;
-use crate::errors::PebbleQueryError::{InvalidOperator, MissingValue};
+use crate::errors::PebbleQueryError::{InvalidField, InvalidOperator, MissingValue};
 
 /// Add sort option to the Select and return the new Select.
+///
+/// Panics if the sort field is not provided in `field_to_column_map`, see `try_add_sort_to_select` for the fallible version.
 pub fn add_sort_to_select<T: ColumnTrait, Entity: EntityTrait>(
     select_entity: Select<Entity>,
     sort_condition: SearchSortOption,
     field_to_column_map: &HashMap<String, (Entity, T)>,
 ) -> Select<Entity> {
+    try_add_sort_to_select(select_entity, sort_condition, field_to_column_map)
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Add sort option to the Select and return the new Select, or an error if the sort field or direction is invalid.
+pub fn try_add_sort_to_select<T: ColumnTrait, Entity: EntityTrait>(
+    select_entity: Select<Entity>,
+    sort_condition: SearchSortOption,
+    field_to_column_map: &HashMap<String, (Entity, T)>,
+) -> Result<Select<Entity>, PebbleQueryError> {
+    try_add_sort_to_select_with_collation(select_entity, sort_condition, field_to_column_map, None)
+}
+
+/// Add sort option to the Select like `try_add_sort_to_select`, comparing the values of the sort field with `collation`, e.g. the one from `PebbleFieldPolicy::sort_collation`.
+pub fn try_add_sort_to_select_with_collation<T: ColumnTrait, Entity: EntityTrait>(
+    select_entity: Select<Entity>,
+    sort_condition: SearchSortOption,
+    field_to_column_map: &HashMap<String, (Entity, T)>,
+    collation: Option<&PebbleSortCollation>,
+) -> Result<Select<Entity>, PebbleQueryError> {
     let mut select_with_sort = select_entity;
 
     let column_tuple: (Entity, T) = *field_to_column_map
-        .iter()
-        .find(|(field_name, _column)| (field_name == &sort_condition.field.as_str()))
-        .unwrap_or_else(|| {
-            panic!(
+        .get(&field_map_key(field_to_column_map, &sort_condition.field))
+        .ok_or_else(|| {
+            InvalidField(format!(
                 "Column {} not provided in field_to_column_map",
                 sort_condition.field.as_str()
-            )
-        })
-        .1;
+            ))
+        })?;
 
     let column = column_tuple.1;
+    let sort_condition = SearchSortOption {
+        case_insensitive: sort_condition.case_insensitive && is_string_column(&column),
+        ..sort_condition
+    };
+
+    if sort_condition.nulls != SortNulls::Unspecified as i32
+        || sort_condition.case_insensitive
+        || collation.is_some()
+    {
+        return try_add_sort_expr_to_select(
+            select_with_sort,
+            &sort_condition,
+            Expr::col(column_tuple).into(),
+            collation,
+        );
+    }
 
-    match SortDirection::try_from(sort_condition.order).unwrap() {
+    match SortDirection::try_from(sort_condition.order)
+        .map_err(|_| InvalidOperator(format!("Invalid sort direction: {}", sort_condition.order)))?
+    {
         SortDirection::Unspecified => {
             select_with_sort = select_with_sort.order_by_asc(column);
         }
@@ -47,7 +93,81 @@ pub fn add_sort_to_select<T: ColumnTrait, Entity: EntityTrait>(
         }
     }
 
-    select_with_sort
+    Ok(select_with_sort)
+}
+
+/// Order the select by an expression, e.g. a computed field, in the direction and with the options of `sort_condition`; its `field` is not used.
+pub fn try_add_sort_expr_to_select<E: EntityTrait>(
+    select: Select<E>,
+    sort_condition: &SearchSortOption,
+    expr: SimpleExpr,
+    collation: Option<&PebbleSortCollation>,
+) -> Result<Select<E>, PebbleQueryError> {
+    let order = match SortDirection::try_from(sort_condition.order)
+        .map_err(|_| InvalidOperator(format!("Invalid sort direction: {}", sort_condition.order)))?
+    {
+        SortDirection::Unspecified | SortDirection::Asc => Order::Asc,
+        SortDirection::Desc => Order::Desc,
+    };
+    order_by_with_nulls(
+        select,
+        sort_key_expr(expr, sort_condition.case_insensitive, collation),
+        order,
+        sort_condition.nulls,
+    )
+}
+
+/// Order the select by `expr`, with the NULLs first or last according to `nulls`, a `SortNulls` value.
+///
+/// `NULLS FIRST` and `NULLS LAST` are written natively for Postgres and SQLite (3.30+). MySQL has no such syntax, so SeaQuery emulates it there by ordering by `expr IS NULL` first, which is the same as a `CASE WHEN expr IS NULL` sort key. With `SortNulls::Unspecified`, the database default is kept: NULLs first in ascending order on SQLite and MySQL, last on Postgres.
+pub fn order_by_with_nulls<E: EntityTrait>(
+    select: Select<E>,
+    expr: SimpleExpr,
+    order: Order,
+    nulls: i32,
+) -> Result<Select<E>, PebbleQueryError> {
+    let mut select = select;
+    let null_ordering = match SortNulls::try_from(nulls)
+        .map_err(|_| InvalidOperator(format!("Invalid nulls ordering: {}", nulls)))?
+    {
+        SortNulls::Unspecified => return Ok(select.order_by(expr, order)),
+        SortNulls::First => NullOrdering::First,
+        SortNulls::Last => NullOrdering::Last,
+    };
+    QueryTrait::query(&mut select).order_by_expr_with_nulls(expr, order, null_ordering);
+    Ok(select)
+}
+
+/// Whether the values of `column` are strings, the only values a case-insensitive sort lowercases; sorting other columns by their lowercased text would compare numbers and dates as strings.
+pub(crate) fn is_string_column<C: ColumnTrait>(column: &C) -> bool {
+    matches!(
+        column.def().get_column_type(),
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text
+    )
+}
+
+/// The expression the rows are sorted by for a sort value `expr`: lowercased if `case_insensitive`, then with the `collation`.
+///
+/// Cursor conditions apply it to both the column and the cursor value, so that they compare the same way the rows are ordered.
+pub fn sort_key_expr(
+    expr: SimpleExpr,
+    case_insensitive: bool,
+    collation: Option<&PebbleSortCollation>,
+) -> SimpleExpr {
+    let expr = if case_insensitive {
+        Func::lower(expr).into()
+    } else {
+        expr
+    };
+    match collation {
+        None => expr,
+        Some(PebbleSortCollation::CaseInsensitive) if case_insensitive => expr,
+        Some(PebbleSortCollation::CaseInsensitive) => Func::lower(expr).into(),
+        Some(PebbleSortCollation::Collate(collation)) => expr.binary(
+            BinOper::Custom("COLLATE"),
+            Expr::custom_keyword(Alias::new(collation)),
+        ),
+    }
 }
 
 // Add final condition
@@ -59,67 +179,115 @@ pub fn add_query_condition_to_sea_orm_condition<E>(
 where
     E: EntityTrait,
 {
-    let value = filter.value.clone().unwrap_or_else(|| "".to_string());
-    let value_list = filter.value_list.clone();
-    let value_to = filter.value_to.clone();
-    let result = add_condition::<E>(
+    add_condition(
+        input_current_condition,
+        filter,
+        Expr::col(column).into(),
+        false,
+        &PebbleOperatorRegistry::default(),
+    )
+}
+
+/// Add the condition on an expression instead of a column, e.g. a computed field, to the SeaOrm condition.
+///
+/// An expression has no column type to convert the string values to, so the values that parse as numbers or booleans are compared as such, e.g. `LENGTH(title) > 3` rather than `> '3'`, which SQLite would compare as text.
+pub fn add_query_condition_to_sea_orm_condition_on_expr(
+    input_current_condition: Condition,
+    filter: &SearchCondition,
+    expr: SimpleExpr,
+) -> Result<Condition, PebbleQueryError> {
+    add_condition(
         input_current_condition,
-        filter.operator,
-        &column,
-        value.as_str(),
-        value_list,
-        value_to,
-    )?;
-
-    Ok(result)
+        filter,
+        expr,
+        true,
+        &PebbleOperatorRegistry::default(),
+    )
 }
 
-/// Construct a new condition based on `operator`, `column`, `value`, `value_list`, `value_to` and add it to the `input_condition`.
+/// The value a condition compares with: a number or a boolean if `parse_values` is set and it parses as one, the string otherwise.
+fn condition_value(value: &str, parse_values: bool) -> sea_orm::Value {
+    if parse_values {
+        if let Ok(number) = value.parse::<i64>() {
+            return number.into();
+        }
+        if let Ok(number) = value.parse::<f64>() {
+            return number.into();
+        }
+        match value {
+            "true" => return true.into(),
+            "false" => return false.into(),
+            _ => {}
+        }
+    }
+    value.into()
+}
+
+/// Construct a new condition on `expr` based on the operator and values of `filter` and add it to the `input_condition`.
 /// Returns the new condition.
+/// Operators that are not in `SearchOperator`, or named by `operator_name`, are looked up in `custom_operators` before they are rejected.
 /// This is very incomplete and does not take SQL backend differences into account. Adjust as needed.
-fn add_condition<E>(
+fn add_condition(
     input_condition: Condition,
-    operator: i32,
-    column_tuple: &(E, E::Column),
-    value: &str,
-    value_list: Vec<String>,
-    value_to: Option<String>,
-) -> Result<Condition, PebbleQueryError>
-where
-    E: EntityTrait,
-{
-    let condition = match SearchOperator::try_from(operator).unwrap_or(SearchOperator::Unspecified)
-    {
+    filter: &SearchCondition,
+    expr: SimpleExpr,
+    parse_values: bool,
+    custom_operators: &PebbleOperatorRegistry,
+) -> Result<Condition, PebbleQueryError> {
+    let operator = match &filter.operator_name {
+        Some(operator_name) => custom_operators
+            .code(operator_name)
+            .ok_or_else(|| InvalidOperator(format!("Invalid operator: {}", operator_name)))?,
+        None => filter.operator,
+    };
+    let search_operator = match SearchOperator::try_from(operator) {
+        Ok(search_operator) => search_operator,
+        Err(_) => {
+            return match custom_operators.get(operator) {
+                Some(custom_operator) => {
+                    Ok(input_condition.add((custom_operator.build)(expr, filter)?))
+                }
+                None => Err(InvalidOperator(format!("Invalid operator: {}", operator))),
+            };
+        }
+    };
+    let value = filter.value.as_deref().unwrap_or_default();
+    let value_list = &filter.value_list;
+    let value_to = filter.value_to.clone();
+    let typed_value = condition_value(value, parse_values);
+    let typed_value_list = value_list
+        .iter()
+        .map(|value| condition_value(value, parse_values));
+    let condition = match search_operator {
         SearchOperator::Contains => {
             let value: &str = value;
 
-            input_condition.add(Expr::col(*column_tuple).like(format!("%{}%", value)))
+            input_condition.add(Expr::expr(expr).like(format!("%{}%", value)))
         }
-        SearchOperator::Equals => input_condition.add(Expr::col(*column_tuple).eq(value)),
-        SearchOperator::GreaterThan => input_condition.add(Expr::col(*column_tuple).gt(value)),
+        SearchOperator::Equals => input_condition.add(Expr::expr(expr).eq(typed_value)),
+        SearchOperator::GreaterThan => input_condition.add(Expr::expr(expr).gt(typed_value)),
         SearchOperator::GreaterThanOrEquals => {
-            input_condition.add(Expr::col(*column_tuple).gte(value))
-        }
-        SearchOperator::Like => input_condition.add(Expr::col(*column_tuple).like(value)),
-        SearchOperator::LessThan => input_condition.add(Expr::col(*column_tuple).lt(value)),
-        SearchOperator::LessThanOrEquals => {
-            input_condition.add(Expr::col(*column_tuple).lte(value))
-        }
-        SearchOperator::NotEquals => input_condition.add(Expr::col(*column_tuple).ne(value)),
-        SearchOperator::In => input_condition.add(Expr::col(*column_tuple).is_in(value_list)),
-        SearchOperator::NotIn => {
-            input_condition.add(Expr::col(*column_tuple).is_not_in(value_list))
+            input_condition.add(Expr::expr(expr).gte(typed_value))
         }
-        SearchOperator::IsNull => input_condition.add(Expr::col(*column_tuple).is_null()),
-        SearchOperator::IsNotNull => input_condition.add(Expr::col(*column_tuple).is_not_null()),
+        SearchOperator::Like => input_condition.add(Expr::expr(expr).like(value)),
+        SearchOperator::LessThan => input_condition.add(Expr::expr(expr).lt(typed_value)),
+        SearchOperator::LessThanOrEquals => input_condition.add(Expr::expr(expr).lte(typed_value)),
+        SearchOperator::NotEquals => input_condition.add(Expr::expr(expr).ne(typed_value)),
+        SearchOperator::In => input_condition.add(Expr::expr(expr).is_in(typed_value_list)),
+        SearchOperator::NotIn => input_condition.add(Expr::expr(expr).is_not_in(typed_value_list)),
+        SearchOperator::IsNull => input_condition.add(Expr::expr(expr).is_null()),
+        SearchOperator::IsNotNull => input_condition.add(Expr::expr(expr).is_not_null()),
         // SearchOperator::StartsWith => {
-        //     input_condition.add(Expr::col(*column_tuple).starts_with(value)) // These are commented because they are currently not supported by SeaORM 12's `Expr`.
+        //     input_condition.add(Expr::expr(expr).starts_with(value)) // These are commented because they are currently not supported by SeaORM 12's `Expr`.
         // }
-        // SearchOperator::EndsWith => input_condition.add(Expr::col(*column_tuple).ends_with(value)),
+        // SearchOperator::EndsWith => input_condition.add(Expr::expr(expr).ends_with(value)),
         SearchOperator::Between => {
             if !value.is_empty() {
                 if let Some(value_to) = value_to {
-                    input_condition.add(Expr::col(*column_tuple).between(value, value_to.as_str()))
+                    input_condition.add(
+                        Expr::expr(expr)
+                            .between(typed_value, condition_value(&value_to, parse_values)),
+                    )
                 } else {
                     return Err(MissingValue("value_to is required for between".to_string()));
                 }
@@ -130,8 +298,10 @@ where
         SearchOperator::NotBetween => {
             if !value.is_empty() {
                 if let Some(value_to) = value_to {
-                    input_condition
-                        .add(Expr::col(*column_tuple).not_between(value, value_to.as_str()))
+                    input_condition.add(
+                        Expr::expr(expr)
+                            .not_between(typed_value, condition_value(&value_to, parse_values)),
+                    )
                 } else {
                     return Err(MissingValue(
                         "value_to is required for not between".to_string(),
@@ -143,13 +313,36 @@ where
                 ));
             }
         }
+        // `Unspecified` and the operators without an implementation are not looked up in `custom_operators`, which cannot register their codes.
         _ => {
-            return Err(InvalidOperator(format!("Invalid operator: {}", operator)));
+            return Err(InvalidOperator(format!(
+                "Unsupported operator: {}",
+                search_operator.as_str_name()
+            )));
         }
     };
     Ok(condition)
 }
 
+/// Normalize a field to snake case. The path of a JSON path field of `map`, e.g. `zipCode` in `meta.zipCode` with a `meta.*` entry, is kept as is, as it names JSON keys rather than columns.
+fn normalize_field<V>(map: &HashMap<String, V>, field: &str) -> String {
+    if let Some((prefix, path)) = field.split_once('.') {
+        let prefix = prefix.to_case(Case::Snake);
+        if map.contains_key(&format!("{}.*", prefix)) {
+            return format!("{}.{}", prefix, path);
+        }
+    }
+    field.to_case(Case::Snake)
+}
+
+/// The key of `field` in a map keyed by field name, e.g. the field map: the field itself if the map has it, or else its snake case form, the way `normalize_query_with_map` normalizes the fields of a query.
+pub(crate) fn field_map_key<V>(map: &HashMap<String, V>, field: &str) -> String {
+    match map.contains_key(field) {
+        true => field.to_string(),
+        false => normalize_field(map, field),
+    }
+}
+
 /// Traverse all query fields and normalize them to snake case.
 ///
 /// assert_eq!("my_variable_name", "My variable NAME".to_case(Case::Snake)
@@ -183,6 +376,33 @@ pub fn normalize_query(input_query: &SearchQuery) -> SearchQuery {
             sort
         });
     }
+
+    query.distinct_on = query
+        .distinct_on
+        .iter()
+        .map(|field| field.to_case(Case::Snake))
+        .collect();
+    query.group_by = query
+        .group_by
+        .iter()
+        .map(|field| field.to_case(Case::Snake))
+        .collect();
+    query
+}
+
+/// Normalize the query like `normalize_query`, but keep the paths of the JSON path fields of `field_to_column_map` as they are, e.g. `meta.zipCode` for a `meta.*` entry.
+pub fn normalize_query_with_map<V>(
+    input_query: &SearchQuery,
+    field_to_column_map: &HashMap<String, V>,
+) -> SearchQuery {
+    let mut query = normalize_query(input_query);
+    if let (Some(filter), Some(input_filter)) = (query.filter.as_mut(), &input_query.filter) {
+        let conditions = filter.must.iter_mut().chain(filter.any.iter_mut());
+        let input_conditions = input_filter.must.iter().chain(input_filter.any.iter());
+        for (condition, input_condition) in conditions.zip(input_conditions) {
+            condition.field = normalize_field(field_to_column_map, &input_condition.field);
+        }
+    }
     query
 }
 
@@ -224,6 +444,209 @@ where
     Ok(total_items_and_pages_number)
 }
 
+/// Get the pagination information from the total number of items, using the same page size as `get_query_pagination_numbers`.
+pub fn pagination_numbers_from_total(
+    query: &SearchQuery,
+    number_of_items: u64,
+) -> ItemsAndPagesNumber {
+    let page_size = match query.length > 0 {
+        true => query.length as u64,
+        false => DEFAULT_PAGE_SIZE,
+    };
+    ItemsAndPagesNumber {
+        number_of_items,
+        number_of_pages: number_of_items.div_ceil(page_size),
+    }
+}
+
+/// Execute the select with conditions and return the rows, or only the first row if the query asks for `find_one`.
+pub async fn fetch_query_results<E, C, M>(
+    db: &C,
+    query: &SearchQuery,
+    select_with_conditions: Select<E>,
+) -> Result<Vec<M>, DbErr>
+where
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    C: ConnectionTrait,
+{
+    // check find_one and find_all fields in the SearchQuery before execute the queries receive data back.
+    if query.find_one {
+        let result = select_with_conditions.one(db).await?;
+        // if exists return the result in Vec, else return empty Vec
+        Ok(result.into_iter().collect())
+    } else {
+        select_with_conditions.all(db).await
+    }
+}
+
+static WINDOW_TOTAL_COUNT_ALIAS: &str = "pebble_total_count";
+
+/// A row of the select together with the `COUNT(*) OVER ()` total selected alongside it.
+struct ModelWithWindowCount<M> {
+    model: M,
+    total_count: i64,
+}
+
+impl<M: FromQueryResult> FromQueryResult for ModelWithWindowCount<M> {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(ModelWithWindowCount {
+            model: M::from_query_result(res, pre)?,
+            total_count: res.try_get(pre, WINDOW_TOTAL_COUNT_ALIAS)?,
+        })
+    }
+}
+
+/// Execute the select with conditions once, getting both the rows and the pagination information from a `COUNT(*) OVER ()` window function.
+///
+/// If no row is returned, e.g. the offset is past the last row, the total is unknown and a separate count query is run.
+pub async fn fetch_query_results_with_window_count<E, C, M>(
+    db: &C,
+    query: &SearchQuery,
+    select_with_conditions: Select<E>,
+) -> Result<(Vec<M>, ItemsAndPagesNumber), DbErr>
+where
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    C: ConnectionTrait,
+{
+    let mut select_with_window_count = select_with_conditions.clone();
+    QueryTrait::query(&mut select_with_window_count).expr_window_as(
+        Expr::cust("COUNT(*)"),
+        WindowStatement::new(),
+        Alias::new(WINDOW_TOTAL_COUNT_ALIAS),
+    );
+    let selector = select_with_window_count.into_model::<ModelWithWindowCount<M>>();
+
+    let rows: Vec<ModelWithWindowCount<M>> = if query.find_one {
+        selector.one(db).await?.into_iter().collect()
+    } else {
+        selector.all(db).await?
+    };
+
+    let total_items_and_pages_number = match rows.first() {
+        Some(row) => pagination_numbers_from_total(query, row.total_count as u64),
+        None => get_query_pagination_numbers(db, query, select_with_conditions).await?,
+    };
+    let results = rows.into_iter().map(|row| row.model).collect();
+    Ok((results, total_items_and_pages_number))
+}
+
+/// Get the pagination information from the query with the given count strategy, and how accurate the total number of items is.
+///
+/// With `PebbleCountStrategy::Exact` this is the same as `get_query_pagination_numbers`.
+pub async fn get_query_pagination_numbers_with_strategy<E, C, M>(
+    db: &C,
+    query: &SearchQuery,
+    select_with_conditions: Select<E>,
+    count_strategy: PebbleCountStrategy,
+) -> Result<(ItemsAndPagesNumber, SearchCountAccuracy), DbErr>
+where
+    E: EntityTrait<Model = M>,
+    M: FromQueryResult + Sized + Send + Sync,
+    C: ConnectionTrait,
+{
+    match count_strategy {
+        PebbleCountStrategy::Exact => {}
+        PebbleCountStrategy::Estimated => {
+            if let Some(estimated_items) =
+                estimate_query_items::<E, C>(db, select_with_conditions.clone()).await?
+            {
+                return Ok((
+                    pagination_numbers_from_total(query, estimated_items),
+                    SearchCountAccuracy::Estimated,
+                ));
+            }
+        }
+        PebbleCountStrategy::Capped(cap) => {
+            let counted_items = count_query_items_up_to(db, select_with_conditions, cap).await?;
+            let accuracy = match counted_items >= cap {
+                true => SearchCountAccuracy::LowerBound,
+                false => SearchCountAccuracy::Exact,
+            };
+            return Ok((
+                pagination_numbers_from_total(query, counted_items),
+                accuracy,
+            ));
+        }
+    }
+
+    let total_items_and_pages_number =
+        get_query_pagination_numbers(db, query, select_with_conditions).await?;
+    Ok((total_items_and_pages_number, SearchCountAccuracy::Exact))
+}
+
+/// The select statement with the pagination and sorting of the query removed, ready to be counted.
+fn select_to_count_statement<E: EntityTrait>(select_with_conditions: Select<E>) -> SelectStatement {
+    select_with_conditions
+        .into_query()
+        .reset_limit()
+        .reset_offset()
+        .clear_order_by()
+        .to_owned()
+}
+
+/// Count the rows of the select, but stop counting at `cap`.
+async fn count_query_items_up_to<E, C>(
+    db: &C,
+    select_with_conditions: Select<E>,
+    cap: u64,
+) -> Result<u64, DbErr>
+where
+    E: EntityTrait,
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+    let statement = backend.build(
+        SelectStatement::new()
+            .expr_as(Expr::cust("COUNT(*)"), Alias::new("num_items"))
+            .from_subquery(
+                select_to_count_statement(select_with_conditions)
+                    .limit(cap)
+                    .to_owned(),
+                Alias::new("sub_query"),
+            ),
+    );
+    let num_items = match db.query_one(statement).await? {
+        Some(result) => result.try_get::<i64>("", "num_items")? as u64,
+        None => 0,
+    };
+    Ok(num_items)
+}
+
+/// Estimate the rows of the select from the plan of the query planner, or `None` if there is no estimate.
+///
+/// Only Postgres estimates the rows of a filtered select, with `EXPLAIN`; the other backends have no estimate. A failed `EXPLAIN` is returned as an error rather than ignored, as it aborts the transaction it runs in.
+async fn estimate_query_items<E, C>(
+    db: &C,
+    select_with_conditions: Select<E>,
+) -> Result<Option<u64>, DbErr>
+where
+    E: EntityTrait,
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+    if backend != DbBackend::Postgres {
+        return Ok(None);
+    }
+    let count_statement = backend.build(&select_to_count_statement(select_with_conditions));
+    let explain_statement = Statement::from_sql_and_values(
+        backend,
+        format!("EXPLAIN (FORMAT JSON) {}", count_statement.sql),
+        count_statement
+            .values
+            .map(|values| values.0)
+            .unwrap_or_default(),
+    );
+    let plan: serde_json::Value = match db.query_one(explain_statement).await? {
+        Some(result) => result.try_get("", "QUERY PLAN")?,
+        None => return Ok(None),
+    };
+    Ok(plan[0]["Plan"]["Plan Rows"]
+        .as_f64()
+        .map(|plan_rows| plan_rows as u64))
+}
+
 pub fn apply_conditions_to_select<E, M>(
     query: &SearchQuery,
     field_to_column_map: &HashMap<String, (E, <E as EntityTrait>::Column)>,
@@ -306,29 +729,60 @@ where
     select_with_conditions
 }
 
+/// Convert the filter of a SearchQuery into the SeaOrm `must` and `any` conditions.
+///
+/// Panics if a filter field is not provided in `field_to_column_map` or a condition is invalid, see `try_query_to_seaorm_conditions` for the fallible version.
 pub fn query_to_seaorm_conditions<E: EntityTrait>(
     query: &SearchQuery,
     field_to_column_map: &HashMap<String, (E, <E>::Column)>,
 ) -> (Condition, Condition) {
-    let query = normalize_query(query);
+    try_query_to_seaorm_conditions(query, field_to_column_map)
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Convert the filter of a SearchQuery into the SeaOrm `must` and `any` conditions, or an error if a field or condition is invalid.
+pub fn try_query_to_seaorm_conditions<E: EntityTrait>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E>::Column)>,
+) -> Result<(Condition, Condition), PebbleQueryError> {
+    try_query_to_seaorm_conditions_with_policy(
+        query,
+        field_to_column_map,
+        &PebbleFieldPolicy::default(),
+    )
+}
+
+/// Convert the filter of a SearchQuery into the SeaOrm `must` and `any` conditions like `try_query_to_seaorm_conditions`, also accepting the computed fields and custom operators of the field policy.
+pub fn try_query_to_seaorm_conditions_with_policy<E: EntityTrait>(
+    query: &SearchQuery,
+    field_to_column_map: &HashMap<String, (E, <E>::Column)>,
+    field_policy: &PebbleFieldPolicy,
+) -> Result<(Condition, Condition), PebbleQueryError> {
+    let query = normalize_query_with_map(query, field_to_column_map);
     let mut current_must_condition = Condition::all();
     let mut current_any_condition = Condition::any();
 
     let query_filter = query.filter.clone();
     if let Some(filter_set) = query_filter {
         for filter in filter_set.must {
-            current_must_condition =
-                extract_query_conditions::<E>(current_must_condition, filter, field_to_column_map)
-                    .unwrap();
+            current_must_condition = extract_query_conditions::<E>(
+                current_must_condition,
+                filter,
+                field_to_column_map,
+                field_policy,
+            )?;
         }
 
         for filter in filter_set.any {
-            current_any_condition =
-                extract_query_conditions::<E>(current_any_condition, filter, field_to_column_map)
-                    .unwrap();
+            current_any_condition = extract_query_conditions::<E>(
+                current_any_condition,
+                filter,
+                field_to_column_map,
+                field_policy,
+            )?;
         }
     };
-    (current_must_condition, current_any_condition)
+    Ok((current_must_condition, current_any_condition))
 }
 
 /// Extract conditions from SearchQuery's FilterSet and returns SeaOrm Condition ready to be used in Find selector.
@@ -344,84 +798,112 @@ pub fn query_to_seaorm_conditions<E: EntityTrait>(
 /// ## Example:
 ///
 /// ```
-/// use carrel_commons::generic::api::query::v1::{Condition, Operator};
-///
-///     fn main() {
-///
-///      let sample_sq_filter_is_1 = carrel_commons::generic::api::query::v1::Condition {
-///! field: "id".to_string(),
-///! operator: Operator::Eq,
-///! value: Some("1".to_string()),
-///! value_list: vec![], // for operators like `in` or `not in`
-///! value_to: None, // for operators like `between` or `not between`
-///      }; // this translate to `id = 1`
-///
-///     let sample_sq_filter_is_in_1_2_3 = carrel_commons::generic::api::query::v1::Condition {
-///! field: "id".to_string(),
-///! operator: i32::from(Operator::In),
-///! value: None,
-///! value_list: vec!["1".to_string(), "2".to_string(), "3".to_string()],
-///! value_to: None,
-///      }; // this translate to `id in (1, 2, 3)`
-///
-///     let sample_sq_filter_is_between_1_to_10 = carrel_commons::generic::api::query::v1::Condition {
-///! field: "id".to_string(),
-///! operator: i32::from(Operator::Between),
-///! value: Some("1".to_string()),
-///! value_list: vec![],
-///! value_to: Some("10".to_string()),
-///
-///     }; // this translate to `id between 1 and 10`
-///
-///     }
+/// use pebble_query::sample_dto_structs::{SearchCondition, SearchOperator};
 ///
+/// let sample_sq_filter_is_1 = SearchCondition {
+///     field: "id".to_string(),
+///     operator: SearchOperator::Equals as i32,
+///     value: Some("1".to_string()),
+///     value_list: vec![], // for operators like `in` or `not in`
+///     value_to: None,     // for operators like `between` or `not between`
+///     ..Default::default()
+/// }; // this translate to `id = 1`
 ///
+/// let sample_sq_filter_is_in_1_2_3 = SearchCondition {
+///     field: "id".to_string(),
+///     operator: SearchOperator::In as i32,
+///     value: None,
+///     value_list: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+///     value_to: None,
+///     ..Default::default()
+/// }; // this translate to `id in (1, 2, 3)`
 ///
+/// let sample_sq_filter_is_between_1_to_10 = SearchCondition {
+///     field: "id".to_string(),
+///     operator: SearchOperator::Between as i32,
+///     value: Some("1".to_string()),
+///     value_list: vec![],
+///     value_to: Some("10".to_string()),
+///     ..Default::default()
+/// }; // this translate to `id between 1 and 10`
 /// ```
 ///
 /// * `field_column_map`: User-provided map of string field name to SeaOrm Column. This is used to convert the field name in the `sq_filter` to SeaOrm Column.
+/// * `field_policy`: Fields that are not in `field_column_map` are looked up in its computed fields, and operators that are not in `SearchOperator` in its custom operators.
 ///
 /// ## Example:
 ///
 /// ```
-/// use sea_orm::tests_cfg::cake;
-///
-///     let map = std::collections::HashMap::from([
-///    ("id".to_string(), (cake::Entity, cake::Column::Id)),
-///    ("name".to_string(), (cake::Entity, cake::Column::Name)),
-///    ("price".to_string(), (cake::Entity,cake::Column::Price)),
-///    // ... and so on.
-///
-///     ]);
+/// use pebble_query::sample_entities::book;
 ///
+/// let map = std::collections::HashMap::from([
+///     ("id".to_string(), (book::Entity, book::Column::Id)),
+///     ("title".to_string(), (book::Entity, book::Column::Title)),
+///     ("price".to_string(), (book::Entity, book::Column::Price)),
+///     // ... and so on.
+/// ]);
 /// ```
 fn extract_query_conditions<E>(
     base_sea_orm_condition: Condition,
     sq_filter: SearchCondition,
     field_column_map: &HashMap<String, (E, E::Column)>,
-) -> Result<Condition, DbErr>
+    field_policy: &PebbleFieldPolicy,
+) -> Result<Condition, PebbleQueryError>
 where
     E: EntityTrait,
 {
     // use the provided column name to Expr(Entity, Entity::Column) map to construct a condition
-    let mapped_column: (E, E::Column) = *field_column_map
+    let mapped_column = field_column_map
         .iter()
-        .find(|(field_name, _column)| (field_name == &sq_filter.field.as_str()))
-        .expect(
-            format!(
-                "Column \"{}\" not provided in field_to_column_map",
-                sq_filter.field.as_str() // throw if
-            )
-            .as_str(),
-        )
-        .1;
+        .find(|(field_name, _column)| field_name == &sq_filter.field.as_str())
+        .map(|(_, column)| *column);
+    if let Some(mapped_column) = mapped_column {
+        return add_condition(
+            base_sea_orm_condition,
+            &sq_filter,
+            Expr::col(mapped_column).into(),
+            false,
+            &field_policy.custom_operators,
+        );
+    }
 
-    let result = add_query_condition_to_sea_orm_condition::<E>(
+    // `meta.color` with a `meta.*` entry is the `color` key of the `meta` JSON column.
+    if let Some((json_column, path)) = json_path_column(&sq_filter.field, field_column_map) {
+        let column: SimpleExpr = Expr::col(json_column).into();
+        let dialect = field_policy.json_dialect.unwrap_or_default();
+        return match SearchOperator::try_from(sq_filter.operator) {
+            Ok(SearchOperator::Contains) => {
+                Ok(base_sea_orm_condition
+                    .add(json_contains_expr(column, path, &sq_filter, dialect)?))
+            }
+            Ok(SearchOperator::NotContains) => Ok(base_sea_orm_condition
+                .add(json_contains_expr(column, path, &sq_filter, dialect)?.not())),
+            _ => {
+                let (expr, sq_filter, typed) = json_comparison(column, path, &sq_filter, dialect)?;
+                add_condition(
+                    base_sea_orm_condition,
+                    &sq_filter,
+                    expr,
+                    typed,
+                    &field_policy.custom_operators,
+                )
+            }
+        };
+    }
+
+    let computed_field = field_policy
+        .computed_field(sq_filter.field.as_str())
+        .ok_or_else(|| {
+            InvalidField(format!(
+                "Column \"{}\" not provided in field_to_column_map",
+                sq_filter.field.as_str()
+            ))
+        })?;
+    add_condition(
         base_sea_orm_condition,
         &sq_filter,
-        mapped_column,
+        computed_field.clone(),
+        true,
+        &field_policy.custom_operators,
     )
-    .unwrap();
-    Ok(result)
 }
-
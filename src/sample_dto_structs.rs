@@ -21,6 +21,30 @@ pub struct SearchQuery {
     /// return all results
     #[prost(bool, tag = "8")]
     pub find_all: bool,
+    /// also return soft-deleted rows, if the entity allows it.
+    #[prost(bool, tag = "10")]
+    #[serde(default)]
+    pub include_deleted: bool,
+    /// return only soft-deleted rows, if the entity allows it.
+    #[prost(bool, tag = "11")]
+    #[serde(default)]
+    pub only_deleted: bool,
+    /// return the rows after this cursor, taken from a connection of the previous page with the same sort. `offset` is ignored when it is set.
+    #[prost(string, optional, tag = "12")]
+    #[serde(default)]
+    pub after: ::core::option::Option<::prost::alloc::string::String>,
+    /// the names of the related entities to load along with each row, as registered for the entity.
+    #[prost(string, repeated, tag = "13")]
+    #[serde(default)]
+    pub include: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// return the distinct combinations of these fields instead of the rows.
+    #[prost(string, repeated, tag = "14")]
+    #[serde(default)]
+    pub distinct_on: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// return one row per group of these fields, with the number of rows in the group as `_count` (`pebble_grouping::GROUP_COUNT_FIELD`), instead of the rows.
+    #[prost(string, repeated, tag = "15")]
+    #[serde(default)]
+    pub group_by: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// Find all the relations that are related to the object.
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -50,7 +74,9 @@ pub struct SearchFilter {
 pub struct SearchCondition {
     #[prost(string, tag = "1")]
     pub field: ::prost::alloc::string::String,
+    /// a `SearchOperator`, or the code of a custom operator. It can be left out when `operator_name` is set.
     #[prost(enumeration = "SearchOperator", tag = "2")]
+    #[serde(default)]
     pub operator: i32,
     /// the optional parameter for determining matches for semantic similarity search.
     #[prost(float, optional, tag = "5")]
@@ -64,6 +90,10 @@ pub struct SearchCondition {
     /// a list of string value, to be used with IN, NOT_IN
     #[prost(string, repeated, tag = "4")]
     pub value_list: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// the name of a custom operator of the entity, used instead of `operator` when set.
+    #[prost(string, optional, tag = "7")]
+    #[serde(default)]
+    pub operator_name: ::core::option::Option<::prost::alloc::string::String>,
 }
 /// Metadata about the query and the result returned for the query.
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -93,6 +123,26 @@ pub struct SearchResultMetadata {
     /// describe the reason
     #[prost(string, optional, tag = "10")]
     pub filter_reason: ::core::option::Option<::prost::alloc::string::String>,
+    /// whether the result was served from the query cache instead of the database.
+    #[prost(bool, optional, tag = "11")]
+    #[serde(default)]
+    pub cache_hit: ::core::option::Option<bool>,
+    /// whether `result_total_items` is an exact count, an estimate, or a lower bound.
+    #[prost(enumeration = "SearchCountAccuracy", tag = "12")]
+    #[serde(default)]
+    pub total_items_accuracy: i32,
+    /// whether there are rows after this page, when the page was fetched with an `after` cursor.
+    #[prost(bool, optional, tag = "13")]
+    #[serde(default)]
+    pub has_next_page: ::core::option::Option<bool>,
+    /// the number of rows read from the database to fill the page, when rows were filtered out after the query and the page was backfilled.
+    #[prost(int32, optional, tag = "14")]
+    #[serde(default)]
+    pub scanned_items: ::core::option::Option<i32>,
+    /// the offset to request the next page from, when the page was backfilled. This is `offset` plus `scanned_items`, not plus `length`.
+    #[prost(int32, optional, tag = "15")]
+    #[serde(default)]
+    pub next_offset: ::core::option::Option<i32>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -102,6 +152,14 @@ pub struct SearchSortOption {
     pub field: ::prost::alloc::string::String,
     #[prost(enumeration = "SortDirection", tag = "2")]
     pub order: i32,
+    /// whether rows with a NULL sort value come first or last. Unspecified leaves it to the database.
+    #[prost(enumeration = "SortNulls", tag = "3")]
+    #[serde(default)]
+    pub nulls: i32,
+    /// sort a string field by its lowercased value, so that "apple" comes before "Zebra". Ignored for the other fields.
+    #[prost(bool, tag = "4")]
+    #[serde(default)]
+    pub case_insensitive: bool,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
@@ -234,3 +292,61 @@ impl SortDirection {
         }
     }
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SortNulls {
+    Unspecified = 0,
+    First = 1,
+    Last = 2,
+}
+impl SortNulls {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SortNulls::Unspecified => "SORT_NULLS_UNSPECIFIED",
+            SortNulls::First => "SORT_NULLS_FIRST",
+            SortNulls::Last => "SORT_NULLS_LAST",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SORT_NULLS_UNSPECIFIED" => Some(Self::Unspecified),
+            "SORT_NULLS_FIRST" => Some(Self::First),
+            "SORT_NULLS_LAST" => Some(Self::Last),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SearchCountAccuracy {
+    Unspecified = 0,
+    /// the total is the exact number of matching rows.
+    Exact = 1,
+    /// the total is an estimate from the database statistics.
+    Estimated = 2,
+    /// there are at least as many matching rows as the total.
+    LowerBound = 3,
+}
+impl SearchCountAccuracy {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SearchCountAccuracy::Unspecified => "SEARCH_COUNT_ACCURACY_UNSPECIFIED",
+            SearchCountAccuracy::Exact => "SEARCH_COUNT_ACCURACY_EXACT",
+            SearchCountAccuracy::Estimated => "SEARCH_COUNT_ACCURACY_ESTIMATED",
+            SearchCountAccuracy::LowerBound => "SEARCH_COUNT_ACCURACY_LOWER_BOUND",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SEARCH_COUNT_ACCURACY_UNSPECIFIED" => Some(Self::Unspecified),
+            "SEARCH_COUNT_ACCURACY_EXACT" => Some(Self::Exact),
+            "SEARCH_COUNT_ACCURACY_ESTIMATED" => Some(Self::Estimated),
+            "SEARCH_COUNT_ACCURACY_LOWER_BOUND" => Some(Self::LowerBound),
+            _ => None,
+        }
+    }
+}
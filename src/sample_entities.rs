@@ -0,0 +1,107 @@
+//! Sample SeaOrm entities, `book` and `author`, with their field maps, used by the examples of the documentation and the tests.
+//!
+//! Like the sample DTOs, you can get rid of them and use your own entities.
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+pub mod author {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, serde::Serialize, serde::Deserialize)]
+    #[sea_orm(table_name = "author")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i32,
+        pub name: String,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {
+        #[sea_orm(has_many = "super::book::Entity")]
+        Book,
+    }
+
+    impl Related<super::book::Entity> for Entity {
+        fn to() -> RelationDef {
+            Relation::Book.def()
+        }
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod book {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, serde::Serialize, serde::Deserialize)]
+    #[sea_orm(table_name = "book")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i32,
+        pub title: String,
+        pub publication_year: i32,
+        pub author_id: i32,
+        pub tenant_id: i32,
+        pub price: Option<f64>,
+        pub published_on: Option<Date>,
+        /// Set when the book is soft-deleted.
+        pub deleted_at: Option<DateTime>,
+        /// Free-form metadata, e.g. `{"color": "red", "tags": ["sea"]}`.
+        pub meta: Option<Json>,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {
+        #[sea_orm(
+            belongs_to = "super::author::Entity",
+            from = "Column::AuthorId",
+            to = "super::author::Column::Id"
+        )]
+        Author,
+    }
+
+    impl Related<super::author::Entity> for Entity {
+        fn to() -> RelationDef {
+            Relation::Author.def()
+        }
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+/// The field map of `book`, with the `meta.*` JSON path fields of its `meta` column.
+pub static BOOK_COLUMN_MAP: LazyLock<HashMap<String, (book::Entity, book::Column)>> =
+    LazyLock::new(|| {
+        [
+            ("id", book::Column::Id),
+            ("title", book::Column::Title),
+            ("publication_year", book::Column::PublicationYear),
+            ("author_id", book::Column::AuthorId),
+            ("tenant_id", book::Column::TenantId),
+            ("price", book::Column::Price),
+            ("published_on", book::Column::PublishedOn),
+            ("deleted_at", book::Column::DeletedAt),
+            ("meta.*", book::Column::Meta),
+        ]
+        .into_iter()
+        .map(|(field, column)| (field.to_string(), (book::Entity, column)))
+        .collect()
+    });
+
+/// The field map of `author`.
+pub static AUTHOR_COLUMN_MAP: LazyLock<HashMap<String, (author::Entity, author::Column)>> =
+    LazyLock::new(|| {
+        [("id", author::Column::Id), ("name", author::Column::Name)]
+            .into_iter()
+            .map(|(field, column)| (field.to_string(), (author::Entity, column)))
+            .collect()
+    });
+
+/// The `CREATE TABLE` statements of the sample entities for `backend`, e.g. to set up an in-memory SQLite database.
+pub fn sample_schema_statements(backend: sea_orm::DbBackend) -> Vec<sea_orm::Statement> {
+    let schema = sea_orm::Schema::new(backend);
+    vec![
+        backend.build(&schema.create_table_from_entity(author::Entity)),
+        backend.build(&schema.create_table_from_entity(book::Entity)),
+    ]
+}
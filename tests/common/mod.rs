@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+use chrono::NaiveDate;
+use pebble_query::pebble_query_result::PebbleQueryResult;
+use pebble_query::sample_dto_structs::{
+    SearchCondition, SearchFilter, SearchOperator, SearchQuery, SearchSortOption, SortDirection,
+};
+use pebble_query::sample_entities::{author, book, sample_schema_statements};
+use sea_orm::{ActiveValue, ConnectionTrait, Database, DatabaseConnection, EntityTrait};
+use serde_json::json;
+
+/// An in-memory SQLite database with the sample entities and `BOOKS`.
+pub async fn setup_db() -> DatabaseConnection {
+    let db = Database::connect("sqlite::memory:").await.unwrap();
+    for statement in sample_schema_statements(db.get_database_backend()) {
+        db.execute(statement).await.unwrap();
+    }
+    author::Entity::insert_many(
+        [(1, "Ursula"), (2, "Iain"), (3, "Octavia")].map(|(id, name)| author::ActiveModel {
+            id: ActiveValue::Set(id),
+            name: ActiveValue::Set(name.to_string()),
+        }),
+    )
+    .exec(&db)
+    .await
+    .unwrap();
+    book::Entity::insert_many(books().into_iter().map(book::ActiveModel::from))
+        .exec(&db)
+        .await
+        .unwrap();
+    db
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+/// The sample books: 8 books of 3 authors in 2 tenants, book 5 soft-deleted.
+pub fn books() -> Vec<book::Model> {
+    let book =
+        |id: i32, title: &str, publication_year: i32, author_id: i32, tenant_id: i32| book::Model {
+            id,
+            title: title.to_string(),
+            publication_year,
+            author_id,
+            tenant_id,
+            price: None,
+            published_on: None,
+            deleted_at: None,
+            meta: None,
+        };
+    vec![
+        book::Model {
+            price: Some(9.5),
+            published_on: Some(date(1968, 11, 1)),
+            meta: Some(
+                json!({"color": "blue", "tags": ["sea", "magic"], "zip": "02134", "dims": {"width": 12}}),
+            ),
+            ..book(1, "A Wizard of Earthsea", 1968, 1, 1)
+        },
+        book::Model {
+            price: Some(12.0),
+            published_on: Some(date(1969, 3, 1)),
+            meta: Some(
+                json!({"color": "red", "tags": ["ice"], "zip": "10001", "dims": {"width": 15}}),
+            ),
+            ..book(2, "The Left Hand of Darkness", 1969, 1, 1)
+        },
+        book::Model {
+            published_on: Some(date(1974, 5, 1)),
+            meta: Some(json!({"color": "red", "tags": ["moon"]})),
+            ..book(3, "The Dispossessed", 1974, 1, 2)
+        },
+        book::Model {
+            price: Some(8.0),
+            published_on: Some(date(1987, 4, 23)),
+            ..book(4, "Consider Phlebas", 1987, 2, 1)
+        },
+        book::Model {
+            price: Some(8.5),
+            published_on: Some(date(1988, 1, 1)),
+            deleted_at: Some(date(2020, 1, 1).and_hms_opt(0, 0, 0).unwrap()),
+            meta: Some(json!({"color": "green", "zip": "n/a"})),
+            ..book(5, "The Player of Games", 1988, 2, 2)
+        },
+        book::Model {
+            price: Some(7.0),
+            published_on: Some(date(1979, 6, 1)),
+            meta: Some(json!({"color": "blue", "tags": ["time"]})),
+            ..book(6, "Kindred", 1979, 3, 1)
+        },
+        book(7, "Parable of the Sower", 1993, 3, 2),
+        book::Model {
+            price: Some(6.0),
+            meta: Some(json!({"tags": ["sea"]})),
+            ..book(8, "the sea and summer", 1987, 3, 1)
+        },
+    ]
+}
+
+pub fn condition(field: &str, operator: SearchOperator, value: &str) -> SearchCondition {
+    SearchCondition {
+        field: field.to_string(),
+        operator: operator as i32,
+        value: Some(value.to_string()),
+        ..Default::default()
+    }
+}
+
+/// A query with all the conditions in `must`.
+pub fn must_query(conditions: Vec<SearchCondition>) -> SearchQuery {
+    SearchQuery {
+        filter: Some(SearchFilter {
+            must: conditions,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+pub fn sort(field: &str, order: SortDirection) -> Option<SearchSortOption> {
+    Some(SearchSortOption {
+        field: field.to_string(),
+        order: order as i32,
+        ..Default::default()
+    })
+}
+
+pub fn ids(result: &PebbleQueryResult<book::Entity>) -> Vec<i32> {
+    result.results.iter().map(|book| book.id).collect()
+}
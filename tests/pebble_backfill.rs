@@ -0,0 +1,111 @@
+mod common;
+
+use common::{setup_db, sort};
+use pebble_query::pebble_backfill::{
+    use_pebble_query_backfilled, use_pebble_query_backfilled_with_config,
+};
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::sample_dto_structs::{SearchQuery, SortDirection};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, EntityTrait};
+
+fn query(offset: i32, length: i32) -> SearchQuery {
+    SearchQuery {
+        sort: sort("id", SortDirection::Asc),
+        offset,
+        length,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn pages_are_backfilled_to_the_requested_length() {
+    let db = setup_db().await;
+
+    // only the books of tenant 1 are readable: 1, 2, 4, 6 and 8.
+    let result = use_pebble_query_backfilled(
+        book::Entity::find(),
+        Some(query(0, 3)),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &PebbleQueryOptions::default(),
+        5,
+        |book| book.tenant_id == 1,
+    )
+    .await
+    .unwrap();
+    assert_eq!(common::ids(&result), vec![1, 2, 4]);
+    assert_eq!(result.metadata.result_items, 3);
+    assert_eq!(result.metadata.scanned_items, Some(4));
+    assert_eq!(result.metadata.filter_count, Some(1));
+    assert_eq!(result.metadata.next_offset, Some(4));
+    assert_eq!(result.metadata.has_next_page, Some(true));
+    assert_eq!(result.metadata.result_total_items, 8);
+
+    let result = use_pebble_query_backfilled(
+        book::Entity::find(),
+        Some(query(4, 3)),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &PebbleQueryOptions::default(),
+        5,
+        |book| book.tenant_id == 1,
+    )
+    .await
+    .unwrap();
+    assert_eq!(common::ids(&result), vec![6, 8]);
+    assert_eq!(result.metadata.scanned_items, Some(4));
+    assert_eq!(result.metadata.has_next_page, Some(false));
+}
+
+#[tokio::test]
+async fn max_iterations_bounds_the_scan() {
+    let db = setup_db().await;
+
+    let result = use_pebble_query_backfilled(
+        book::Entity::find(),
+        Some(query(0, 2)),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &PebbleQueryOptions::default(),
+        2,
+        |book| book.id == 7,
+    )
+    .await
+    .unwrap();
+    assert!(result.results.is_empty());
+    assert_eq!(result.metadata.scanned_items, Some(4));
+    assert_eq!(result.metadata.filter_count, Some(4));
+    assert_eq!(result.metadata.next_offset, Some(4));
+    assert_eq!(result.metadata.has_next_page, Some(true));
+}
+
+#[tokio::test]
+async fn the_config_scopes_and_soft_delete_apply_before_the_predicate() {
+    let db = setup_db().await;
+    let config: PebbleQueryConfig<book::Entity, i32> =
+        PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+            .with_scope(|tenant_id: &i32| {
+                Ok(Condition::all().add(book::Column::TenantId.eq(*tenant_id)))
+            })
+            .with_soft_delete((book::Entity, book::Column::DeletedAt));
+
+    // the books of tenant 2 are 3, 5 and 7, and 5 is deleted.
+    let result = use_pebble_query_backfilled_with_config(
+        book::Entity::find(),
+        Some(query(0, 1)),
+        &config,
+        &2,
+        &db,
+        5,
+        |book| book.id != 3,
+    )
+    .await
+    .unwrap();
+    assert_eq!(common::ids(&result), vec![7]);
+    assert_eq!(result.metadata.scanned_items, Some(2));
+    assert_eq!(result.metadata.filter_count, Some(1));
+    assert_eq!(result.metadata.result_total_items, 2);
+    assert_eq!(result.metadata.has_next_page, Some(false));
+}
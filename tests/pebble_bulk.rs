@@ -0,0 +1,137 @@
+mod common;
+
+use common::{condition, setup_db};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_bulk::{
+    pebble_delete_many, pebble_delete_many_with_config, pebble_update_many,
+    pebble_update_many_with_config, PebbleBulkResult,
+};
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::sample_dto_structs::{SearchFilter, SearchOperator};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, EntityTrait, PaginatorTrait, QueryFilter, Set};
+
+struct RequestContext {
+    tenant_id: i32,
+}
+
+fn author_filter(author_id: &str) -> SearchFilter {
+    SearchFilter {
+        must: vec![condition("author_id", SearchOperator::Equals, author_id)],
+        ..Default::default()
+    }
+}
+
+fn repriced() -> book::ActiveModel {
+    book::ActiveModel {
+        price: Set(Some(5.0)),
+        ..Default::default()
+    }
+}
+
+fn config() -> PebbleQueryConfig<book::Entity, RequestContext> {
+    PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+        .with_scope(|ctx: &RequestContext| {
+            Ok(Condition::all().add(book::Column::TenantId.eq(ctx.tenant_id)))
+        })
+        .with_soft_delete((book::Entity, book::Column::DeletedAt))
+}
+
+async fn repriced_ids(db: &sea_orm::DatabaseConnection) -> Vec<i32> {
+    book::Entity::find()
+        .filter(book::Column::Price.eq(5.0))
+        .all(db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|book| book.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn dry_runs_only_count_the_matching_rows() {
+    let db = setup_db().await;
+
+    let result = pebble_update_many(&author_filter("1"), &BOOK_COLUMN_MAP, repriced(), &db, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        PebbleBulkResult {
+            rows_affected: 3,
+            dry_run: true
+        }
+    );
+    assert!(repriced_ids(&db).await.is_empty());
+
+    let result = pebble_delete_many(&author_filter("2"), &BOOK_COLUMN_MAP, &db, true)
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 2);
+    assert_eq!(book::Entity::find().count(&db).await.unwrap(), 8);
+}
+
+#[tokio::test]
+async fn bulk_operations_change_the_matching_rows() {
+    let db = setup_db().await;
+
+    let result = pebble_update_many(
+        &author_filter("1"),
+        &BOOK_COLUMN_MAP,
+        repriced(),
+        &db,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.rows_affected, 3);
+    assert_eq!(repriced_ids(&db).await, vec![1, 2, 3]);
+
+    let result = pebble_delete_many(&author_filter("2"), &BOOK_COLUMN_MAP, &db, false)
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 2);
+    assert_eq!(book::Entity::find().count(&db).await.unwrap(), 6);
+}
+
+#[tokio::test]
+async fn filters_without_conditions_are_refused() {
+    let db = setup_db().await;
+
+    let error = pebble_delete_many(&SearchFilter::default(), &BOOK_COLUMN_MAP, &db, false)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, PebbleQueryError::EmptyFilter(_)));
+    assert_eq!(book::Entity::find().count(&db).await.unwrap(), 8);
+}
+
+#[tokio::test]
+async fn config_variants_apply_the_scopes_and_skip_soft_deleted_rows() {
+    let db = setup_db().await;
+    let ctx = RequestContext { tenant_id: 2 };
+
+    // author 2 has book 4 in tenant 1 and the soft-deleted book 5 in tenant 2.
+    let result = pebble_update_many_with_config(
+        &author_filter("2"),
+        &config(),
+        &ctx,
+        repriced(),
+        &db,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.rows_affected, 0);
+    assert!(repriced_ids(&db).await.is_empty());
+
+    let result = pebble_delete_many_with_config(&author_filter("3"), &config(), &ctx, &db, false)
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 1);
+    assert!(book::Entity::find_by_id(7)
+        .one(&db)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(book::Entity::find().count(&db).await.unwrap(), 7);
+}
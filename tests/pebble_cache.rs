@@ -0,0 +1,162 @@
+mod common;
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use chrono::DateTime;
+use common::{condition, ids, must_query, setup_db};
+use pebble_query::pebble_cache::{
+    use_pebble_query_cached, PebbleCacheKey, PebbleCacheStore, PebbleLruCacheStore,
+    PebbleQueryCache,
+};
+use pebble_query::pebble_query_result::PebbleQueryResult;
+use pebble_query::pebble_relative_time::PebbleClock;
+use pebble_query::sample_dto_structs::SearchOperator;
+use pebble_query::sample_entities::{author, book, BOOK_COLUMN_MAP};
+use sea_orm::{EntityTrait, JoinType, QuerySelect, RelationTrait};
+
+type BookCache = PebbleQueryCache<PebbleLruCacheStore<PebbleQueryResult<book::Entity>>>;
+
+fn book_cache() -> BookCache {
+    PebbleQueryCache::new(
+        PebbleLruCacheStore::new(NonZeroUsize::new(10).unwrap()),
+        Duration::from_secs(60),
+    )
+}
+
+#[tokio::test]
+async fn repeated_query_is_served_from_the_cache() {
+    let db = setup_db().await;
+    let cache = book_cache();
+    let query = must_query(vec![condition("author_id", SearchOperator::Equals, "1")]);
+
+    let result = use_pebble_query_cached(
+        book::Entity::find(),
+        Some(query.clone()),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &cache,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.metadata.cache_hit, Some(false));
+
+    book::Entity::delete_many().exec(&db).await.unwrap();
+    // differently cased field names are the same query.
+    let mut camel_case_query = query;
+    camel_case_query.filter.as_mut().unwrap().must[0].field = "AuthorId".to_string();
+    let result = use_pebble_query_cached(
+        book::Entity::find(),
+        Some(camel_case_query.clone()),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &cache,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.metadata.cache_hit, Some(true));
+    assert_eq!(ids(&result), vec![1, 2, 3]);
+
+    cache.invalidate::<book::Entity, _>().await.unwrap();
+    let result = use_pebble_query_cached(
+        book::Entity::find(),
+        Some(camel_case_query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &cache,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.metadata.cache_hit, Some(false));
+    assert!(result.results.is_empty());
+}
+
+#[test]
+fn key_depends_on_the_field_map() {
+    let query = must_query(vec![condition("name", SearchOperator::Equals, "1")]);
+    let renamed_map: HashMap<String, (book::Entity, book::Column)> =
+        HashMap::from([("name".to_string(), (book::Entity, book::Column::Title))]);
+    let other_map: HashMap<String, (book::Entity, book::Column)> =
+        HashMap::from([("name".to_string(), (book::Entity, book::Column::Meta))]);
+
+    assert_ne!(
+        PebbleCacheKey::new(&book::Entity::find(), &renamed_map, &query),
+        PebbleCacheKey::new(&book::Entity::find(), &other_map, &query)
+    );
+}
+
+#[tokio::test]
+async fn joined_entities_invalidate_the_results() {
+    let cache = book_cache().with_dependency::<author::Entity>();
+    let joined_select =
+        book::Entity::find().join(JoinType::InnerJoin, book::Relation::Author.def());
+    let query = must_query(vec![]);
+    let key = PebbleCacheKey::new(&joined_select, &BOOK_COLUMN_MAP, &query)
+        .with_dependencies(&cache.dependencies);
+    assert_eq!(key.tables, vec!["book".to_string(), "author".to_string()]);
+
+    let unjoined_key = PebbleCacheKey::new(&book::Entity::find(), &BOOK_COLUMN_MAP, &query);
+    let store = &cache.store;
+    let result = || PebbleQueryResult::<book::Entity> {
+        metadata: Default::default(),
+        results: vec![],
+    };
+    store
+        .insert(key.clone(), result(), cache.ttl)
+        .await
+        .unwrap();
+    store
+        .insert(unjoined_key.clone(), result(), cache.ttl)
+        .await
+        .unwrap();
+
+    cache
+        .invalidate::<author::Entity, PebbleQueryResult<book::Entity>>()
+        .await
+        .unwrap();
+
+    assert!(store.get(&key).await.unwrap().is_none());
+    assert!(store.get(&unjoined_key).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn relative_values_are_resolved_before_the_key_is_built() {
+    let db = setup_db().await;
+    let clock = |rfc3339: &str| PebbleClock::fixed(DateTime::parse_from_rfc3339(rfc3339).unwrap());
+    let mut cache = book_cache().with_clock(clock("1969-03-15T12:00:00+00:00"));
+    let query = must_query(vec![
+        condition(
+            "published_on",
+            SearchOperator::GreaterThanOrEquals,
+            "now-30d",
+        ),
+        condition("published_on", SearchOperator::LessThanOrEquals, "now"),
+    ]);
+
+    let result = use_pebble_query_cached(
+        book::Entity::find(),
+        Some(query.clone()),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &cache,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.metadata.cache_hit, Some(false));
+    assert_eq!(ids(&result), vec![2]);
+
+    // "now-30d" stands for another day once the time has moved on.
+    cache.clock = clock("1969-11-20T12:00:00+00:00");
+    let result = use_pebble_query_cached(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &cache,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.metadata.cache_hit, Some(false));
+    assert!(result.results.is_empty());
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::pebble_bulk::pebble_delete_many_with_config;
+use pebble_query::pebble_grouping::use_pebble_query_grouped_with_config;
+use pebble_query::pebble_query::use_pebble_query_with_config;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_schema::query_json_schema_with_config;
+use pebble_query::pebble_typescript::entity_fields_typescript_with_config;
+use pebble_query::sample_dto_structs::{
+    SearchFilter, SearchOperator, SearchQuery, SearchSortOption, SortDirection,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::sea_query::{Alias, Expr, Func};
+use sea_orm::{ColumnTrait, Condition, EntityTrait, PaginatorTrait};
+use serde_json::json;
+
+struct RequestContext {
+    tenant_id: i32,
+}
+
+fn config() -> PebbleQueryConfig<book::Entity, RequestContext> {
+    PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+        .with_scope(|ctx: &RequestContext| {
+            Ok(Condition::all().add(book::Column::TenantId.eq(ctx.tenant_id)))
+        })
+        .with_soft_delete((book::Entity, book::Column::DeletedAt))
+        .with_computed_field(
+            "title_length",
+            Func::cust(Alias::new("LENGTH")).arg(Expr::col((book::Entity, book::Column::Title))),
+        )
+}
+
+#[tokio::test]
+async fn queries_filter_and_sort_by_computed_fields() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        sort: sort("title_length", SortDirection::Asc),
+        ..must_query(vec![condition(
+            "title_length",
+            SearchOperator::GreaterThan,
+            "17",
+        )])
+    };
+    let result = use_pebble_query_with_config(
+        book::Entity::find(),
+        Some(query),
+        &config(),
+        &RequestContext { tenant_id: 1 },
+        &db,
+    )
+    .await
+    .unwrap();
+    // "the sea and summer", "A Wizard of Earthsea", "The Left Hand of Darkness".
+    assert_eq!(ids(&result), vec![8, 1, 2]);
+}
+
+#[tokio::test]
+async fn bulk_operations_filter_by_computed_fields() {
+    let db = setup_db().await;
+    let filter = SearchFilter {
+        must: vec![condition("title_length", SearchOperator::LessThan, "17")],
+        ..Default::default()
+    };
+    // "Consider Phlebas" and "Kindred"; "The Dispossessed" is in tenant 2.
+    let result = pebble_delete_many_with_config(
+        &filter,
+        &config(),
+        &RequestContext { tenant_id: 1 },
+        &db,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.rows_affected, 2);
+    assert_eq!(book::Entity::find().count(&db).await.unwrap(), 6);
+}
+
+#[tokio::test]
+async fn computed_fields_can_be_grouped_by() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        group_by: vec!["title_length".to_string()],
+        sort: sort("title_length", SortDirection::Asc),
+        ..Default::default()
+    };
+    let result = use_pebble_query_grouped_with_config(
+        book::Entity::find(),
+        query,
+        &config(),
+        &RequestContext { tenant_id: 2 },
+        &db,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        result.results,
+        vec![
+            json!({"title_length": 16, "_count": 1}),
+            json!({"title_length": 20, "_count": 1}),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn only_text_computed_fields_are_lowercased() {
+    let db = setup_db().await;
+    let case_insensitive_sort = |field: &str| {
+        sort(field, SortDirection::Asc).map(|sort| SearchSortOption {
+            case_insensitive: true,
+            ..sort
+        })
+    };
+    let query = SearchQuery {
+        sort: case_insensitive_sort("title_length"),
+        ..Default::default()
+    };
+    let ctx = RequestContext { tenant_id: 1 };
+    // sorted as text, "7" would come after "25".
+    let result = use_pebble_query_with_config(
+        book::Entity::find(),
+        Some(query.clone()),
+        &config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap();
+    assert_eq!(ids(&result), vec![6, 4, 8, 1, 2]);
+
+    let grouped_query = SearchQuery {
+        group_by: vec!["title_length".to_string()],
+        ..query
+    };
+    let result = use_pebble_query_grouped_with_config(
+        book::Entity::find(),
+        grouped_query,
+        &config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap();
+    let lengths: Vec<&serde_json::Value> = result
+        .results
+        .iter()
+        .map(|row| &row["title_length"])
+        .collect();
+    assert_eq!(
+        lengths,
+        vec![&json!(7), &json!(16), &json!(18), &json!(20), &json!(25)]
+    );
+
+    let text_config = config()
+        .with_text_computed_field("title_text", Expr::col((book::Entity, book::Column::Title)));
+    let query = SearchQuery {
+        sort: case_insensitive_sort("title_text"),
+        ..Default::default()
+    };
+    let result =
+        use_pebble_query_with_config(book::Entity::find(), Some(query), &text_config, &ctx, &db)
+            .await
+            .unwrap();
+    // "the sea and summer" is lowercase, which sorts after the uppercase titles unless lowercased.
+    assert_eq!(ids(&result), vec![1, 4, 6, 2, 8]);
+}
+
+#[test]
+fn schemas_list_the_computed_fields() {
+    let schema = query_json_schema_with_config(&config());
+    let sort_fields = schema["properties"]["sort"]["properties"]["field"]["enum"]
+        .as_array()
+        .unwrap();
+    assert!(sort_fields.contains(&json!("title_length")));
+    assert!(
+        schema["properties"]["filter"]["properties"]["must"]["items"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|condition| condition["properties"]["field"]["const"] == "title_length")
+    );
+
+    let definitions = entity_fields_typescript_with_config("book", &config());
+    assert!(definitions.contains("\"title\" | \"title_length\""));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{ids, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_connection::{
+    cursor_key_columns, decode_cursor, encode_cursor, pebble_connection,
+};
+use pebble_query::pebble_query::{try_query_to_select, use_pebble_query_with_options};
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::sample_dto_structs::{SearchQuery, SortDirection};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{DbBackend, EntityTrait, IdenStatic, MockDatabase, QueryTrait, Value};
+
+fn connection_options() -> PebbleQueryOptions {
+    PebbleQueryOptions {
+        connection_paging: true,
+        ..Default::default()
+    }
+}
+
+fn sorted_sql(query: &SearchQuery) -> String {
+    try_query_to_select(query, &BOOK_COLUMN_MAP, book::Entity::find())
+        .unwrap()
+        .build(DbBackend::Sqlite)
+        .to_string()
+}
+
+#[test]
+fn cursors_round_trip() {
+    let values = vec![Some("1987".to_string()), None, Some("8".to_string())];
+    assert_eq!(decode_cursor(&encode_cursor(&values)).unwrap(), values);
+
+    let error = decode_cursor("not a cursor!").unwrap_err();
+    assert!(matches!(error, PebbleQueryError::InvalidCursor(_)));
+}
+
+#[test]
+fn cursor_keys_use_the_normalized_sort_field() {
+    let query = SearchQuery {
+        sort: sort("publicationYear", SortDirection::Desc),
+        ..Default::default()
+    };
+    let columns: Vec<(String, bool)> = cursor_key_columns(&query, &BOOK_COLUMN_MAP)
+        .unwrap()
+        .into_iter()
+        .map(|(column, descending)| (column.as_str().to_string(), descending))
+        .collect();
+    assert_eq!(
+        columns,
+        vec![
+            ("publication_year".to_string(), true),
+            ("id".to_string(), false)
+        ]
+    );
+}
+
+#[test]
+fn the_tiebreak_is_only_added_after_a_cursor() {
+    let query = SearchQuery {
+        sort: sort("publication_year", SortDirection::Asc),
+        ..Default::default()
+    };
+    assert!(sorted_sql(&query).ends_with(r#"ORDER BY "book"."publication_year" ASC"#));
+
+    let query = SearchQuery {
+        after: Some(encode_cursor(&[
+            Some("1987".to_string()),
+            Some("4".to_string()),
+        ])),
+        ..query
+    };
+    assert!(
+        sorted_sql(&query).contains(r#"ORDER BY "book"."publication_year" ASC, "book"."id" ASC"#)
+    );
+}
+
+#[tokio::test]
+async fn connection_paging_adds_the_tiebreak_to_the_first_page() {
+    let db = MockDatabase::new(DbBackend::Sqlite)
+        .append_query_results([common::books()[..2].to_vec()])
+        .append_query_results([[BTreeMap::from([("num_items", Value::Int(Some(8)))])]])
+        .into_connection();
+    let query = SearchQuery {
+        sort: sort("publication_year", SortDirection::Asc),
+        length: 2,
+        ..Default::default()
+    };
+    use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &connection_options(),
+    )
+    .await
+    .unwrap();
+
+    let data_query = format!("{:?}", db.into_transaction_log()[0]);
+    assert!(
+        data_query.contains(r#"ORDER BY \"book\".\"publication_year\" ASC, \"book\".\"id\" ASC"#)
+    );
+}
+
+#[tokio::test]
+async fn cursors_page_through_ties_without_skipping_rows() {
+    let db = setup_db().await;
+    let mut query = SearchQuery {
+        sort: sort("publicationYear", SortDirection::Desc),
+        length: 3,
+        ..Default::default()
+    };
+
+    let mut pages = vec![];
+    loop {
+        let result = use_pebble_query_with_options(
+            book::Entity::find(),
+            Some(query.clone()),
+            &BOOK_COLUMN_MAP,
+            &db,
+            &connection_options(),
+        )
+        .await
+        .unwrap();
+        pages.push(ids(&result));
+        let connection = pebble_connection(result, &BOOK_COLUMN_MAP).unwrap();
+        assert_eq!(connection.total_count, 8);
+        if !connection.page_info.has_next_page {
+            break;
+        }
+        query.after = connection.page_info.end_cursor;
+    }
+    // books 4 and 8 are both from 1987.
+    assert_eq!(pages, vec![vec![7, 5, 4], vec![8, 6, 3], vec![2, 1]]);
+}
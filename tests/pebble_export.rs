@@ -0,0 +1,126 @@
+mod common;
+
+use common::books;
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_export::{
+    csv_columns_from_row, write_csv, write_ndjson, PebbleMetadataPlacement,
+};
+use pebble_query::sample_dto_structs::SearchResultMetadata;
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BookRow {
+    id: i32,
+    publication_year: i32,
+    price: Option<f64>,
+}
+
+fn rows() -> Vec<BookRow> {
+    books()
+        .into_iter()
+        .take(3)
+        .map(|book| BookRow {
+            id: book.id,
+            publication_year: book.publication_year,
+            price: book.price,
+        })
+        .collect()
+}
+
+fn columns(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn csv_rows_are_written_under_the_header() {
+    let mut output = vec![];
+    write_csv(
+        &mut output,
+        &books()[..2],
+        &columns(&["id", "title", "meta"]),
+        None,
+        PebbleMetadataPlacement::Omit,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "id,title,meta\n\
+         1,A Wizard of Earthsea,\"{\"\"color\"\":\"\"blue\"\",\"\"dims\"\":{\"\"width\"\":12},\"\"tags\"\":[\"\"sea\"\",\"\"magic\"\"],\"\"zip\"\":\"\"02134\"\"}\"\n\
+         2,The Left Hand of Darkness,\"{\"\"color\"\":\"\"red\"\",\"\"dims\"\":{\"\"width\"\":15},\"\"tags\"\":[\"\"ice\"\"],\"\"zip\"\":\"\"10001\"\"}\"\n"
+    );
+}
+
+#[test]
+fn columns_can_be_derived_from_renamed_fields() {
+    let rows = rows();
+    let columns = csv_columns_from_row(&rows[0]).unwrap();
+    assert_eq!(columns, vec!["id", "price", "publicationYear"]);
+
+    let mut output = vec![];
+    write_csv(
+        &mut output,
+        &rows,
+        &columns,
+        None,
+        PebbleMetadataPlacement::Omit,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "id,price,publicationYear\n1,9.5,1968\n2,12.0,1969\n3,,1974\n"
+    );
+}
+
+#[test]
+fn columns_missing_from_the_rows_are_refused() {
+    let mut output = vec![];
+    let error = write_csv(
+        &mut output,
+        rows(),
+        &columns(&["id", "publication_year"]),
+        None,
+        PebbleMetadataPlacement::Omit,
+    )
+    .unwrap_err();
+    assert!(matches!(error, PebbleQueryError::ExportError(_)));
+    assert!(csv_columns_from_row(&1).is_err());
+}
+
+#[test]
+fn metadata_is_written_where_it_is_placed() {
+    let metadata = SearchResultMetadata {
+        result_total_items: 3,
+        ..Default::default()
+    };
+
+    let mut output = vec![];
+    write_csv(
+        &mut output,
+        rows(),
+        &columns(&["id"]),
+        Some(&metadata),
+        PebbleMetadataPlacement::Header,
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("# {"));
+    assert!(output.ends_with("id\n1\n2\n3\n"));
+
+    let mut output = vec![];
+    write_ndjson(
+        &mut output,
+        rows(),
+        Some(&metadata),
+        PebbleMetadataPlacement::Trailer,
+    )
+    .unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0]["publicationYear"], 1968);
+    assert_eq!(lines[3]["metadata"]["result_total_items"], 3);
+}
@@ -0,0 +1,267 @@
+#![cfg(feature = "async-graphql")]
+
+mod common;
+
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use common::setup_db;
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_connection::encode_cursor;
+use pebble_query::pebble_graphql::{
+    pebble_graphql_query, pebble_graphql_query_with_config, GraphqlSearchCondition,
+    GraphqlSearchFilter, GraphqlSearchOperator, GraphqlSearchQuery, GraphqlSearchSortOption,
+    GraphqlSortDirection, PebbleGraphqlConnection,
+};
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::sample_dto_structs::{SearchOperator, SearchQuery, SortDirection};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::sea_query::{Alias, Expr, Func};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait};
+use serde_json::json;
+
+#[derive(SimpleObject)]
+struct Book {
+    id: i32,
+    title: String,
+}
+
+impl From<book::Model> for Book {
+    fn from(book: book::Model) -> Self {
+        Book {
+            id: book.id,
+            title: book.title,
+        }
+    }
+}
+
+struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn books(
+        &self,
+        ctx: &Context<'_>,
+        query: Option<GraphqlSearchQuery>,
+    ) -> async_graphql::Result<PebbleGraphqlConnection<Book>> {
+        let db = ctx.data::<DatabaseConnection>()?;
+        pebble_graphql_query(
+            book::Entity::find(),
+            query,
+            &BOOK_COLUMN_MAP,
+            db,
+            Book::from,
+        )
+        .await
+    }
+
+    /// The books of tenant 2, with a computed `title_length` field and a `starts_with` operator.
+    async fn tenant_books(
+        &self,
+        ctx: &Context<'_>,
+        query: Option<GraphqlSearchQuery>,
+    ) -> async_graphql::Result<PebbleGraphqlConnection<Book>> {
+        let db = ctx.data::<DatabaseConnection>()?;
+        let config: PebbleQueryConfig<book::Entity, i32> =
+            PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+                .with_scope(|tenant_id: &i32| {
+                    Ok(Condition::all().add(book::Column::TenantId.eq(*tenant_id)))
+                })
+                .with_computed_field(
+                    "title_length",
+                    Func::cust(Alias::new("LENGTH"))
+                        .arg(Expr::col((book::Entity, book::Column::Title))),
+                )
+                .with_custom_operator(1000, "starts_with", |expr, condition| {
+                    let prefix = condition.value.as_deref().unwrap_or_default();
+                    Ok(Expr::expr(expr).like(format!("{}%", prefix)))
+                });
+        pebble_graphql_query_with_config(book::Entity::find(), query, &config, &2, db, Book::from)
+            .await
+    }
+}
+
+/// The cursor of a book sorted by `publication_year`.
+fn cursor(publication_year: i32, id: i32) -> String {
+    encode_cursor(&[Some(publication_year.to_string()), Some(id.to_string())])
+}
+
+async fn execute(request: &str) -> async_graphql::Response {
+    let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(setup_db().await)
+        .finish();
+    schema.execute(request).await
+}
+
+#[test]
+fn inputs_convert_to_the_search_query() {
+    let input = GraphqlSearchQuery {
+        sort: Some(GraphqlSearchSortOption {
+            field: "title".to_string(),
+            order: Some(GraphqlSortDirection::Desc),
+            nulls: None,
+            case_insensitive: None,
+        }),
+        length: 2,
+        filter: Some(GraphqlSearchFilter {
+            must: vec![GraphqlSearchCondition {
+                field: "author_id".to_string(),
+                operator: Some(GraphqlSearchOperator::In),
+                operator_name: None,
+                threshold: None,
+                value: None,
+                value_to: None,
+                value_list: vec!["1".to_string(), "2".to_string()],
+            }],
+            any: vec![],
+        }),
+        ..Default::default()
+    };
+
+    let query = SearchQuery::from(input);
+    let sort = query.sort.unwrap();
+    assert_eq!(sort.order, SortDirection::Desc as i32);
+    assert_eq!(query.length, 2);
+    let condition = &query.filter.unwrap().must[0];
+    assert_eq!(condition.operator, SearchOperator::In as i32);
+    assert_eq!(condition.value_list, vec!["1", "2"]);
+}
+
+#[tokio::test]
+async fn the_resolver_returns_a_connection() {
+    let response = execute(
+        r#"{
+            books(query: {
+                length: 2,
+                sort: { field: "publication_year", order: ASC },
+                filter: { must: [{ field: "author_id", operator: EQUALS, value: "1" }] }
+            }) {
+                totalCount
+                pageInfo { hasNextPage hasPreviousPage }
+                edges { cursor node { id title } }
+            }
+        }"#,
+    )
+    .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data.into_json().unwrap(),
+        json!({
+            "books": {
+                "totalCount": 3,
+                "pageInfo": { "hasNextPage": true, "hasPreviousPage": false },
+                "edges": [
+                    { "cursor": cursor(1968, 1), "node": { "id": 1, "title": "A Wizard of Earthsea" } },
+                    { "cursor": cursor(1969, 2), "node": { "id": 2, "title": "The Left Hand of Darkness" } },
+                ]
+            }
+        })
+    );
+}
+
+#[tokio::test]
+async fn the_end_cursor_is_sent_back_as_after() {
+    let response = execute(&format!(
+        r#"{{
+            books(query: {{
+                length: 2,
+                sort: {{ field: "publication_year", order: ASC }},
+                filter: {{ must: [{{ field: "author_id", operator: EQUALS, value: "1" }}] }},
+                after: "{}"
+            }}) {{
+                pageInfo {{ hasNextPage hasPreviousPage endCursor }}
+                edges {{ node {{ id }} }}
+            }}
+        }}"#,
+        cursor(1969, 2)
+    ))
+    .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data.into_json().unwrap(),
+        json!({
+            "books": {
+                "pageInfo": {
+                    "hasNextPage": false,
+                    "hasPreviousPage": true,
+                    "endCursor": cursor(1974, 3)
+                },
+                "edges": [{ "node": { "id": 3 } }]
+            }
+        })
+    );
+}
+
+#[tokio::test]
+async fn invalid_queries_are_bad_user_input() {
+    let response = execute(
+        r#"{ books(query: { filter: { must: [{ field: "isbn", operator: EQUALS, value: "1" }] } }) { totalCount } }"#,
+    )
+    .await;
+    assert_eq!(response.errors.len(), 1);
+    let code = response.errors[0]
+        .extensions
+        .as_ref()
+        .and_then(|extensions| extensions.get("code"))
+        .cloned();
+    assert_eq!(code, Some(async_graphql::Value::from("BAD_USER_INPUT")));
+}
+
+#[test]
+fn errors_carry_their_code() {
+    use async_graphql::ErrorExtensions;
+
+    let cases = [
+        (PebbleQueryError::ScopeError("".into()), "FORBIDDEN"),
+        (PebbleQueryError::UnknownEntity("".into()), "NOT_FOUND"),
+        (
+            PebbleQueryError::SerializationError("".into()),
+            "INTERNAL_SERVER_ERROR",
+        ),
+        (DbErr::Custom("".into()).into(), "INTERNAL_SERVER_ERROR"),
+    ];
+    for (err, code) in cases {
+        let extensions = err.extend().extensions.unwrap();
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from(code))
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_config_resolver_applies_the_scopes_and_the_field_policy() {
+    let response = execute(
+        r#"{
+            tenantBooks(query: {
+                sort: { field: "title_length", order: DESC },
+                filter: { must: [{ field: "title_length", operator: LESS_THAN, value: "20" }] }
+            }) {
+                edges { node { id } }
+            }
+        }"#,
+    )
+    .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data.into_json().unwrap(),
+        json!({ "tenantBooks": { "edges": [{ "node": { "id": 5 } }, { "node": { "id": 3 } }] } })
+    );
+}
+
+#[tokio::test]
+async fn custom_operators_are_named_in_operator_name() {
+    let response = execute(
+        r#"{
+            tenantBooks(query: {
+                filter: { must: [{ field: "title", operatorName: "starts_with", value: "The" }] }
+            }) {
+                edges { node { id } }
+            }
+        }"#,
+    )
+    .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data.into_json().unwrap(),
+        json!({ "tenantBooks": { "edges": [{ "node": { "id": 3 } }, { "node": { "id": 5 } }] } })
+    );
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use common::{condition, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_grouping::{
+    use_pebble_query_grouped, use_pebble_query_grouped_with_config, GROUP_COUNT_FIELD,
+};
+use pebble_query::pebble_query::{query_to_select, try_query_to_select};
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_query_limits::PebbleQueryLimits;
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::sample_dto_structs::{SearchOperator, SearchQuery, SortDirection};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, EntityTrait};
+use serde_json::{json, Value};
+
+struct RequestContext {
+    tenant_id: i32,
+}
+
+fn config() -> PebbleQueryConfig<book::Entity, RequestContext> {
+    PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+        .with_scope(|ctx: &RequestContext| {
+            Ok(Condition::all().add(book::Column::TenantId.eq(ctx.tenant_id)))
+        })
+        .with_soft_delete((book::Entity, book::Column::DeletedAt))
+}
+
+fn books_per_author() -> SearchQuery {
+    SearchQuery {
+        group_by: vec!["author_id".to_string()],
+        sort: sort(GROUP_COUNT_FIELD, SortDirection::Desc),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn groups_are_counted_and_sorted_by_their_size() {
+    let db = setup_db().await;
+    let result = use_pebble_query_grouped(
+        book::Entity::find(),
+        books_per_author(),
+        &BOOK_COLUMN_MAP,
+        &db,
+    )
+    .await
+    .unwrap();
+    // authors 1 and 3 both have 3 books, so only the sizes are in a fixed order.
+    let counts: Vec<&Value> = result.results.iter().map(|row| &row["_count"]).collect();
+    assert_eq!(counts, vec![&json!(3), &json!(3), &json!(2)]);
+    assert_eq!(result.results[2], json!({"author_id": 2, "_count": 2}));
+    assert_eq!(result.metadata.result_total_items, 3);
+}
+
+#[tokio::test]
+async fn distinct_rows_are_filtered_and_paginated() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        distinct_on: vec!["publication_year".to_string()],
+        sort: sort("publication_year", SortDirection::Asc),
+        length: 2,
+        ..must_query(vec![condition(
+            "publication_year",
+            SearchOperator::GreaterThan,
+            "1970",
+        )])
+    };
+    let result = use_pebble_query_grouped(book::Entity::find(), query, &BOOK_COLUMN_MAP, &db)
+        .await
+        .unwrap();
+    let years: Vec<&Value> = result
+        .results
+        .iter()
+        .map(|row| &row["publication_year"])
+        .collect();
+    assert_eq!(years, vec![&json!(1974), &json!(1979)]);
+    // 1974, 1979, 1987, 1988 and 1993.
+    assert_eq!(result.metadata.result_total_items, 5);
+}
+
+#[tokio::test]
+async fn the_group_size_is_not_a_field() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        group_by: vec![GROUP_COUNT_FIELD.to_string()],
+        ..Default::default()
+    };
+    let error = use_pebble_query_grouped(book::Entity::find(), query, &BOOK_COLUMN_MAP, &db)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, PebbleQueryError::InvalidGrouping(_)));
+}
+
+#[test]
+fn try_query_to_select_refuses_grouping() {
+    let error = try_query_to_select(&books_per_author(), &BOOK_COLUMN_MAP, book::Entity::find())
+        .err()
+        .unwrap();
+    assert!(matches!(error, PebbleQueryError::InvalidGrouping(_)));
+}
+
+#[test]
+#[should_panic(expected = "Invalid grouping: distinct_on and group_by queries return values")]
+fn query_to_select_panics_on_grouping() {
+    query_to_select(&books_per_author(), &BOOK_COLUMN_MAP, book::Entity::find());
+}
+
+#[tokio::test]
+async fn the_config_variant_applies_scopes_soft_delete_and_limits() {
+    let db = setup_db().await;
+    let ctx = RequestContext { tenant_id: 2 };
+    // tenant 2 has books 3, 5 and 7, and book 5 is soft-deleted.
+    let result = use_pebble_query_grouped_with_config(
+        book::Entity::find(),
+        books_per_author(),
+        &config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap();
+    let mut rows = result.results;
+    rows.sort_by_key(|row| row["author_id"].as_i64());
+    assert_eq!(
+        rows,
+        vec![
+            json!({"author_id": 1, "_count": 1}),
+            json!({"author_id": 3, "_count": 1}),
+        ]
+    );
+
+    let config = config().with_options(PebbleQueryOptions {
+        limits: PebbleQueryLimits {
+            max_page_length: Some(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let query = SearchQuery {
+        length: 1000,
+        ..books_per_author()
+    };
+    let error =
+        use_pebble_query_grouped_with_config(book::Entity::find(), query, &config, &ctx, &db)
+            .await
+            .err()
+            .unwrap();
+    assert!(matches!(error, PebbleQueryError::PageLengthTooLarge(_)));
+}
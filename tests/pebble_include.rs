@@ -0,0 +1,141 @@
+mod common;
+
+use common::{condition, must_query, setup_db};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_include::{
+    use_pebble_query_with_includes, use_pebble_query_with_includes_with_config, PebbleIncludes,
+};
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::sample_dto_structs::{SearchOperator, SearchQuery};
+use pebble_query::sample_entities::{author, book, AUTHOR_COLUMN_MAP, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, DbBackend, EntityTrait, MockDatabase};
+use serde_json::json;
+
+#[tokio::test]
+async fn belongs_to_relations_are_included_as_objects() {
+    let db = setup_db().await;
+    let includes: PebbleIncludes<book::Entity, DatabaseConnection> =
+        PebbleIncludes::new().with_include::<author::Entity>("author");
+    let query = SearchQuery {
+        include: vec!["author".to_string()],
+        ..must_query(vec![condition("tenant_id", SearchOperator::Equals, "2")])
+    };
+
+    let result = use_pebble_query_with_includes(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &includes,
+        &db,
+    )
+    .await
+    .unwrap();
+    let authors: Vec<(i32, &serde_json::Value)> = result
+        .results
+        .iter()
+        .map(|row| (row.row.id, &row.included["author"]))
+        .collect();
+    assert_eq!(
+        authors,
+        vec![
+            (3, &json!({ "id": 1, "name": "Ursula" })),
+            (5, &json!({ "id": 2, "name": "Iain" })),
+            (7, &json!({ "id": 3, "name": "Octavia" })),
+        ]
+    );
+    assert_eq!(result.metadata.result_total_items, 3);
+}
+
+#[tokio::test]
+async fn has_many_relations_are_included_as_arrays() {
+    let db = setup_db().await;
+    let includes: PebbleIncludes<author::Entity, DatabaseConnection> =
+        PebbleIncludes::new().with_include::<book::Entity>("books");
+    let query = SearchQuery {
+        include: vec!["books".to_string()],
+        ..must_query(vec![condition("name", SearchOperator::Equals, "Iain")])
+    };
+
+    let result = use_pebble_query_with_includes(
+        author::Entity::find(),
+        Some(query),
+        &AUTHOR_COLUMN_MAP,
+        &includes,
+        &db,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.results.len(), 1);
+    let titles: Vec<&str> = result.results[0].included["books"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|book| book["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, vec!["Consider Phlebas", "The Player of Games"]);
+}
+
+#[tokio::test]
+async fn configured_relations_keep_to_their_scopes_and_soft_delete() {
+    let db = setup_db().await;
+    let book_config: PebbleQueryConfig<book::Entity, i32> =
+        PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+            .with_scope(|tenant_id: &i32| {
+                Ok(Condition::all().add(book::Column::TenantId.eq(*tenant_id)))
+            })
+            .with_soft_delete((book::Entity, book::Column::DeletedAt));
+    let includes: PebbleIncludes<author::Entity, DatabaseConnection, i32> =
+        PebbleIncludes::new().with_include_with_config::<book::Entity>("books", book_config);
+    let author_config: PebbleQueryConfig<author::Entity, i32> =
+        PebbleQueryConfig::new(AUTHOR_COLUMN_MAP.clone());
+    let query = SearchQuery {
+        include: vec!["books".to_string()],
+        ..must_query(vec![condition("name", SearchOperator::Equals, "Iain")])
+    };
+
+    // "Consider Phlebas" is a book of tenant 1, and "The Player of Games" a deleted book of tenant 2.
+    for (tenant_id, titles) in [(1, vec![json!("Consider Phlebas")]), (2, vec![])] {
+        let result = use_pebble_query_with_includes_with_config(
+            author::Entity::find(),
+            Some(query.clone()),
+            &author_config,
+            &includes,
+            &tenant_id,
+            &db,
+        )
+        .await
+        .unwrap();
+        let included_titles: Vec<serde_json::Value> = result.results[0].included["books"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|book| book["title"].clone())
+            .collect();
+        assert_eq!(included_titles, titles);
+    }
+}
+
+#[tokio::test]
+async fn unregistered_includes_are_refused_before_querying() {
+    // the mock database has no results, so any query would fail.
+    let db = MockDatabase::new(DbBackend::Sqlite).into_connection();
+    let includes: PebbleIncludes<book::Entity, _> =
+        PebbleIncludes::new().with_include::<author::Entity>("author");
+    let query = SearchQuery {
+        include: vec!["publisher".to_string()],
+        ..Default::default()
+    };
+
+    let error = use_pebble_query_with_includes(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &includes,
+        &db,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert!(matches!(error, PebbleQueryError::InvalidInclude(_)));
+    assert!(db.into_transaction_log().is_empty());
+}
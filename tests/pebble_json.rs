@@ -0,0 +1,200 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::pebble_backfill::use_pebble_query_backfilled;
+use pebble_query::pebble_bulk::pebble_delete_many;
+use pebble_query::pebble_cache::PebbleCacheKey;
+use pebble_query::pebble_export::csv_columns_from_field_map;
+use pebble_query::pebble_json::PebbleJsonDialect;
+use pebble_query::pebble_query::{try_query_to_select_with_policy, use_pebble_query};
+use pebble_query::pebble_query_config::PebbleFieldPolicy;
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::pebble_schema::query_json_schema;
+use pebble_query::pebble_typescript::entity_fields_typescript;
+use pebble_query::pebble_utils::{normalize_query, normalize_query_with_map};
+use pebble_query::sample_dto_structs::{
+    SearchCondition, SearchFilter, SearchOperator, SearchQuery, SortDirection,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{
+    DatabaseConnection, DbBackend, EntityTrait, MockDatabase, MockExecResult, QueryTrait, Value,
+};
+use serde_json::json;
+
+async fn query_ids(db: &DatabaseConnection, conditions: Vec<SearchCondition>) -> Vec<i32> {
+    let query = SearchQuery {
+        sort: sort("id", SortDirection::Asc),
+        ..must_query(conditions)
+    };
+    let result = use_pebble_query(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, db)
+        .await
+        .unwrap();
+    ids(&result)
+}
+
+#[tokio::test]
+async fn quoted_values_are_compared_as_strings() {
+    let db = setup_db().await;
+    assert_eq!(
+        query_ids(
+            &db,
+            vec![condition("meta.zip", SearchOperator::Equals, "\"02134\"")]
+        )
+        .await,
+        vec![1]
+    );
+    // the zip codes are strings, which are never equal to the number 2134.
+    assert_eq!(
+        query_ids(
+            &db,
+            vec![condition("meta.zip", SearchOperator::Equals, "02134")]
+        )
+        .await,
+        Vec::<i32>::new()
+    );
+}
+
+#[tokio::test]
+async fn numbers_are_only_compared_with_numbers() {
+    let db = setup_db().await;
+    assert_eq!(
+        query_ids(
+            &db,
+            vec![condition(
+                "meta.dims.width",
+                SearchOperator::GreaterThan,
+                "12"
+            )]
+        )
+        .await,
+        vec![2]
+    );
+    // "02134", "10001" and "n/a" are strings.
+    assert_eq!(
+        query_ids(
+            &db,
+            vec![condition("meta.zip", SearchOperator::GreaterThan, "0")]
+        )
+        .await,
+        Vec::<i32>::new()
+    );
+    assert_eq!(
+        query_ids(
+            &db,
+            vec![condition("meta.tags", SearchOperator::Contains, "sea")]
+        )
+        .await,
+        vec![1, 8]
+    );
+}
+
+#[test]
+fn postgres_casts_the_numeric_json_values_only() {
+    let field_policy = PebbleFieldPolicy {
+        json_dialect: Some(PebbleJsonDialect::Postgres),
+        ..Default::default()
+    };
+    let query = must_query(vec![condition(
+        "meta.dims.width",
+        SearchOperator::GreaterThan,
+        "12",
+    )]);
+    let sql = try_query_to_select_with_policy(
+        &query,
+        &BOOK_COLUMN_MAP,
+        &field_policy,
+        book::Entity::find(),
+    )
+    .unwrap()
+    .build(DbBackend::Postgres)
+    .to_string();
+    assert!(sql.ends_with(
+        r#"WHERE (CASE WHEN (jsonb_typeof("book"."meta" #> CAST(E'{\"dims\",\"width\"}' AS text[])) = 'number') THEN CAST(("book"."meta" #>> CAST(E'{\"dims\",\"width\"}' AS text[])) AS numeric) END) > 12"#
+    ));
+}
+
+#[tokio::test]
+async fn the_dialect_is_the_one_of_the_database() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 2,
+        }])
+        .into_connection();
+    let filter = SearchFilter {
+        must: vec![condition("meta.color", SearchOperator::Equals, "red")],
+        ..Default::default()
+    };
+    pebble_delete_many::<_, book::Entity>(&filter, &BOOK_COLUMN_MAP, &db, false)
+        .await
+        .unwrap();
+    let delete = format!("{:?}", db.into_transaction_log()[0]);
+    assert!(delete.contains(r#"\"book\".\"meta\" #>> CAST($1 AS text[])"#));
+}
+
+#[tokio::test]
+async fn backfilled_queries_use_the_dialect_of_the_database() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+        .append_query_results([Vec::<book::Model>::new()])
+        .append_query_results([[BTreeMap::from([("num_items", Value::BigInt(Some(0)))])]])
+        .into_connection();
+    let query = must_query(vec![condition("meta.color", SearchOperator::Equals, "red")]);
+    use_pebble_query_backfilled(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &PebbleQueryOptions::default(),
+        1,
+        |_| true,
+    )
+    .await
+    .unwrap();
+    let select = format!("{:?}", db.into_transaction_log()[0]);
+    assert!(select.contains(r#"\"book\".\"meta\" #>> CAST($1 AS text[])"#));
+}
+
+#[test]
+fn only_the_paths_of_json_path_fields_keep_their_case() {
+    let query = must_query(vec![
+        condition("Meta.zipCode", SearchOperator::Equals, "\"02134\""),
+        condition("author.firstName", SearchOperator::Equals, "Ursula"),
+    ]);
+    let normalized = normalize_query_with_map(&query, &BOOK_COLUMN_MAP);
+    let conditions = &normalized.filter.unwrap().must;
+    assert_eq!(conditions[0].field, "meta.zipCode");
+    assert_eq!(
+        conditions[1].field,
+        normalize_query(&query).filter.unwrap().must[1].field
+    );
+
+    let key = |field: &str| {
+        let query = must_query(vec![condition(field, SearchOperator::Equals, "1")]);
+        PebbleCacheKey::new(&book::Entity::find(), &BOOK_COLUMN_MAP, &query)
+    };
+    assert_eq!(key("Meta.zipCode"), key("meta.zipCode"));
+    assert_ne!(key("meta.zipCode"), key("meta.zip_code"));
+}
+
+#[test]
+fn json_path_fields_are_described_by_their_prefix() {
+    let schema = query_json_schema(&BOOK_COLUMN_MAP);
+    let conditions = schema["properties"]["filter"]["properties"]["must"]["items"]["oneOf"]
+        .as_array()
+        .unwrap();
+    assert!(conditions
+        .iter()
+        .any(|condition| condition["properties"]["field"]["pattern"] == json!(r"^meta\..+")));
+    let sort_fields = schema["properties"]["sort"]["properties"]["field"]["enum"]
+        .as_array()
+        .unwrap();
+    assert!(!sort_fields.contains(&json!("meta.*")));
+
+    let definitions = entity_fields_typescript("book", &BOOK_COLUMN_MAP);
+    assert!(definitions.contains("| `meta.${string}`;"));
+    assert!(!definitions.contains("meta.*"));
+
+    assert!(!csv_columns_from_field_map(&BOOK_COLUMN_MAP).contains(&"meta.*".to_string()));
+}
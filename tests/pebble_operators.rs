@@ -0,0 +1,183 @@
+mod common;
+
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_bulk::pebble_delete_many_with_config;
+use pebble_query::pebble_grouping::use_pebble_query_grouped_with_config;
+use pebble_query::pebble_operators::PebbleOperatorRegistry;
+use pebble_query::pebble_query::use_pebble_query_with_config;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_schema::query_json_schema_with_config;
+use pebble_query::sample_dto_structs::{
+    SearchCondition, SearchFilter, SearchOperator, SearchQuery, SortDirection,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{DatabaseConnection, EntityTrait, PaginatorTrait};
+use serde_json::json;
+
+const WITHIN: i32 = 1000;
+
+/// `price WITHIN 1 OF 8` is `price BETWEEN 7 AND 9`.
+fn within(expr: SimpleExpr, condition: &SearchCondition) -> Result<SimpleExpr, PebbleQueryError> {
+    let parse = |value: Option<&str>| {
+        value
+            .unwrap_or_default()
+            .parse::<f64>()
+            .map_err(|_| PebbleQueryError::InvalidValue("within takes two numbers".to_string()))
+    };
+    let value = parse(condition.value.as_deref())?;
+    let distance = parse(condition.value_to.as_deref())?;
+    Ok(Expr::expr(expr).between(value - distance, value + distance))
+}
+
+fn config() -> PebbleQueryConfig<book::Entity> {
+    PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone()).with_custom_operator(WITHIN, "within", within)
+}
+
+fn within_condition() -> SearchCondition {
+    SearchCondition {
+        field: "price".to_string(),
+        operator: WITHIN,
+        value: Some("8".to_string()),
+        value_to: Some("1".to_string()),
+        ..Default::default()
+    }
+}
+
+async fn query_ids(
+    db: &DatabaseConnection,
+    conditions: Vec<SearchCondition>,
+) -> Result<Vec<i32>, PebbleQueryError> {
+    let query = SearchQuery {
+        sort: sort("id", SortDirection::Asc),
+        ..must_query(conditions)
+    };
+    use_pebble_query_with_config(book::Entity::find(), Some(query), &config(), &(), db)
+        .await
+        .map(|result| ids(&result))
+}
+
+#[tokio::test]
+async fn custom_operators_are_found_by_code_and_by_name() {
+    let db = setup_db().await;
+    // 8.0, 8.5 and 7.0.
+    assert_eq!(
+        query_ids(&db, vec![within_condition()]).await.unwrap(),
+        vec![4, 5, 6]
+    );
+
+    let named = SearchCondition {
+        operator: SearchOperator::Unspecified as i32,
+        operator_name: Some("within".to_string()),
+        ..within_condition()
+    };
+    assert_eq!(query_ids(&db, vec![named]).await.unwrap(), vec![4, 5, 6]);
+
+    let unknown = SearchCondition {
+        operator_name: Some("near".to_string()),
+        ..within_condition()
+    };
+    let error = query_ids(&db, vec![unknown]).await.unwrap_err();
+    assert!(matches!(error, PebbleQueryError::InvalidOperator(_)));
+}
+
+#[tokio::test]
+async fn unspecified_and_unsupported_operators_are_refused() {
+    let db = setup_db().await;
+    for operator in [SearchOperator::Unspecified, SearchOperator::StartsWith] {
+        let error = query_ids(&db, vec![condition("title", operator, "The")])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, PebbleQueryError::InvalidOperator(_)),
+            "{:?}",
+            operator
+        );
+    }
+    assert!(
+        query_ids(&db, vec![condition("price", SearchOperator::Equals, "8")])
+            .await
+            .is_ok()
+    );
+}
+
+#[test]
+fn built_in_codes_cannot_be_registered() {
+    let error = PebbleOperatorRegistry::new()
+        .try_with_operator(SearchOperator::Equals as i32, "equals", within)
+        .err()
+        .unwrap();
+    assert!(matches!(error, PebbleQueryError::InvalidOperator(_)));
+    assert!(PebbleOperatorRegistry::new()
+        .try_with_operator(0, "unspecified", within)
+        .is_err());
+
+    let registry = PebbleOperatorRegistry::new()
+        .try_with_operator(WITHIN, "within", within)
+        .unwrap();
+    assert_eq!(registry.code("within"), Some(WITHIN));
+}
+
+#[test]
+#[should_panic(expected = "SEARCH_OPERATOR_EQUALS")]
+fn with_operator_panics_on_built_in_codes() {
+    PebbleOperatorRegistry::new().with_operator(SearchOperator::Equals as i32, "equals", within);
+}
+
+#[tokio::test]
+async fn bulk_operations_and_grouping_use_the_custom_operators() {
+    let db = setup_db().await;
+    let filter = SearchFilter {
+        must: vec![within_condition()],
+        ..Default::default()
+    };
+    let result = pebble_delete_many_with_config(&filter, &config(), &(), &db, true)
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 3);
+
+    let query = SearchQuery {
+        group_by: vec!["author_id".to_string()],
+        sort: sort("author_id", SortDirection::Asc),
+        ..must_query(vec![within_condition()])
+    };
+    let result =
+        use_pebble_query_grouped_with_config(book::Entity::find(), query, &config(), &(), &db)
+            .await
+            .unwrap();
+    assert_eq!(
+        result.results,
+        vec![
+            json!({"author_id": 2, "_count": 2}),
+            json!({"author_id": 3, "_count": 1}),
+        ]
+    );
+    assert_eq!(book::Entity::find().count(&db).await.unwrap(), 8);
+}
+
+#[test]
+fn schemas_list_the_custom_operators() {
+    let schema = query_json_schema_with_config(&config());
+    let price = schema["properties"]["filter"]["properties"]["must"]["items"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|condition| condition["properties"]["field"]["const"] == "price")
+        .unwrap();
+    assert!(price["properties"]["operator"]["enum"]
+        .as_array()
+        .unwrap()
+        .contains(&json!(WITHIN)));
+    assert_eq!(
+        price["properties"]["operator_name"]["enum"],
+        json!(["within"])
+    );
+}
+
+#[test]
+fn conditions_without_an_operator_name_deserialize() {
+    let condition: SearchCondition =
+        serde_json::from_str(r#"{"field": "price", "operator": 1000, "value_list": []}"#).unwrap();
+    assert_eq!(condition.operator_name, None);
+}
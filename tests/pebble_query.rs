@@ -0,0 +1,94 @@
+mod common;
+
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query::{try_use_pebble_query, use_pebble_query, RunQueryExt};
+use pebble_query::sample_dto_structs::{SearchOperator, SearchQuery, SortDirection};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter};
+
+#[tokio::test]
+async fn filters_sorts_and_paginates() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        sort: sort("title", SortDirection::Asc),
+        length: 2,
+        offset: 1,
+        ..must_query(vec![condition(
+            "publication_year",
+            SearchOperator::GreaterThan,
+            "1970",
+        )])
+    };
+
+    let result = use_pebble_query(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, &db)
+        .await
+        .unwrap();
+
+    // Consider Phlebas, Kindred, Parable of the Sower, The Dispossessed, The Player of Games, the sea and summer
+    assert_eq!(ids(&result), vec![6, 7]);
+    assert_eq!(result.metadata.result_total_items, 6);
+    assert_eq!(result.metadata.result_total_pages, 3);
+}
+
+#[tokio::test]
+async fn no_query_returns_all_rows_of_the_initial_select() {
+    let db = setup_db().await;
+    let select = book::Entity::find().filter(book::Column::AuthorId.eq(1));
+
+    let result = use_pebble_query(select, None, &BOOK_COLUMN_MAP, &db)
+        .await
+        .unwrap();
+
+    assert_eq!(ids(&result), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn use_pebble_query_returns_db_err() {
+    let db = setup_db().await;
+    let query = must_query(vec![condition("isbn", SearchOperator::Equals, "1")]);
+
+    let err = use_pebble_query(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, &db)
+        .await
+        .err().unwrap();
+
+    assert!(matches!(err, DbErr::Custom(message) if message.contains("isbn")));
+}
+
+#[tokio::test]
+async fn try_use_pebble_query_returns_pebble_query_error() {
+    let db = setup_db().await;
+    let query = must_query(vec![condition("isbn", SearchOperator::Equals, "1")]);
+
+    let err = try_use_pebble_query(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, &db)
+        .await
+        .err().unwrap();
+
+    assert!(matches!(err, PebbleQueryError::InvalidField(_)));
+}
+
+#[tokio::test]
+async fn fluent_syntax() {
+    let db = setup_db().await;
+    let query = must_query(vec![condition("title", SearchOperator::Contains, "sea")]);
+
+    let result = book::Entity::find()
+        .pebble_query(Some(query.clone()), &BOOK_COLUMN_MAP, &db)
+        .await
+        .unwrap();
+    assert_eq!(ids(&result), vec![1, 8]);
+
+    let err = book::Entity::find()
+        .try_pebble_query(
+            Some(must_query(vec![condition(
+                "isbn",
+                SearchOperator::Equals,
+                "1",
+            )])),
+            &BOOK_COLUMN_MAP,
+            &db,
+        )
+        .await
+        .err().unwrap();
+    assert!(matches!(err, PebbleQueryError::InvalidField(_)));
+}
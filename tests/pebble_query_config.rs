@@ -0,0 +1,159 @@
+mod common;
+
+use common::{condition, ids, must_query, setup_db};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query::use_pebble_query_with_config;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::sample_dto_structs::{SearchFilter, SearchOperator, SearchQuery};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, EntityTrait};
+
+struct RequestContext {
+    tenant_id: Option<i32>,
+}
+
+fn tenant_config() -> PebbleQueryConfig<book::Entity, RequestContext> {
+    PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone()).with_scope(|ctx: &RequestContext| {
+        let tenant_id = ctx
+            .tenant_id
+            .ok_or_else(|| PebbleQueryError::ScopeError("no tenant".to_string()))?;
+        Ok(Condition::all().add(book::Column::TenantId.eq(tenant_id)))
+    })
+}
+
+#[tokio::test]
+async fn scopes_restrict_every_query() {
+    let db = setup_db().await;
+    let ctx = RequestContext { tenant_id: Some(2) };
+
+    let result =
+        use_pebble_query_with_config(book::Entity::find(), None, &tenant_config(), &ctx, &db)
+            .await
+            .unwrap();
+    assert_eq!(ids(&result), vec![3, 5, 7]);
+
+    let query = must_query(vec![condition("author_id", SearchOperator::Equals, "1")]);
+    let result = use_pebble_query_with_config(
+        book::Entity::find(),
+        Some(query),
+        &tenant_config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap();
+    assert_eq!(ids(&result), vec![3]);
+    assert_eq!(result.metadata.result_total_items, 1);
+}
+
+#[tokio::test]
+async fn any_conditions_cannot_bypass_the_scopes() {
+    let db = setup_db().await;
+    let ctx = RequestContext { tenant_id: Some(2) };
+    let query = SearchQuery {
+        filter: Some(SearchFilter {
+            any: vec![
+                condition("tenant_id", SearchOperator::Equals, "1"),
+                condition("id", SearchOperator::Equals, "7"),
+            ],
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let result = use_pebble_query_with_config(
+        book::Entity::find(),
+        Some(query),
+        &tenant_config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(ids(&result), vec![7]);
+}
+
+#[tokio::test]
+async fn scope_errors_are_returned() {
+    let db = setup_db().await;
+    let ctx = RequestContext { tenant_id: None };
+
+    let result =
+        use_pebble_query_with_config(book::Entity::find(), None, &tenant_config(), &ctx, &db).await;
+
+    assert!(matches!(result, Err(PebbleQueryError::ScopeError(_))));
+}
+
+#[test]
+fn scope_condition_ands_all_the_scopes() {
+    let config = tenant_config()
+        .with_scope(|_: &RequestContext| Ok(Condition::all().add(book::Column::AuthorId.ne(3))));
+    let ctx = RequestContext { tenant_id: Some(1) };
+
+    let condition = config.scope_condition(&ctx).unwrap();
+
+    assert_eq!(condition.len(), 2);
+}
+
+fn soft_delete_config(allow_deleted: bool) -> PebbleQueryConfig<book::Entity> {
+    let mut config = PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+        .with_soft_delete((book::Entity, book::Column::DeletedAt));
+    config.field_policy.allow_deleted = allow_deleted;
+    config
+}
+
+#[tokio::test]
+async fn soft_deleted_rows_are_filtered_out() {
+    let db = setup_db().await;
+    let query = |include_deleted: bool, only_deleted: bool| SearchQuery {
+        include_deleted,
+        only_deleted,
+        ..must_query(vec![condition("author_id", SearchOperator::Equals, "2")])
+    };
+
+    for (include_deleted, only_deleted, expected_ids) in [
+        (false, false, vec![4]),
+        (true, false, vec![4, 5]),
+        (false, true, vec![5]),
+        (true, true, vec![5]),
+    ] {
+        let result = use_pebble_query_with_config(
+            book::Entity::find(),
+            Some(query(include_deleted, only_deleted)),
+            &soft_delete_config(true),
+            &(),
+            &db,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids(&result), expected_ids);
+        assert_eq!(
+            result.metadata.result_total_items,
+            expected_ids.len() as i32
+        );
+    }
+}
+
+#[tokio::test]
+async fn deleted_rows_need_the_field_policy() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        include_deleted: true,
+        ..Default::default()
+    };
+
+    let result = use_pebble_query_with_config(
+        book::Entity::find(),
+        Some(query),
+        &soft_delete_config(false),
+        &(),
+        &db,
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(PebbleQueryError::DeletedRowsNotAllowed(_))
+    ));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{condition, must_query};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query::use_pebble_query_with_options;
+use pebble_query::pebble_query_limits::PebbleQueryLimits;
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::sample_dto_structs::{SearchCondition, SearchOperator, SearchQuery};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{DbBackend, EntityTrait, MockDatabase};
+
+fn limits() -> PebbleQueryLimits {
+    PebbleQueryLimits {
+        max_conditions: Some(2),
+        max_in_list_size: Some(3),
+        max_page_length: Some(50),
+        max_offset: Some(100),
+        forbid_leading_wildcard_like: true,
+    }
+}
+
+fn in_condition(values: &[&str]) -> SearchCondition {
+    SearchCondition {
+        field: "id".to_string(),
+        operator: SearchOperator::In as i32,
+        value_list: values.iter().map(|value| value.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn queries_within_the_limits_are_valid() {
+    let query = SearchQuery {
+        length: 50,
+        offset: 100,
+        ..must_query(vec![
+            in_condition(&["1", "2", "3"]),
+            condition("title", SearchOperator::Like, "sea%"),
+        ])
+    };
+
+    assert!(limits().validate(&query).is_ok());
+    assert!(PebbleQueryLimits::default()
+        .validate(&SearchQuery::default())
+        .is_ok());
+}
+
+#[test]
+fn queries_exceeding_a_limit_are_rejected() {
+    let page = |query: SearchQuery| SearchQuery {
+        length: 10,
+        ..query
+    };
+    let cases = [
+        (
+            SearchQuery {
+                length: 51,
+                ..Default::default()
+            },
+            "PageLengthTooLarge",
+        ),
+        (SearchQuery::default(), "PageLengthTooLarge"),
+        (
+            page(SearchQuery {
+                offset: 101,
+                ..Default::default()
+            }),
+            "OffsetTooLarge",
+        ),
+        (
+            page(must_query(vec![
+                condition("id", SearchOperator::Equals, "1"),
+                condition("id", SearchOperator::Equals, "2"),
+                condition("id", SearchOperator::Equals, "3"),
+            ])),
+            "TooManyConditions",
+        ),
+        (
+            page(must_query(vec![in_condition(&["1", "2", "3", "4"])])),
+            "InListTooLarge",
+        ),
+        (
+            page(must_query(vec![condition(
+                "title",
+                SearchOperator::Contains,
+                "sea",
+            )])),
+            "LeadingWildcardLike",
+        ),
+        (
+            page(must_query(vec![condition(
+                "title",
+                SearchOperator::Like,
+                "%sea",
+            )])),
+            "LeadingWildcardLike",
+        ),
+    ];
+
+    for (query, variant) in cases {
+        let err = limits().validate(&query).unwrap_err();
+        assert!(format!("{:?}", err).starts_with(variant), "{:?}", err);
+    }
+}
+
+#[test]
+fn find_one_needs_no_length() {
+    let query = SearchQuery {
+        find_one: true,
+        ..Default::default()
+    };
+
+    assert!(limits().validate(&query).is_ok());
+}
+
+#[tokio::test]
+async fn queries_exceeding_a_limit_run_no_sql() {
+    let db = MockDatabase::new(DbBackend::Postgres).into_connection();
+    let options = PebbleQueryOptions {
+        limits: limits(),
+        ..Default::default()
+    };
+    let query = SearchQuery {
+        length: 1000,
+        ..Default::default()
+    };
+
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &options,
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(PebbleQueryError::PageLengthTooLarge(_))
+    ));
+    assert!(db.into_transaction_log().is_empty());
+}
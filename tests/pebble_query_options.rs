@@ -0,0 +1,221 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query::use_pebble_query_with_options;
+use pebble_query::pebble_query_options::{
+    PebbleCountStrategy, PebbleExecutionMode, PebbleQueryOptions,
+};
+use pebble_query::sample_dto_structs::{
+    SearchCountAccuracy, SearchOperator, SearchQuery, SortDirection,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{DbBackend, DbErr, EntityTrait, MockDatabase, Value};
+use serde_json::json;
+
+const EXECUTION_MODES: [PebbleExecutionMode; 3] = [
+    PebbleExecutionMode::Sequential,
+    PebbleExecutionMode::Concurrent,
+    PebbleExecutionMode::WindowCount,
+];
+
+#[tokio::test]
+async fn execution_modes_return_the_same_page_and_counts() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        sort: sort("id", SortDirection::Desc),
+        length: 3,
+        offset: 3,
+        ..must_query(vec![condition("tenant_id", SearchOperator::Equals, "1")])
+    };
+
+    for execution_mode in EXECUTION_MODES {
+        let options = PebbleQueryOptions {
+            execution_mode,
+            ..Default::default()
+        };
+        let result = use_pebble_query_with_options(
+            book::Entity::find(),
+            Some(query.clone()),
+            &BOOK_COLUMN_MAP,
+            &db,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(ids(&result), vec![2, 1], "{:?}", execution_mode);
+        assert_eq!(
+            result.metadata.result_total_items, 5,
+            "{:?}",
+            execution_mode
+        );
+        assert_eq!(
+            result.metadata.result_total_pages, 2,
+            "{:?}",
+            execution_mode
+        );
+    }
+}
+
+#[tokio::test]
+async fn window_count_past_the_last_page_counts_separately() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        length: 5,
+        offset: 20,
+        ..Default::default()
+    };
+    let options = PebbleQueryOptions {
+        execution_mode: PebbleExecutionMode::WindowCount,
+        ..Default::default()
+    };
+
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &options,
+    )
+    .await
+    .unwrap();
+
+    assert!(result.results.is_empty());
+    assert_eq!(result.metadata.result_total_items, 8);
+}
+
+#[tokio::test]
+async fn window_count_with_find_one() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        find_one: true,
+        sort: sort("title", SortDirection::Asc),
+        ..Default::default()
+    };
+    let options = PebbleQueryOptions {
+        execution_mode: PebbleExecutionMode::WindowCount,
+        ..Default::default()
+    };
+
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &options,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(ids(&result), vec![1]);
+    assert_eq!(result.metadata.result_total_items, 8);
+}
+
+fn count_strategy_options(count_strategy: PebbleCountStrategy) -> PebbleQueryOptions {
+    PebbleQueryOptions {
+        count_strategy,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn estimated_count_uses_the_postgres_plan() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+        .append_query_results([common::books()[..2].to_vec()])
+        .append_query_results([[BTreeMap::from([(
+            "QUERY PLAN",
+            Value::Json(Some(Box::new(json!([{ "Plan": { "Plan Rows": 1234.0 } }])))),
+        )])]])
+        .into_connection();
+    let query = SearchQuery {
+        length: 2,
+        ..must_query(vec![condition("tenant_id", SearchOperator::Equals, "1")])
+    };
+
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &count_strategy_options(PebbleCountStrategy::Estimated),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(result.metadata.result_total_items, 1234);
+    assert_eq!(result.metadata.result_total_pages, 617);
+    assert_eq!(
+        result.metadata.total_items_accuracy,
+        SearchCountAccuracy::Estimated as i32
+    );
+    let explain = format!("{:?}", db.into_transaction_log()[1]);
+    assert!(explain.contains(r#"EXPLAIN (FORMAT JSON) SELECT \"book\".\"id\""#));
+    assert!(explain.contains(r#"WHERE \"book\".\"tenant_id\" = $1"#));
+}
+
+#[tokio::test]
+async fn failed_explain_is_an_error() {
+    let db = MockDatabase::new(DbBackend::Postgres)
+        .append_query_results([common::books()[..2].to_vec()])
+        .append_query_errors([DbErr::Custom("current transaction is aborted".to_string())])
+        .into_connection();
+
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        None,
+        &BOOK_COLUMN_MAP,
+        &db,
+        &count_strategy_options(PebbleCountStrategy::Estimated),
+    )
+    .await;
+
+    assert!(matches!(result, Err(PebbleQueryError::SeaOrmDbError(_))));
+}
+
+#[tokio::test]
+async fn estimated_count_is_exact_on_sqlite() {
+    let db = setup_db().await;
+    let query = must_query(vec![condition("tenant_id", SearchOperator::Equals, "1")]);
+
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &count_strategy_options(PebbleCountStrategy::Estimated),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(result.metadata.result_total_items, 5);
+    assert_eq!(
+        result.metadata.total_items_accuracy,
+        SearchCountAccuracy::Exact as i32
+    );
+}
+
+#[tokio::test]
+async fn capped_count_is_a_lower_bound_once_the_cap_is_reached() {
+    let db = setup_db().await;
+
+    for (cap, total_items, accuracy) in [
+        (3, 3, SearchCountAccuracy::LowerBound),
+        (100, 8, SearchCountAccuracy::Exact),
+    ] {
+        let result = use_pebble_query_with_options(
+            book::Entity::find(),
+            None,
+            &BOOK_COLUMN_MAP,
+            &db,
+            &count_strategy_options(PebbleCountStrategy::Capped(cap)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.metadata.result_total_items, total_items);
+        assert_eq!(result.metadata.total_items_accuracy, accuracy as i32);
+    }
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::books;
+use pebble_query::pebble_query_result::{
+    PebbleQueryResult, PebbleQueryResultGeneric, PebbleQueryResultGenericUtilTraits,
+    PebbleQueryResultUtilTrait,
+};
+use pebble_query::sample_dto_structs::SearchResultMetadata;
+use pebble_query::sample_entities::book;
+
+fn sample_result() -> PebbleQueryResultGeneric<book::Model> {
+    let results = books();
+    PebbleQueryResultGeneric {
+        metadata: SearchResultMetadata {
+            result_items: results.len() as i32,
+            result_total_items: results.len() as i32,
+            ..Default::default()
+        },
+        results,
+    }
+}
+
+#[test]
+fn closures_can_capture_context() {
+    let tenant_id = 2;
+    let mut seen = 0;
+    let result = sample_result().map_filter_result_with(
+        |book| {
+            seen += 1;
+            (book.tenant_id == tenant_id).then_some(book.id)
+        },
+        Some("other tenant".to_string()),
+    );
+    assert_eq!(seen, 8);
+    assert_eq!(result.results, vec![3, 5, 7]);
+    assert_eq!(result.metadata.result_items, 3);
+    assert_eq!(result.metadata.filter_count, Some(5));
+    assert_eq!(
+        result.metadata.filter_reason.as_deref(),
+        Some("other tenant")
+    );
+}
+
+#[test]
+fn chained_filters_add_up() {
+    let result = sample_result()
+        .map_filter_result_with(
+            |book| (book.author_id != 1).then_some(book),
+            Some("author 1".to_string()),
+        )
+        .map_filter_result_with(|book| book.price, Some("no price".to_string()));
+    assert_eq!(result.results, vec![8.0, 8.5, 7.0, 6.0]);
+    assert_eq!(result.metadata.filter_count, Some(4));
+    assert_eq!(
+        result.metadata.filter_reason.as_deref(),
+        Some("author 1; no price")
+    );
+}
+
+#[test]
+fn map_filter_result_replaces_the_filter_metadata() {
+    let result = sample_result()
+        .map_filter_result(
+            |book| (book.author_id != 1).then_some(book),
+            Some("author 1".to_string()),
+        )
+        .map_filter_result(|book| book.price, None);
+    assert_eq!(result.results, vec![8.0, 8.5, 7.0, 6.0]);
+    assert_eq!(result.metadata.filter_count, Some(1));
+    assert_eq!(result.metadata.filter_reason, None);
+
+    let filtered = PebbleQueryResult::<book::Entity> {
+        metadata: SearchResultMetadata {
+            filter_count: Some(3),
+            filter_reason: Some("author 1".to_string()),
+            ..Default::default()
+        },
+        results: books(),
+    };
+    let result = filtered.map_into_generic(|book| book.meta.map(|_| book.id), None);
+    assert_eq!(result.results, vec![1, 2, 3, 5, 6, 8]);
+    assert_eq!(result.metadata.result_items, 6);
+    assert_eq!(result.metadata.filter_count, Some(2));
+    assert_eq!(result.metadata.filter_reason, None);
+}
+
+#[test]
+fn fallible_maps_return_the_first_error() {
+    let error = sample_result()
+        .try_map(|book| match book.price {
+            Some(price) => Ok(price),
+            None => Err(book.id),
+        })
+        .unwrap_err();
+    assert_eq!(error, 3);
+
+    let result = sample_result()
+        .try_filter_map(
+            |book| Ok::<_, String>(book.meta.is_some().then_some(book.id)),
+            Some("no meta".to_string()),
+        )
+        .unwrap();
+    assert_eq!(result.results, vec![1, 2, 3, 5, 6, 8]);
+    assert_eq!(result.metadata.filter_count, Some(2));
+}
+
+#[tokio::test]
+async fn async_maps_keep_the_order_and_the_counts() {
+    let result = sample_result()
+        .map_async(|book| async move { book.title })
+        .await;
+    assert_eq!(result.results.len(), 8);
+    assert_eq!(result.results[0], "A Wizard of Earthsea");
+    assert_eq!(result.metadata.filter_count, None);
+
+    let result = sample_result()
+        .filter_map_async(
+            |book| async move { (book.publication_year < 1975).then_some(book.id) },
+            Some("too recent".to_string()),
+        )
+        .await;
+    assert_eq!(result.results, vec![1, 2, 3]);
+    assert_eq!(result.metadata.result_items, 3);
+    assert_eq!(result.metadata.filter_count, Some(5));
+}
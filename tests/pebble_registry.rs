@@ -0,0 +1,65 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{condition, must_query, setup_db};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_registry::PebbleRegistry;
+use pebble_query::sample_dto_structs::SearchOperator;
+use pebble_query::sample_entities::{author, book, AUTHOR_COLUMN_MAP, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
+
+struct RequestContext {
+    tenant_id: i32,
+}
+
+fn registry() -> PebbleRegistry<DatabaseConnection, RequestContext> {
+    let book_config = Arc::new(PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone()).with_scope(
+        |ctx: &RequestContext| Ok(Condition::all().add(book::Column::TenantId.eq(ctx.tenant_id))),
+    ));
+    let author_config = Arc::new(PebbleQueryConfig::new(AUTHOR_COLUMN_MAP.clone()));
+
+    let mut registry = PebbleRegistry::new();
+    registry
+        .register("books", book_config, book::Entity::find)
+        .register("authors", author_config, || {
+            author::Entity::find().filter(author::Column::Id.ne(2))
+        });
+    registry
+}
+
+#[tokio::test]
+async fn entities_are_queried_by_name_as_json() {
+    let db = setup_db().await;
+    let registry = registry();
+    let ctx = RequestContext { tenant_id: 2 };
+    assert_eq!(registry.names(), vec!["authors", "books"]);
+
+    let query = must_query(vec![condition("author_id", SearchOperator::Equals, "3")]);
+    let result = registry
+        .query("books", Some(query), &ctx, &db)
+        .await
+        .unwrap();
+    assert_eq!(result.metadata.result_total_items, 1);
+    assert_eq!(result.results[0]["title"], "Parable of the Sower");
+
+    let result = registry.query("authors", None, &ctx, &db).await.unwrap();
+    let names: Vec<&str> = result
+        .results
+        .iter()
+        .map(|author| author["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["Ursula", "Octavia"]);
+}
+
+#[tokio::test]
+async fn unknown_entities_are_refused() {
+    let db = setup_db().await;
+    let error = registry()
+        .query("publishers", None, &RequestContext { tenant_id: 1 }, &db)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, PebbleQueryError::UnknownEntity(_)));
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use chrono::{DateTime, FixedOffset};
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_bulk::pebble_delete_many_with_config;
+use pebble_query::pebble_grouping::use_pebble_query_grouped_with_config;
+use pebble_query::pebble_query::use_pebble_query_with_options;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::pebble_relative_time::{
+    resolve_relative_time, resolve_relative_times, PebbleClock,
+};
+use pebble_query::sample_dto_structs::{
+    SearchCondition, SearchFilter, SearchOperator, SearchQuery, SortDirection,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::EntityTrait;
+use serde_json::json;
+
+fn time(rfc3339: &str) -> DateTime<FixedOffset> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap()
+}
+
+/// 1969-03-15, two weeks after "The Left Hand of Darkness" was published.
+fn now() -> DateTime<FixedOffset> {
+    time("1969-03-15T12:00:00+00:00")
+}
+
+fn clock_options() -> PebbleQueryOptions {
+    PebbleQueryOptions {
+        clock: PebbleClock::fixed(now()),
+        ..Default::default()
+    }
+}
+
+fn published_between(from: &str, to: &str) -> SearchCondition {
+    SearchCondition {
+        field: "published_on".to_string(),
+        operator: SearchOperator::Between as i32,
+        value: Some(from.to_string()),
+        value_to: Some(to.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn expressions_resolve_against_the_clock() {
+    assert_eq!(
+        resolve_relative_time("now-7d", now()).unwrap(),
+        Some(time("1969-03-08T12:00:00+00:00"))
+    );
+    assert_eq!(
+        resolve_relative_time("start_of_month", now()).unwrap(),
+        Some(time("1969-03-01T00:00:00+00:00"))
+    );
+    assert_eq!(
+        resolve_relative_time("end_of_month", now()).unwrap(),
+        Some(time("1969-03-31T23:59:59.999999+00:00"))
+    );
+    assert_eq!(
+        resolve_relative_time("start_of_month-1M+2d", now()).unwrap(),
+        Some(time("1969-02-03T00:00:00+00:00"))
+    );
+    assert_eq!(resolve_relative_time("1969-03-01", now()).unwrap(), None);
+}
+
+#[test]
+fn out_of_range_offsets_are_invalid_values() {
+    for value in [
+        "now+4000000000d",
+        "now+400000000y",
+        "now-999999999w",
+        "now+99999999999s",
+        "now+7x",
+    ] {
+        let error = resolve_relative_time(value, now()).unwrap_err();
+        assert!(
+            matches!(error, PebbleQueryError::InvalidValue(_)),
+            "{}",
+            value
+        );
+    }
+}
+
+#[test]
+fn only_date_and_time_columns_are_resolved() {
+    let query = must_query(vec![
+        published_between("start_of_month", "end_of_month"),
+        condition("title", SearchOperator::Equals, "now"),
+    ]);
+    let resolved =
+        resolve_relative_times(&query, &BOOK_COLUMN_MAP, &PebbleClock::fixed(now())).unwrap();
+    let conditions = &resolved.filter.unwrap().must;
+    assert_eq!(conditions[0].value.as_deref(), Some("1969-03-01"));
+    assert_eq!(conditions[0].value_to.as_deref(), Some("1969-03-31"));
+    assert_eq!(conditions[1].value.as_deref(), Some("now"));
+}
+
+#[tokio::test]
+async fn naive_date_times_are_compared_in_utc() {
+    let db = setup_db().await;
+    // 23:30 UTC on the eve of the day "The Player of Games" was deleted, at 00:00 UTC.
+    let clock = PebbleClock::fixed(time("2020-01-01T01:30:00+02:00"));
+
+    let query = must_query(vec![
+        condition(
+            "deleted_at",
+            SearchOperator::GreaterThanOrEquals,
+            "start_of_day",
+        ),
+        condition("deleted_at", SearchOperator::LessThan, "now+1h"),
+    ]);
+    let resolved = resolve_relative_times(&query, &BOOK_COLUMN_MAP, &clock).unwrap();
+    let conditions = &resolved.filter.unwrap().must;
+    // the midnight of the clock's zone.
+    assert_eq!(conditions[0].value.as_deref(), Some("2019-12-31 22:00:00"));
+    assert_eq!(conditions[1].value.as_deref(), Some("2020-01-01 00:30:00"));
+
+    let options = PebbleQueryOptions {
+        clock,
+        ..Default::default()
+    };
+    for (value, expected_ids) in [("now", vec![5]), ("now+1h", vec![])] {
+        let result = use_pebble_query_with_options(
+            book::Entity::find(),
+            Some(must_query(vec![condition(
+                "deleted_at",
+                SearchOperator::GreaterThan,
+                value,
+            )])),
+            &BOOK_COLUMN_MAP,
+            &db,
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids(&result), expected_ids, "deleted_at > {}", value);
+    }
+}
+
+#[tokio::test]
+async fn queries_run_resolved_and_echo_the_unresolved_query() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        sort: sort("id", SortDirection::Asc),
+        ..must_query(vec![published_between("start_of_month", "end_of_month")])
+    };
+    let result = use_pebble_query_with_options(
+        book::Entity::find(),
+        Some(query),
+        &BOOK_COLUMN_MAP,
+        &db,
+        &clock_options(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ids(&result), vec![2]);
+
+    let echoed = result.metadata.query.unwrap().filter.unwrap().must[0].clone();
+    assert_eq!(echoed.value.as_deref(), Some("start_of_month"));
+    assert_eq!(echoed.value_to.as_deref(), Some("end_of_month"));
+}
+
+#[tokio::test]
+async fn bulk_operations_and_grouping_use_the_clock_of_the_config() {
+    let db = setup_db().await;
+    let config: PebbleQueryConfig<book::Entity> =
+        PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone()).with_options(clock_options());
+    let before_this_month = condition("published_on", SearchOperator::LessThan, "start_of_month");
+
+    // only "A Wizard of Earthsea" was published before March 1969.
+    let filter = SearchFilter {
+        must: vec![before_this_month.clone()],
+        ..Default::default()
+    };
+    let result = pebble_delete_many_with_config(&filter, &config, &(), &db, true)
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 1);
+
+    let query = SearchQuery {
+        group_by: vec!["author_id".to_string()],
+        ..must_query(vec![before_this_month])
+    };
+    let result =
+        use_pebble_query_grouped_with_config(book::Entity::find(), query, &config, &(), &db)
+            .await
+            .unwrap();
+    assert_eq!(result.results, vec![json!({"author_id": 1, "_count": 1})]);
+}
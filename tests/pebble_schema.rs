@@ -0,0 +1,106 @@
+use std::collections::BTreeSet;
+
+use pebble_query::pebble_schema::{query_json_schema, query_openapi_components};
+use pebble_query::sample_dto_structs::{
+    SearchCondition, SearchFilter, SearchOperator, SearchQuery, SearchRelation, SearchSortOption,
+};
+use pebble_query::sample_entities::{AUTHOR_COLUMN_MAP, BOOK_COLUMN_MAP};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// The keys of a DTO as serialized by serde.
+fn serde_keys<T: Serialize>(dto: &T) -> BTreeSet<String> {
+    serde_json::to_value(dto)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect()
+}
+
+fn property_keys(schema: &Value) -> BTreeSet<String> {
+    schema["properties"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// The schema of the conditions on `field`.
+fn condition_schema<'a>(schema: &'a Value, field: &str) -> &'a Value {
+    schema["properties"]["filter"]["properties"]["must"]["items"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|condition| condition["properties"]["field"]["const"] == field)
+        .unwrap()
+}
+
+#[test]
+fn schema_properties_match_the_dtos() {
+    let schema = query_json_schema(&AUTHOR_COLUMN_MAP);
+    let properties = &schema["properties"];
+
+    assert_eq!(property_keys(&schema), serde_keys(&SearchQuery::default()));
+    assert_eq!(
+        property_keys(&properties["relation"]),
+        serde_keys(&SearchRelation::default())
+    );
+    assert_eq!(
+        property_keys(&properties["sort"]),
+        serde_keys(&SearchSortOption::default())
+    );
+    assert_eq!(
+        property_keys(&properties["filter"]),
+        serde_keys(&SearchFilter::default())
+    );
+    assert_eq!(
+        property_keys(condition_schema(&schema, "name")),
+        serde_keys(&SearchCondition::default())
+    );
+}
+
+#[test]
+fn condition_operators_and_value_types_follow_the_column_type() {
+    let schema = query_json_schema(&BOOK_COLUMN_MAP);
+
+    let title = condition_schema(&schema, "title");
+    assert_eq!(
+        title["properties"]["value"]["x-pebble-value-type"],
+        "string"
+    );
+    let title_operators = title["properties"]["operator"]["enum"].as_array().unwrap();
+    assert!(title_operators.contains(&(SearchOperator::Contains as i32).into()));
+    assert!(!title_operators.contains(&(SearchOperator::IsNull as i32).into()));
+
+    let published_on = condition_schema(&schema, "published_on");
+    assert_eq!(published_on["properties"]["value"]["format"], "date");
+    let published_on_operators = published_on["properties"]["operator"]["enum"]
+        .as_array()
+        .unwrap();
+    assert!(published_on_operators.contains(&(SearchOperator::Between as i32).into()));
+    assert!(published_on_operators.contains(&(SearchOperator::IsNull as i32).into()));
+}
+
+#[test]
+fn openapi_components_drop_the_schema_keyword() {
+    let components = query_openapi_components("BookSearchQuery", &BOOK_COLUMN_MAP);
+    let schema = &components["components"]["schemas"]["BookSearchQuery"];
+
+    assert!(schema.get("$schema").is_none());
+    assert_eq!(schema["title"], "BookSearchQuery");
+}
+
+#[test]
+fn conditions_need_an_operator_or_an_operator_name() {
+    let schema = query_json_schema(&BOOK_COLUMN_MAP);
+    let title = condition_schema(&schema, "title");
+
+    assert_eq!(title["required"], json!(["field"]));
+    assert_eq!(
+        title["anyOf"],
+        json!([{ "required": ["operator"] }, { "required": ["operator_name"] }])
+    );
+}
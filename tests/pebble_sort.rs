@@ -0,0 +1,194 @@
+mod common;
+
+use common::{condition, ids, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query::{
+    try_query_to_select, try_query_to_select_with_policy, use_pebble_query,
+};
+use pebble_query::pebble_query_config::{PebbleFieldPolicy, PebbleSortCollation};
+use pebble_query::sample_dto_structs::{
+    SearchOperator, SearchQuery, SearchSortOption, SortDirection, SortNulls,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ActiveValue, DatabaseConnection, DbBackend, EntityTrait, QueryTrait};
+
+fn price_sort(order: SortDirection, nulls: SortNulls) -> Option<SearchSortOption> {
+    Some(SearchSortOption {
+        field: "price".to_string(),
+        order: order as i32,
+        nulls: nulls as i32,
+        ..Default::default()
+    })
+}
+
+fn sorted_sql(sort: Option<SearchSortOption>, backend: DbBackend) -> String {
+    let query = SearchQuery {
+        sort,
+        ..Default::default()
+    };
+    try_query_to_select(&query, &BOOK_COLUMN_MAP, book::Entity::find())
+        .unwrap()
+        .build(backend)
+        .to_string()
+}
+
+/// The ids of the books of author 1, sorted by price; book 3 has no price.
+async fn sorted_ids(db: &DatabaseConnection, sort: Option<SearchSortOption>) -> Vec<i32> {
+    let query = SearchQuery {
+        sort,
+        ..must_query(vec![condition("author_id", SearchOperator::Equals, "1")])
+    };
+    let result = use_pebble_query(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, db)
+        .await
+        .unwrap();
+    ids(&result)
+}
+
+#[tokio::test]
+async fn nulls_go_first_or_last_in_both_directions() {
+    let db = setup_db().await;
+    assert_eq!(
+        sorted_ids(&db, price_sort(SortDirection::Asc, SortNulls::First)).await,
+        vec![3, 1, 2]
+    );
+    assert_eq!(
+        sorted_ids(&db, price_sort(SortDirection::Asc, SortNulls::Last)).await,
+        vec![1, 2, 3]
+    );
+    assert_eq!(
+        sorted_ids(&db, price_sort(SortDirection::Desc, SortNulls::First)).await,
+        vec![3, 2, 1]
+    );
+    assert_eq!(
+        sorted_ids(&db, price_sort(SortDirection::Desc, SortNulls::Last)).await,
+        vec![2, 1, 3]
+    );
+}
+
+#[tokio::test]
+async fn unspecified_nulls_keep_the_database_default() {
+    let db = setup_db().await;
+    // SQLite puts NULLs first in ascending order.
+    assert_eq!(
+        sorted_ids(&db, price_sort(SortDirection::Asc, SortNulls::Unspecified)).await,
+        vec![3, 1, 2]
+    );
+    assert_eq!(
+        sorted_sql(
+            price_sort(SortDirection::Asc, SortNulls::Unspecified),
+            DbBackend::Postgres
+        ),
+        r#"SELECT "book"."id", "book"."title", "book"."publication_year", "book"."author_id", "book"."tenant_id", "book"."price", "book"."published_on", "book"."deleted_at", "book"."meta" FROM "book" ORDER BY "book"."price" ASC"#
+    );
+}
+
+#[test]
+fn nulls_ordering_is_written_for_each_backend() {
+    let sort = price_sort(SortDirection::Desc, SortNulls::Last);
+    assert!(sorted_sql(sort.clone(), DbBackend::Postgres)
+        .ends_with(r#"ORDER BY "book"."price" DESC NULLS LAST"#));
+    assert!(sorted_sql(sort.clone(), DbBackend::Sqlite)
+        .ends_with(r#"ORDER BY "book"."price" DESC NULLS LAST"#));
+    assert!(sorted_sql(sort, DbBackend::MySql)
+        .ends_with("ORDER BY `book`.`price` IS NULL ASC, `book`.`price` DESC"));
+}
+
+#[test]
+fn invalid_nulls_orderings_are_refused() {
+    let query = SearchQuery {
+        sort: Some(SearchSortOption {
+            field: "price".to_string(),
+            nulls: 42,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let error = try_query_to_select(&query, &BOOK_COLUMN_MAP, book::Entity::find())
+        .err()
+        .unwrap();
+    assert!(matches!(error, PebbleQueryError::InvalidOperator(_)));
+}
+
+fn case_insensitive_sort(field: &str) -> Option<SearchSortOption> {
+    Some(SearchSortOption {
+        field: field.to_string(),
+        case_insensitive: true,
+        ..Default::default()
+    })
+}
+
+/// The ids of the first three books.
+async fn first_ids(db: &DatabaseConnection, sort: Option<SearchSortOption>) -> Vec<i32> {
+    let query = SearchQuery {
+        sort,
+        length: 3,
+        ..Default::default()
+    };
+    let result = use_pebble_query(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, db)
+        .await
+        .unwrap();
+    ids(&result)
+}
+
+#[tokio::test]
+async fn case_insensitive_sorts_ignore_the_case_of_strings() {
+    let db = setup_db().await;
+    book::Entity::insert(book::ActiveModel {
+        id: ActiveValue::Set(9),
+        title: ActiveValue::Set("apple".to_string()),
+        publication_year: ActiveValue::Set(2000),
+        author_id: ActiveValue::Set(1),
+        tenant_id: ActiveValue::Set(1),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        first_ids(&db, sort("title", SortDirection::Asc)).await,
+        vec![1, 4, 6]
+    );
+    // "A Wizard of Earthsea", "apple", "Consider Phlebas".
+    assert_eq!(
+        first_ids(&db, case_insensitive_sort("title")).await,
+        vec![1, 9, 4]
+    );
+}
+
+#[test]
+fn case_insensitive_sorts_leave_the_other_columns_alone() {
+    assert!(
+        sorted_sql(case_insensitive_sort("title"), DbBackend::Sqlite)
+            .ends_with(r#"ORDER BY LOWER("book"."title") ASC"#)
+    );
+    assert!(
+        sorted_sql(case_insensitive_sort("publication_year"), DbBackend::Sqlite)
+            .ends_with(r#"ORDER BY "book"."publication_year" ASC"#)
+    );
+}
+
+#[test]
+fn collations_are_looked_up_by_the_normalized_field() {
+    let field_policy = PebbleFieldPolicy {
+        sort_collations: [(
+            "published_on".to_string(),
+            PebbleSortCollation::Collate("BINARY".to_string()),
+        )]
+        .into(),
+        ..Default::default()
+    };
+    let query = SearchQuery {
+        sort: sort("publishedOn", SortDirection::Desc),
+        ..Default::default()
+    };
+    let sql = try_query_to_select_with_policy(
+        &query,
+        &BOOK_COLUMN_MAP,
+        &field_policy,
+        book::Entity::find(),
+    )
+    .unwrap()
+    .build(DbBackend::Sqlite)
+    .to_string();
+    assert!(sql.ends_with(r#"ORDER BY "book"."published_on" COLLATE BINARY DESC"#));
+}
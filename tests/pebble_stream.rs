@@ -0,0 +1,131 @@
+mod common;
+
+use common::{condition, must_query, setup_db, sort};
+use futures::TryStreamExt;
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_query_limits::PebbleQueryLimits;
+use pebble_query::pebble_query_options::PebbleQueryOptions;
+use pebble_query::pebble_stream::{
+    use_pebble_query_metadata, use_pebble_query_metadata_with_config, use_pebble_query_stream,
+    use_pebble_query_stream_with_config,
+};
+use pebble_query::sample_dto_structs::{SearchOperator, SearchQuery, SortDirection};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait};
+
+struct RequestContext {
+    tenant_id: i32,
+}
+
+fn config() -> PebbleQueryConfig<book::Entity, RequestContext> {
+    PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+        .with_scope(|ctx: &RequestContext| {
+            Ok(Condition::all().add(book::Column::TenantId.eq(ctx.tenant_id)))
+        })
+        .with_soft_delete((book::Entity, book::Column::DeletedAt))
+}
+
+async fn streamed_ids(db: &DatabaseConnection, query: Option<SearchQuery>) -> Vec<i32> {
+    use_pebble_query_stream(book::Entity::find(), query, &BOOK_COLUMN_MAP, db)
+        .await
+        .unwrap()
+        .map_ok(|book| book.id)
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_stream_returns_the_rows_of_the_page() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        sort: sort("publication_year", SortDirection::Desc),
+        offset: 1,
+        length: 3,
+        ..must_query(vec![condition("author_id", SearchOperator::NotEquals, "1")])
+    };
+    assert_eq!(streamed_ids(&db, Some(query.clone())).await, vec![5, 4, 8]);
+
+    let metadata =
+        use_pebble_query_metadata(book::Entity::find(), Some(query), &BOOK_COLUMN_MAP, &db)
+            .await
+            .unwrap();
+    assert_eq!(metadata.result_total_items, 5);
+    assert_eq!(metadata.result_items, 3);
+}
+
+#[tokio::test]
+async fn find_one_streams_a_single_row() {
+    let db = setup_db().await;
+    let query = SearchQuery {
+        find_one: true,
+        sort: sort("id", SortDirection::Asc),
+        ..Default::default()
+    };
+    assert_eq!(streamed_ids(&db, Some(query)).await, vec![1]);
+}
+
+#[tokio::test]
+async fn the_config_variants_apply_scopes_and_soft_delete() {
+    let db = setup_db().await;
+    let ctx = RequestContext { tenant_id: 2 };
+    let query = SearchQuery {
+        sort: sort("id", SortDirection::Asc),
+        ..Default::default()
+    };
+
+    let ids: Vec<i32> = use_pebble_query_stream_with_config(
+        book::Entity::find(),
+        Some(query.clone()),
+        &config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap()
+    .map_ok(|book| book.id)
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(ids, vec![3, 7]);
+
+    let metadata = use_pebble_query_metadata_with_config(
+        book::Entity::find(),
+        Some(query),
+        &config(),
+        &ctx,
+        &db,
+    )
+    .await
+    .unwrap();
+    assert_eq!(metadata.result_total_items, 2);
+}
+
+#[tokio::test]
+async fn the_config_variants_check_the_limits() {
+    let db = setup_db().await;
+    let config = config().with_options(PebbleQueryOptions {
+        limits: PebbleQueryLimits {
+            max_page_length: Some(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let query = SearchQuery {
+        length: 1000,
+        ..Default::default()
+    };
+
+    let error = use_pebble_query_stream_with_config(
+        book::Entity::find(),
+        Some(query),
+        &config,
+        &RequestContext { tenant_id: 1 },
+        &db,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert!(matches!(error, PebbleQueryError::PageLengthTooLarge(_)));
+}
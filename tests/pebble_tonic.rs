@@ -0,0 +1,168 @@
+#![cfg(feature = "tonic")]
+
+mod common;
+
+use common::{condition, must_query, setup_db, sort};
+use pebble_query::errors::PebbleQueryError;
+use pebble_query::pebble_query_config::PebbleQueryConfig;
+use pebble_query::pebble_tonic::{
+    pebble_tonic_query, pebble_tonic_query_with_config, PebbleTonicResponse,
+};
+use pebble_query::sample_dto_structs::{
+    SearchOperator, SearchQuery, SearchResultMetadata, SortDirection,
+};
+use pebble_query::sample_entities::{book, BOOK_COLUMN_MAP};
+use sea_orm::sea_query::{Alias, Expr, Func};
+use sea_orm::{ColumnTrait, Condition, DbErr, EntityTrait};
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+struct BookMessage {
+    #[prost(int32, tag = "1")]
+    id: i32,
+    #[prost(string, tag = "2")]
+    title: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+struct SearchBooksResponse {
+    #[prost(message, optional, tag = "1")]
+    metadata: Option<SearchResultMetadata>,
+    #[prost(message, repeated, tag = "2")]
+    results: Vec<BookMessage>,
+}
+
+impl PebbleTonicResponse<BookMessage> for SearchBooksResponse {
+    fn from_pebble_result(metadata: SearchResultMetadata, results: Vec<BookMessage>) -> Self {
+        SearchBooksResponse {
+            metadata: Some(metadata),
+            results,
+        }
+    }
+}
+
+fn into_message(book: book::Model) -> BookMessage {
+    BookMessage {
+        id: book.id,
+        title: book.title,
+    }
+}
+
+#[tokio::test]
+async fn runs_the_request_and_converts_the_rows() {
+    let db = setup_db().await;
+    let request = tonic::Request::new(must_query(vec![condition(
+        "author_id",
+        SearchOperator::Equals,
+        "2",
+    )]));
+
+    let response: tonic::Response<SearchBooksResponse> = pebble_tonic_query(
+        request,
+        book::Entity::find(),
+        &BOOK_COLUMN_MAP,
+        &db,
+        into_message,
+    )
+    .await
+    .unwrap();
+
+    let response = response.into_inner();
+    let titles: Vec<&str> = response
+        .results
+        .iter()
+        .map(|book| book.title.as_str())
+        .collect();
+    assert_eq!(titles, vec!["Consider Phlebas", "The Player of Games"]);
+    assert_eq!(response.metadata.unwrap().result_total_items, 2);
+}
+
+#[tokio::test]
+async fn invalid_field_is_an_invalid_argument() {
+    let db = setup_db().await;
+    let request = tonic::Request::new(must_query(vec![condition(
+        "isbn",
+        SearchOperator::Equals,
+        "1",
+    )]));
+
+    let status = pebble_tonic_query::<_, _, _, _, SearchBooksResponse, _>(
+        request,
+        book::Entity::find(),
+        &BOOK_COLUMN_MAP,
+        &db,
+        into_message,
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn the_config_variant_applies_the_scopes_and_the_field_policy() {
+    let db = setup_db().await;
+    let config: PebbleQueryConfig<book::Entity, i32> =
+        PebbleQueryConfig::new(BOOK_COLUMN_MAP.clone())
+            .with_scope(|tenant_id: &i32| {
+                Ok(Condition::all().add(book::Column::TenantId.eq(*tenant_id)))
+            })
+            .with_computed_field(
+                "title_length",
+                Func::cust(Alias::new("LENGTH"))
+                    .arg(Expr::col((book::Entity, book::Column::Title))),
+            );
+    let request = tonic::Request::new(SearchQuery {
+        sort: sort("title_length", SortDirection::Desc),
+        ..Default::default()
+    });
+
+    let response: tonic::Response<SearchBooksResponse> = pebble_tonic_query_with_config(
+        request,
+        book::Entity::find(),
+        &config,
+        &2,
+        &db,
+        into_message,
+    )
+    .await
+    .unwrap();
+
+    let ids: Vec<i32> = response
+        .into_inner()
+        .results
+        .iter()
+        .map(|book| book.id)
+        .collect();
+    // "Parable of the Sower", "The Player of Games", "The Dispossessed".
+    assert_eq!(ids, vec![7, 5, 3]);
+}
+
+#[test]
+fn maps_errors_to_status_codes() {
+    let cases = [
+        (
+            PebbleQueryError::PageLengthTooLarge("".into()),
+            tonic::Code::OutOfRange,
+        ),
+        (
+            PebbleQueryError::ScopeError("".into()),
+            tonic::Code::PermissionDenied,
+        ),
+        (
+            PebbleQueryError::UnknownEntity("".into()),
+            tonic::Code::NotFound,
+        ),
+        (
+            DbErr::RecordNotFound("".into()).into(),
+            tonic::Code::NotFound,
+        ),
+        (
+            PebbleQueryError::SerializationError("".into()),
+            tonic::Code::Internal,
+        ),
+        (DbErr::Custom("".into()).into(), tonic::Code::Internal),
+    ];
+    for (err, code) in cases {
+        assert_eq!(tonic::Status::from(err).code(), code);
+    }
+}
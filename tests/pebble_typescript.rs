@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use pebble_query::pebble_query_result::PebbleQueryResultGeneric;
+use pebble_query::pebble_typescript::{dto_typescript_definitions, entity_fields_typescript};
+use pebble_query::sample_dto_structs::{
+    SearchCondition, SearchFilter, SearchOperator, SearchQuery, SearchRelation,
+    SearchResultMetadata, SearchSortOption,
+};
+use pebble_query::sample_entities::AUTHOR_COLUMN_MAP;
+use serde::Serialize;
+
+/// The fields of `interface` in the definitions, with whether they are optional.
+fn interface_fields(definitions: &str, interface: &str) -> BTreeMap<String, bool> {
+    let start = definitions
+        .find(&format!("export interface {}", interface))
+        .unwrap_or_else(|| panic!("interface {} is missing", interface));
+    let body = &definitions[start..];
+    let body = &body[body.find('{').unwrap() + 1..body.find("\n}").unwrap()];
+    body.lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .map(|(name, type_name)| {
+            let optional = name.ends_with('?');
+            assert_eq!(optional, type_name.contains("| null"), "{}", name);
+            (name.trim_end_matches('?').to_string(), optional)
+        })
+        .collect()
+}
+
+/// The keys of a DTO as serialized by serde, with whether they are optional, i.e. `null` by default.
+fn serde_fields<T: Serialize>(dto: &T) -> BTreeMap<String, bool> {
+    serde_json::to_value(dto)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .iter()
+        .map(|(key, value)| (key.clone(), value.is_null()))
+        .collect()
+}
+
+#[test]
+fn interfaces_match_the_dtos() {
+    let definitions = dto_typescript_definitions();
+    // `operator` and `operator_name` are the alternatives of the `SearchCondition` type.
+    let mut condition_fields = serde_fields(&SearchCondition::default());
+    condition_fields.remove("operator");
+    condition_fields.remove("operator_name");
+    let cases = [
+        ("SearchQuery", serde_fields(&SearchQuery::default())),
+        ("SearchRelation", serde_fields(&SearchRelation::default())),
+        ("SearchFilter", serde_fields(&SearchFilter::default())),
+        ("SearchConditionFields", condition_fields),
+        (
+            "SearchSortOption",
+            serde_fields(&SearchSortOption::default()),
+        ),
+        (
+            "SearchResultMetadata",
+            serde_fields(&SearchResultMetadata::default()),
+        ),
+        (
+            "PebbleQueryResultGeneric",
+            serde_fields(&PebbleQueryResultGeneric::<()> {
+                metadata: Default::default(),
+                results: vec![],
+            }),
+        ),
+    ];
+    for (interface, fields) in cases {
+        assert_eq!(
+            interface_fields(&definitions, interface),
+            fields,
+            "{}",
+            interface
+        );
+    }
+    assert!(!definitions.contains("unknown"));
+}
+
+#[test]
+fn conditions_name_an_operator_or_a_custom_operator() {
+    let definitions = dto_typescript_definitions();
+    assert!(definitions.contains(
+        "({ operator: SearchOperator; operator_name?: null } | { operator?: SearchOperator; operator_name: string })"
+    ));
+
+    let condition: SearchCondition =
+        serde_json::from_str(r#"{"field": "price", "operator_name": "within", "value_list": []}"#)
+            .unwrap();
+    assert_eq!(condition.operator, SearchOperator::Unspecified as i32);
+    assert_eq!(condition.operator_name.as_deref(), Some("within"));
+}
+
+#[test]
+fn operator_union_has_every_operator() {
+    let definitions = dto_typescript_definitions();
+    let start = definitions.find("export type SearchOperator =").unwrap();
+    let union = &definitions[start..start + definitions[start..].find(";").unwrap()];
+
+    let values: Vec<i32> = (0..=i32::from(u16::MAX))
+        .filter(|value| SearchOperator::try_from(*value).is_ok())
+        .collect();
+    assert_eq!(union.matches("  | ").count(), values.len());
+    for value in values {
+        assert!(union.contains(&format!("  | {} //", value)), "{}", value);
+    }
+}
+
+#[test]
+fn entity_fields_are_string_literal_unions() {
+    let definitions = entity_fields_typescript("author", &AUTHOR_COLUMN_MAP);
+
+    assert!(definitions.contains("export type AuthorFilterField = \"id\" | \"name\";"));
+    assert!(definitions.contains("export type AuthorSortField = \"id\" | \"name\";"));
+    assert!(definitions.contains("export interface AuthorSearchQuery extends Omit<SearchQuery"));
+}
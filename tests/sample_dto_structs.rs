@@ -0,0 +1,55 @@
+use pebble_query::sample_dto_structs::{
+    SearchCountAccuracy, SearchQuery, SearchResultMetadata, SortNulls,
+};
+
+#[test]
+fn queries_of_older_clients_deserialize() {
+    let query: SearchQuery = serde_json::from_str(
+        r#"{
+            "relation": null,
+            "sort": {"field": "title", "order": 1},
+            "offset": 0,
+            "length": 10,
+            "page": 0,
+            "filter": {"must": [], "any": []},
+            "find_one": false,
+            "find_all": false
+        }"#,
+    )
+    .unwrap();
+
+    assert!(!query.include_deleted);
+    assert!(!query.only_deleted);
+    assert_eq!(query.after, None);
+    assert!(query.include.is_empty());
+    assert!(query.distinct_on.is_empty());
+    assert!(query.group_by.is_empty());
+    let sort = query.sort.unwrap();
+    assert_eq!(sort.nulls, SortNulls::Unspecified as i32);
+    assert!(!sort.case_insensitive);
+}
+
+#[test]
+fn metadata_of_older_servers_deserialize() {
+    let metadata: SearchResultMetadata = serde_json::from_str(
+        r#"{
+            "result_items": 1,
+            "offset": 0,
+            "length": 10,
+            "page": 0,
+            "result_total_pages": 1,
+            "result_total_items": 1,
+            "query": null,
+            "filter_count": null,
+            "filter_reason": null
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(metadata.cache_hit, None);
+    assert_eq!(
+        metadata.total_items_accuracy,
+        SearchCountAccuracy::Unspecified as i32
+    );
+    assert_eq!(metadata.has_next_page, None);
+}